@@ -672,6 +672,29 @@ where
         }
     }
 
+    /// Copy image subresource range to buffer region.
+    pub fn copy_image_to_buffer(
+        &mut self,
+        src: &B::Image,
+        src_layout: gfx_hal::image::Layout,
+        dst: &B::Buffer,
+        regions: impl IntoIterator<Item = gfx_hal::command::BufferImageCopy>,
+    ) where
+        C: Supports<Transfer>,
+    {
+        self.capability.assert();
+
+        unsafe {
+            gfx_hal::command::RawCommandBuffer::copy_image_to_buffer(
+                self.inner.raw,
+                src,
+                src_layout,
+                dst,
+                regions,
+            )
+        }
+    }
+
     /// Copy image regions.
     pub fn copy_image(
         &mut self,
@@ -0,0 +1,78 @@
+use gfx_hal::{
+    format::{BufferFeature, Format, ImageFeature},
+    image::{Tiling, Usage},
+};
+
+/// Features supported by the device for particular format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FormatCapabilities {
+    /// Format these capabilities are queried for.
+    pub format: Format,
+
+    /// Features supported by images with linear tiling.
+    pub linear_tiling: ImageFeature,
+
+    /// Features supported by images with optimal tiling.
+    pub optimal_tiling: ImageFeature,
+
+    /// Features supported by buffers.
+    pub buffer_features: BufferFeature,
+}
+
+impl FormatCapabilities {
+    /// Get features supported by images with specified tiling.
+    pub fn image_features(&self, tiling: Tiling) -> ImageFeature {
+        match tiling {
+            Tiling::Linear => self.linear_tiling,
+            Tiling::Optimal => self.optimal_tiling,
+        }
+    }
+
+    /// Check if images with specified tiling support all `features`.
+    pub fn supports(&self, tiling: Tiling, features: ImageFeature) -> bool {
+        self.image_features(tiling).contains(features)
+    }
+
+    /// Get image usage flags allowed by the features of images with specified tiling.
+    pub fn supported_usage(&self, tiling: Tiling) -> Usage {
+        let features = self.image_features(tiling);
+        if features.is_empty() {
+            return Usage::empty();
+        }
+
+        // Transfer operations are supported for any format usable by images.
+        let mut usage = Usage::TRANSFER_SRC | Usage::TRANSFER_DST;
+        if features.contains(ImageFeature::SAMPLED) {
+            usage |= Usage::SAMPLED;
+        }
+        if features.contains(ImageFeature::STORAGE) {
+            usage |= Usage::STORAGE;
+        }
+        if features.contains(ImageFeature::COLOR_ATTACHMENT) {
+            usage |= Usage::COLOR_ATTACHMENT | Usage::INPUT_ATTACHMENT;
+        }
+        if features.contains(ImageFeature::DEPTH_STENCIL_ATTACHMENT) {
+            usage |= Usage::DEPTH_STENCIL_ATTACHMENT | Usage::INPUT_ATTACHMENT;
+        }
+        usage
+    }
+}
+
+/// Get image features required for image usage.
+pub fn usage_features(usage: Usage) -> ImageFeature {
+    let mut features = ImageFeature::empty();
+    if usage.contains(Usage::SAMPLED) {
+        features |= ImageFeature::SAMPLED;
+    }
+    if usage.contains(Usage::STORAGE) {
+        features |= ImageFeature::STORAGE;
+    }
+    if usage.contains(Usage::COLOR_ATTACHMENT) {
+        features |= ImageFeature::COLOR_ATTACHMENT;
+    }
+    if usage.contains(Usage::DEPTH_STENCIL_ATTACHMENT) {
+        features |= ImageFeature::DEPTH_STENCIL_ATTACHMENT;
+    }
+    features
+}
@@ -2,7 +2,7 @@ use std::cmp::min;
 
 use crate::{
     command::FamilyId,
-    memory::{DynamicConfig, HeapsConfig, LinearConfig},
+    memory::{BuddyConfig, DynamicConfig, HeapsConfig, LinearConfig},
     util::DeviceId,
 };
 
@@ -156,6 +156,7 @@ unsafe impl HeapsConfigure for BasicHeapsConfigure {
         self,
         properties: &gfx_hal::adapter::MemoryProperties,
     ) -> (Self::Types, Self::Heaps) {
+        let _4kb = 4 * 1024;
         let _16mb = 16 * 1024 * 1024;
         let _256mb = 256 * 1024 * 1024;
 
@@ -189,6 +190,16 @@ unsafe impl HeapsConfigure for BasicHeapsConfigure {
                             (properties.memory_heaps[mt.heap_index] / 8 - 1).next_power_of_two(),
                         ),
                     }),
+                    buddy: Some(BuddyConfig {
+                        min_block_size: min(
+                            _4kb,
+                            (properties.memory_heaps[mt.heap_index] / 1024 - 1).next_power_of_two(),
+                        ),
+                        chunk_size: min(
+                            _256mb,
+                            (properties.memory_heaps[mt.heap_index] / 8 - 1).next_power_of_two(),
+                        ),
+                    }),
                 };
 
                 (mt.properties, mt.heap_index as u32, config)
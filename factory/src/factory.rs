@@ -1,11 +1,14 @@
 use {
     crate::{
+        capabilities::{usage_features, FormatCapabilities},
         command::{
-            families_from_device, CommandPool, Families, Family, FamilyId, Fence, QueueType, Reset,
+            families_from_device, CommandPool, Families, Family, FamilyId, Fence, IndividualReset,
+            OneShot, QueueType, Reset, Submission, Transfer,
         },
         config::{Config, DevicesConfigure, HeapsConfigure, QueuesConfigure},
         descriptor::DescriptorAllocator,
-        memory::{self, Heaps, MemoryUsage, TotalMemoryUtilization, Write},
+        memory::{self, Heaps, MemoryUsage, TotalMemoryUtilization},
+        recorder::MemoryUtilizationRecorder,
         resource::*,
         upload::{BufferState, ImageState, ImageStateOrLayout, Uploader},
         util::{Device, DeviceId, Instance},
@@ -84,6 +87,7 @@ pub struct Factory<B: Backend> {
     epochs: Vec<parking_lot::RwLock<Vec<u64>>>,
     uploader: Uploader<B>,
     families_indices: Vec<usize>,
+    memory_recorder: Option<MemoryUtilizationRecorder>,
     #[derivative(Debug = "ignore")]
     device: Device<B>,
     #[derivative(Debug = "ignore")]
@@ -250,6 +254,85 @@ where
         )
     }
 
+    /// Check if image with `info` can be created.
+    ///
+    /// Returns `info` with sample count reduced to the highest supported value
+    /// if requested number of samples is not supported, logging a warning.
+    /// Returns `None` if format doesn't support requested usage,
+    /// view capabilities, number of layers or extent.
+    pub fn supported_image_info(&self, mut info: ImageInfo) -> Option<ImageInfo> {
+        let props = self.image_format_properties(info)?;
+        let extent = info.kind.extent();
+        if props.max_layers < info.kind.num_layers()
+            || props.max_extent.width < extent.width
+            || props.max_extent.height < extent.height
+            || props.max_extent.depth < extent.depth
+        {
+            return None;
+        }
+
+        if let Kind::D2(_, _, _, samples) = &mut info.kind {
+            let requested = *samples;
+            while *samples > 1 && *samples & props.sample_count_mask != *samples {
+                *samples >>= 1;
+            }
+            if *samples != requested {
+                log::warn!(
+                    "{} samples are not supported for image with format {:?}. Using {} samples",
+                    requested,
+                    info.format,
+                    *samples
+                );
+            }
+        }
+        Some(info)
+    }
+
+    /// Pick first format from `candidates` that can be used for image with `info`.
+    ///
+    /// Returns `info` with picked format, adjusted as by [`supported_image_info`].
+    ///
+    /// [`supported_image_info`]: #method.supported_image_info
+    pub fn pick_image_format(
+        &self,
+        candidates: impl IntoIterator<Item = format::Format>,
+        info: ImageInfo,
+    ) -> Option<ImageInfo> {
+        candidates
+            .into_iter()
+            .filter_map(|format| self.supported_image_info(ImageInfo { format, ..info }))
+            .next()
+    }
+
+    /// Fetch features supported by the device for `format`.
+    pub fn format_capabilities(&self, format: format::Format) -> FormatCapabilities {
+        let properties = self.physical().format_properties(Some(format));
+        FormatCapabilities {
+            format,
+            linear_tiling: properties.linear_tiling,
+            optimal_tiling: properties.optimal_tiling,
+            buffer_features: properties.buffer_features,
+        }
+    }
+
+    /// Pick first format from `candidates` that supports `usage` for images with `tiling`.
+    ///
+    /// Unlike [`pick_image_format`] this function checks only format features
+    /// and doesn't take image kind, extent or view capabilities into account.
+    ///
+    /// [`pick_image_format`]: #method.pick_image_format
+    pub fn pick_format(
+        &self,
+        candidates: impl IntoIterator<Item = format::Format>,
+        tiling: image::Tiling,
+        usage: image::Usage,
+    ) -> Option<format::Format> {
+        let features = usage_features(usage);
+        candidates
+            .into_iter()
+            .find(|&format| self.format_capabilities(format).supports(tiling, features))
+    }
+
     /// Create an image view with the specified properties
     ///
     /// This function returns relevant value, that is, the value cannot be dropped.
@@ -298,11 +381,8 @@ where
     /// However sampler can be destroyed using [`destroy_relevant_sampler`] function.
     ///
     /// [`destroy_relevant_sampler`]: #method.destroy_relevant_sampler
-    pub fn create_relevant_sampler(
-        &self,
-        info: SamplerInfo,
-    ) -> Result<Sampler<B>, AllocationError> {
-        Sampler::create(&self.device, info)
+    pub fn create_relevant_sampler(&self, info: SamplerInfo) -> Result<Sampler<B>, SamplerError> {
+        Sampler::create_validated(&self.device, info, &self.sampler_limits())
     }
 
     /// Destroy sampler.
@@ -327,7 +407,7 @@ where
     /// This function (unlike [`create_relevant_sampler`]) returns value that can be dropped.
     ///
     /// [`create_relevant_sampler`]: #method.create_relevant_sampler
-    pub fn create_sampler(&self, info: SamplerInfo) -> Result<Escape<Sampler<B>>, AllocationError> {
+    pub fn create_sampler(&self, info: SamplerInfo) -> Result<Escape<Sampler<B>>, SamplerError> {
         let sampler = self.create_relevant_sampler(info)?;
         Ok(self.resources.samplers.escape(sampler))
     }
@@ -338,7 +418,7 @@ where
     ///
     /// [`create_sampler`]: #method.create_sampler
     /// [`create_relevant_sampler`]: #method.create_relevant_sampler
-    pub fn get_sampler(&self, info: SamplerInfo) -> Result<Handle<Sampler<B>>, AllocationError> {
+    pub fn get_sampler(&self, info: SamplerInfo) -> Result<Handle<Sampler<B>>, SamplerError> {
         let samplers = &self.resources.samplers;
         let device = &self.device;
 
@@ -346,10 +426,72 @@ where
             self.resources.samplers_cache.upgradable_read(),
             parking_lot::RwLockUpgradableReadGuard::upgrade,
             info.clone(),
-            || Ok(samplers.handle(Sampler::create(device, info)?)),
+            // Limits are queried only when sampler is not found in cache.
+            || {
+                let limits = self.sampler_limits();
+                Ok(samplers.handle(Sampler::create_validated(device, info, &limits)?))
+            },
         )
     }
 
+    /// Get device capabilities against which sampler infos are validated.
+    pub fn sampler_limits(&self) -> SamplerLimits {
+        SamplerLimits {
+            anisotropy: self
+                .physical()
+                .features()
+                .contains(Features::SAMPLER_ANISOTROPY),
+            max_anisotropy: self.physical().limits().max_sampler_anisotropy,
+            // Comparison samplers are only usable with sampled depth images.
+            comparison: [
+                format::Format::D16Unorm,
+                format::Format::X8D24Unorm,
+                format::Format::D32Float,
+            ]
+            .iter()
+            .any(|&format| {
+                self.physical()
+                    .format_properties(Some(format))
+                    .optimal_tiling
+                    .contains(format::ImageFeature::SAMPLED)
+            }),
+        }
+    }
+
+    /// Get statistics of the sampler cache used by [`get_sampler`].
+    ///
+    /// [`get_sampler`]: #method.get_sampler
+    pub fn sampler_cache_stats(&self) -> SamplerCacheStats {
+        self.resources.samplers_cache.read().stats()
+    }
+
+    /// Get typed writer for `len` elements of the buffer bound to host visible memory
+    /// starting from `offset` in bytes.
+    ///
+    /// Whole buffer stays mapped after this call,
+    /// so writers can be fetched repeatedly without remapping memory.
+    /// Written elements will be automatically flushed when writer is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if buffer size is less than `offset` + size of `len` elements.
+    ///
+    /// # Safety
+    ///
+    /// Caller must ensure that device doesn't use memory region that being updated.
+    pub unsafe fn buffer_writer<'a, T>(
+        &'a self,
+        buffer: &'a mut Buffer<B>,
+        offset: u64,
+        len: usize,
+    ) -> Result<BufferWriter<'a, B, T>, failure::Error>
+    where
+        T: Copy,
+    {
+        let non_coherent_atom_size = self.physical().limits().non_coherent_atom_size as u64;
+        Ok(buffer.writer(&self.device, offset, len, non_coherent_atom_size)?)
+    }
+
     /// Update content of the buffer bound to host visible memory.
     /// This function (unlike [`upload_buffer`]) update content immediatelly.
     ///
@@ -379,10 +521,11 @@ where
             content.len() * std::mem::size_of::<T>(),
         );
 
-        let mut mapped = buffer.map(&self.device, offset..offset + content.len() as u64)?;
-        mapped
-            .write(&self.device, 0..content.len() as u64)?
-            .write(content);
+        if !content.is_empty() {
+            let mut writer = self.buffer_writer(buffer, offset, content.len())?;
+            writer.write(0, content);
+            writer.flush()?;
+        }
         Ok(())
     }
 
@@ -469,13 +612,17 @@ where
         assert_eq!(image.format().surface_desc().aspects, image_layers.aspects);
         assert!(image_layers.layers.start <= image_layers.layers.end);
         assert!(image_layers.layers.end <= image.kind().num_layers());
-        assert!(image_layers.level <= image.info().levels);
+        assert!(image_layers.level < image.info().levels);
 
         let content_size = content.len() as u64 * std::mem::size_of::<T>() as u64;
         let format_desc = image.format().surface_desc();
-        let texels_count = (image_extent.width / format_desc.dim.0 as u32) as u64
-            * (image_extent.height / format_desc.dim.1 as u32) as u64
-            * image_extent.depth as u64;
+        // Block-compressed mip levels may be smaller than single block.
+        let texels_count = ((image_extent.width + format_desc.dim.0 as u32 - 1)
+            / format_desc.dim.0 as u32) as u64
+            * ((image_extent.height + format_desc.dim.1 as u32 - 1) / format_desc.dim.1 as u32)
+                as u64
+            * image_extent.depth as u64
+            * (image_layers.layers.end - image_layers.layers.start) as u64;
         let total_bytes = (format_desc.bits as u64 / 8) * texels_count;
         assert_eq!(
             total_bytes, content_size,
@@ -506,6 +653,182 @@ where
         )
     }
 
+    /// Transition newly created image into `next` state without uploading content.
+    ///
+    /// Content of the image stays undefined.
+    /// Transition will happen before all operations that will be submitted
+    /// after next [`flush_uploads`] or [`maintain`] call for this `Factory`.
+    ///
+    /// # Safety
+    ///
+    /// Image must be created by this `Factory` and must not be used by device yet.
+    ///
+    /// [`flush_uploads`]: #method.flush_uploads
+    /// [`maintain`]: #method.maintain
+    pub unsafe fn transition_image(
+        &self,
+        image: &Image<B>,
+        image_range: image::SubresourceRange,
+        next: ImageState,
+    ) -> Result<(), failure::Error> {
+        self.uploader
+            .transition_image(&self.device, image, image_range, next)
+    }
+
+    /// Read image layers content back to the host.
+    ///
+    /// Unlike [`upload_image`] this function is synchronous.
+    /// Copy operation is submitted to the queue of the `next` state immediately
+    /// and this function waits for it to complete.
+    ///
+    /// Content is returned as raw bytes of tightly packed texels (or blocks) of the region.
+    ///
+    /// # Safety
+    ///
+    /// Image must be created by this `Factory`.
+    /// All operations writing to the image must be submitted before this call
+    /// and `last` state must match the last usage state of the image.
+    /// Image is left in the `next` state.
+    ///
+    /// [`upload_image`]: #method.upload_image
+    // Arguments mirror `upload_image`, with `families` to submit the copy.
+    #[allow(clippy::too_many_arguments)]
+    pub unsafe fn download_image(
+        &self,
+        families: &mut Families<B>,
+        image: &Image<B>,
+        image_layers: SubresourceLayers,
+        image_offset: image::Offset,
+        image_extent: Extent,
+        last: ImageState,
+        next: ImageState,
+    ) -> Result<Vec<u8>, failure::Error> {
+        assert!(image.info().usage.contains(image::Usage::TRANSFER_SRC));
+        assert_eq!(image.format().surface_desc().aspects, image_layers.aspects);
+        assert!(image_layers.layers.start <= image_layers.layers.end);
+        assert!(image_layers.layers.end <= image.kind().num_layers());
+        assert!(image_layers.level < image.info().levels);
+
+        if last.queue != next.queue {
+            failure::bail!("Can't sync resources across queues");
+        }
+
+        let format_desc = image.format().surface_desc();
+        let (block_width, block_height) = (format_desc.dim.0 as u32, format_desc.dim.1 as u32);
+        let data_width = (image_extent.width + block_width - 1) / block_width * block_width;
+        let data_height = (image_extent.height + block_height - 1) / block_height * block_height;
+        let total_bytes = (format_desc.bits as u64 / 8)
+            * (data_width / block_width) as u64
+            * (data_height / block_height) as u64
+            * image_extent.depth as u64
+            * (image_layers.layers.end - image_layers.layers.start) as u64;
+
+        let mut staging = self.create_buffer(
+            BufferInfo {
+                size: total_bytes,
+                usage: buffer::Usage::TRANSFER_DST,
+            },
+            memory::Download,
+        )?;
+
+        let image_range = image::SubresourceRange {
+            aspects: image_layers.aspects,
+            levels: image_layers.level..image_layers.level + 1,
+            layers: image_layers.layers.clone(),
+        };
+
+        let fence = self.device.create_fence(false)?;
+        let family = families.family_mut(next.queue.family);
+        let pool = match family.create_pool::<IndividualReset>(&self.device) {
+            Ok(pool) => pool,
+            Err(err) => {
+                self.device.destroy_fence(fence);
+                return Err(err.into());
+            }
+        };
+        let mut pool = match pool.with_capability::<Transfer>() {
+            Ok(pool) => pool,
+            Err(pool) => {
+                pool.dispose(&self.device);
+                self.device.destroy_fence(fence);
+                failure::bail!("Queue family doesn't support transfer operations");
+            }
+        };
+
+        let mut command_buffer = pool.allocate_buffers(1).pop().unwrap().begin(OneShot, ());
+        {
+            let mut encoder = command_buffer.encoder();
+            encoder.pipeline_barrier(
+                last.stage..gfx_hal::pso::PipelineStage::TRANSFER,
+                gfx_hal::memory::Dependencies::empty(),
+                Some(gfx_hal::memory::Barrier::Image {
+                    states: (last.access, last.layout)
+                        ..(
+                            image::Access::TRANSFER_READ,
+                            image::Layout::TransferSrcOptimal,
+                        ),
+                    target: image.raw(),
+                    families: None,
+                    range: image_range.clone(),
+                }),
+            );
+
+            encoder.copy_image_to_buffer(
+                image.raw(),
+                image::Layout::TransferSrcOptimal,
+                staging.raw(),
+                Some(gfx_hal::command::BufferImageCopy {
+                    buffer_offset: 0,
+                    buffer_width: data_width,
+                    buffer_height: data_height,
+                    image_layers,
+                    image_offset,
+                    image_extent,
+                }),
+            );
+
+            encoder.pipeline_barrier(
+                gfx_hal::pso::PipelineStage::TRANSFER..next.stage,
+                gfx_hal::memory::Dependencies::empty(),
+                Some(gfx_hal::memory::Barrier::Image {
+                    states: (
+                        image::Access::TRANSFER_READ,
+                        image::Layout::TransferSrcOptimal,
+                    )..(next.access, next.layout),
+                    target: image.raw(),
+                    families: None,
+                    range: image_range,
+                }),
+            );
+        }
+
+        let (submit, command_buffer) = command_buffer.finish().submit_once();
+        family
+            .queue_mut(next.queue.index)
+            .submit_raw_fence(Some(Submission::new().submits(Some(submit))), Some(&fence));
+        let waited = self.device.wait_for_fence(&fence, !0);
+        if !matches!(waited, Ok(true)) {
+            // Command buffer may still be pending. Wait for the whole device
+            // so that resources below can be released safely.
+            if let Err(err) = self.device.wait_idle() {
+                log::error!("Failed to wait for device idle: {:?}", err);
+            }
+        }
+        self.device.destroy_fence(fence);
+        pool.free_buffers(Some(command_buffer.mark_complete()));
+        pool.dispose(&self.device);
+
+        if !waited? {
+            failure::bail!("Timeout waiting for image download");
+        }
+
+        let non_coherent_atom_size = self.physical().limits().non_coherent_atom_size as u64;
+        let mut mapping = staging.map(&self.device, 0..total_bytes)?;
+        mapping.invalidate(&self.device, 0..total_bytes, non_coherent_atom_size)?;
+        let content = mapping.read::<u8>(&self.device, 0..total_bytes)?.to_vec();
+        Ok(content)
+    }
+
     /// Create rendering surface from window.
     pub fn create_surface(&mut self, window: std::sync::Arc<winit::Window>) -> Surface<B> {
         Surface::new(&self.instance, window)
@@ -531,12 +854,43 @@ where
 
     /// Get surface format.
     ///
+    /// 8 bit sRGB formats usable as color attachment are preferred.
+    /// Otherwise ideal format reported by the surface is returned.
+    ///
     /// # Panics
     ///
     /// Panics if `surface` was not created by this `Factory`
     pub fn get_surface_format(&self, surface: &Surface<B>) -> format::Format {
+        self.pick_surface_format(
+            surface,
+            vec![format::Format::Bgra8Srgb, format::Format::Rgba8Srgb],
+        )
+        .unwrap_or_else(|| unsafe { surface.format(&self.adapter.physical_device) })
+    }
+
+    /// Pick first format from `candidates` that is supported by the surface
+    /// and can be used as color attachment.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `surface` was not created by this `Factory`
+    pub fn pick_surface_format(
+        &self,
+        surface: &Surface<B>,
+        candidates: impl IntoIterator<Item = format::Format>,
+    ) -> Option<format::Format> {
         surface.assert_instance_owner(&self.instance);
-        unsafe { surface.format(&self.adapter.physical_device) }
+        let (_, formats, _, _) = unsafe { surface.compatibility(&self.adapter.physical_device) };
+        candidates.into_iter().find(|&format| {
+            // `None` means that surface supports any format.
+            formats
+                .as_ref()
+                .map_or(true, |formats| formats.contains(&format))
+                && self.format_capabilities(format).supports(
+                    image::Tiling::Optimal,
+                    format::ImageFeature::COLOR_ATTACHMENT,
+                )
+        })
     }
 
     /// Destroy surface returning underlying window back to the caller.
@@ -550,6 +904,7 @@ where
     }
 
     /// Create target out of rendering surface.
+    /// Format is chosen with [`get_surface_format`].
     ///
     /// The compatibility of the surface with the queue family which will present to
     /// this target must have *already* been checked using `Factory::surface_support`.
@@ -557,17 +912,44 @@ where
     /// # Panics
     ///
     /// Panics if `surface` was not created by this `Factory`.
+    ///
+    /// [`get_surface_format`]: #method.get_surface_format
     pub fn create_target(
         &self,
         surface: Surface<B>,
         image_count: u32,
         present_mode: gfx_hal::PresentMode,
         usage: image::Usage,
+    ) -> Result<Target<B>, failure::Error> {
+        let format = self.get_surface_format(&surface);
+        self.create_target_with_format(surface, format, image_count, present_mode, usage)
+    }
+
+    /// Create target out of rendering surface with specified format.
+    /// `format` must be supported by the surface,
+    /// use [`pick_surface_format`] to choose one.
+    ///
+    /// The compatibility of the surface with the queue family which will present to
+    /// this target must have *already* been checked using `Factory::surface_support`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `surface` was not created by this `Factory`.
+    ///
+    /// [`pick_surface_format`]: #method.pick_surface_format
+    pub fn create_target_with_format(
+        &self,
+        surface: Surface<B>,
+        format: format::Format,
+        image_count: u32,
+        present_mode: gfx_hal::PresentMode,
+        usage: image::Usage,
     ) -> Result<Target<B>, failure::Error> {
         unsafe {
-            surface.into_target(
+            surface.into_target_with_format(
                 &self.adapter.physical_device,
                 &self.device,
+                format,
                 image_count,
                 present_mode,
                 usage,
@@ -817,6 +1199,13 @@ where
     pub fn maintain(&mut self, families: &mut Families<B>) {
         self.flush_uploads(families);
         self.cleanup(families);
+
+        if let Some(recorder) = &mut self.memory_recorder {
+            let utilization = self.heaps.get_mut().utilization();
+            if let Err(error) = recorder.record(utilization) {
+                log::error!("Failed to record memory utilization: {}", error);
+            }
+        }
     }
 
     /// Create descriptor set layout with specified bindings.
@@ -891,6 +1280,18 @@ where
     pub fn memory_utilization(&self) -> TotalMemoryUtilization {
         self.heaps.lock().utilization()
     }
+
+    /// Install recorder that will sample memory utilization on each [`maintain`] call.
+    /// Passing `None` stops recording.
+    /// Returns previously installed recorder.
+    ///
+    /// [`maintain`]: #method.maintain
+    pub fn record_memory_utilization(
+        &mut self,
+        recorder: Option<MemoryUtilizationRecorder>,
+    ) -> Option<MemoryUtilizationRecorder> {
+        std::mem::replace(&mut self.memory_recorder, recorder)
+    }
 }
 
 #[doc(hidden)]
@@ -1063,6 +1464,7 @@ where
         resources: ManuallyDrop::new(ResourceHub::default()),
         uploader: unsafe { Uploader::new(&device, &families) }?,
         families_indices: families.indices().into(),
+        memory_recorder: None,
         epochs,
         device,
         adapter,
@@ -17,8 +17,10 @@ use rendy_resource as resource;
 use rendy_util as util;
 use rendy_wsi as wsi;
 
+mod capabilities;
 mod config;
 mod factory;
+mod recorder;
 mod upload;
 
-pub use crate::{config::*, factory::*, upload::*};
+pub use crate::{capabilities::*, config::*, factory::*, recorder::*, upload::*};
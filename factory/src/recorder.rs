@@ -0,0 +1,165 @@
+use {
+    crate::memory::{MemoryUtilization, TotalMemoryUtilization},
+    std::{io::Write, time::Instant},
+};
+
+/// Format of the memory utilization timeline.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoryTimelineFormat {
+    /// Comma separated values.
+    /// One row per heap, memory type and allocator for each sample.
+    /// Columns are `sample,time,scope,heap,type,kind,properties,size,used,effective,chunks,fragmentation`.
+    Csv,
+
+    /// JSON lines.
+    /// One serialized [`MemoryUtilizationSample`] per line.
+    ///
+    /// [`MemoryUtilizationSample`]: struct.MemoryUtilizationSample.html
+    #[cfg(feature = "serde-1")]
+    Json,
+}
+
+/// Memory utilization snapshot taken by [`MemoryUtilizationRecorder`].
+///
+/// [`MemoryUtilizationRecorder`]: struct.MemoryUtilizationRecorder.html
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemoryUtilizationSample {
+    /// Index of the sample.
+    pub sample: u64,
+
+    /// Time in seconds since recorder creation.
+    pub time: f64,
+
+    /// Memory utilization.
+    pub utilization: TotalMemoryUtilization,
+}
+
+/// Records memory utilization timeline.
+/// Recorder installed into `Factory` with [`record_memory_utilization`]
+/// samples utilization on each [`maintain`] call.
+///
+/// [`record_memory_utilization`]: struct.Factory.html#method.record_memory_utilization
+/// [`maintain`]: struct.Factory.html#method.maintain
+#[derive(derivative::Derivative)]
+#[derivative(Debug)]
+pub struct MemoryUtilizationRecorder {
+    #[derivative(Debug = "ignore")]
+    writer: Box<dyn Write + Send + Sync>,
+    format: MemoryTimelineFormat,
+    start: Instant,
+    samples: u64,
+}
+
+impl MemoryUtilizationRecorder {
+    /// Create new recorder that writes timeline in specified `format` into `writer`.
+    pub fn new(writer: impl Write + Send + Sync + 'static, format: MemoryTimelineFormat) -> Self {
+        MemoryUtilizationRecorder {
+            writer: Box::new(writer),
+            format,
+            start: Instant::now(),
+            samples: 0,
+        }
+    }
+
+    /// Get number of samples recorded.
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    /// Record memory utilization sample.
+    pub fn record(&mut self, utilization: TotalMemoryUtilization) -> std::io::Result<()> {
+        let elapsed = self.start.elapsed();
+        let sample = MemoryUtilizationSample {
+            sample: self.samples,
+            time: elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 * 1e-9,
+            utilization,
+        };
+
+        match self.format {
+            MemoryTimelineFormat::Csv => {
+                if self.samples == 0 {
+                    writeln!(
+                        self.writer,
+                        "sample,time,scope,heap,type,kind,properties,size,used,effective,chunks,fragmentation"
+                    )?;
+                }
+                write_csv(&mut self.writer, &sample)?;
+            }
+            #[cfg(feature = "serde-1")]
+            MemoryTimelineFormat::Json => {
+                serde_json::to_writer(&mut self.writer, &sample)?;
+                writeln!(self.writer)?;
+            }
+        }
+
+        self.samples += 1;
+        Ok(())
+    }
+
+    /// Flush underlying writer.
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+fn write_csv(writer: &mut impl Write, sample: &MemoryUtilizationSample) -> std::io::Result<()> {
+    let MemoryUtilizationSample {
+        sample,
+        time,
+        ref utilization,
+    } = *sample;
+
+    for (index, heap) in utilization.heaps.iter().enumerate() {
+        let MemoryUtilization { used, effective } = heap.utilization;
+        writeln!(
+            writer,
+            "{},{:.6},heap,{},,,,{},{},{},,{:.4}",
+            sample,
+            time,
+            index,
+            heap.size,
+            used,
+            effective,
+            heap.utilization.fragmentation(),
+        )?;
+    }
+
+    for (index, ty) in utilization.types.iter().enumerate() {
+        // Properties are formatted as `A | B` which is safe to put into CSV cell.
+        let MemoryUtilization { used, effective } = ty.utilization;
+        writeln!(
+            writer,
+            "{},{:.6},type,{},{},,{:?},,{},{},,{:.4}",
+            sample,
+            time,
+            ty.heap_index,
+            index,
+            ty.properties,
+            used,
+            effective,
+            ty.utilization.fragmentation(),
+        )?;
+    }
+
+    for allocator in &utilization.allocators {
+        let ty = &utilization.types[allocator.type_index];
+        let MemoryUtilization { used, effective } = allocator.utilization;
+        writeln!(
+            writer,
+            "{},{:.6},allocator,{},{},{:?},{:?},,{},{},{},{:.4}",
+            sample,
+            time,
+            ty.heap_index,
+            allocator.type_index,
+            allocator.kind,
+            ty.properties,
+            used,
+            effective,
+            allocator.chunks,
+            allocator.utilization.fragmentation(),
+        )?;
+    }
+
+    Ok(())
+}
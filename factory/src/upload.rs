@@ -341,6 +341,44 @@ where
         Ok(())
     }
 
+    /// Transition image from `Undefined` layout into `next` state
+    /// without uploading any content.
+    ///
+    /// # Safety
+    ///
+    /// `device` must be the same that was used to create this `Uploader`.
+    /// `image` must belong to the `device`.
+    ///
+    pub(crate) unsafe fn transition_image(
+        &self,
+        device: &Device<B>,
+        image: &Image<B>,
+        image_range: gfx_hal::image::SubresourceRange,
+        next: ImageState,
+    ) -> Result<(), failure::Error> {
+        let mut family_uploads = self.family_uploads[next.queue.family.index]
+            .as_ref()
+            .unwrap()
+            .lock();
+        let next_upload = family_uploads.next_upload(device, next.queue.index)?;
+
+        next_upload.command_buffer.encoder().pipeline_barrier(
+            gfx_hal::pso::PipelineStage::TOP_OF_PIPE..next.stage,
+            gfx_hal::memory::Dependencies::empty(),
+            Some(gfx_hal::memory::Barrier::Image {
+                states: (
+                    gfx_hal::image::Access::empty(),
+                    gfx_hal::image::Layout::Undefined,
+                )..(next.access, next.layout),
+                target: image.raw(),
+                families: None,
+                range: image_range,
+            }),
+        );
+
+        Ok(())
+    }
+
     /// Cleanup pending updates.
     ///
     /// # Safety
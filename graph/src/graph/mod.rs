@@ -46,7 +46,13 @@ impl<B: Backend> GraphContext<B> {
         factory: &Factory<B>,
         chains: &chain::Chains,
         buffers: impl IntoIterator<Item = &'a BufferInfo>,
-        images: impl IntoIterator<Item = &'a (ImageInfo, Option<gfx_hal::command::ClearValue>)>,
+        images: impl IntoIterator<
+            Item = &'a (
+                ImageInfo,
+                Vec<gfx_hal::format::Format>,
+                Option<gfx_hal::command::ClearValue>,
+            ),
+        >,
     ) -> Result<Self, failure::Error> {
         log::trace!("Allocate buffers");
         let buffers: Vec<Option<Handle<Buffer<B>>>> = buffers
@@ -75,19 +81,27 @@ impl<B: Backend> GraphContext<B> {
         let images: Vec<Option<(Handle<Image<B>>, _)>> = images
             .into_iter()
             .enumerate()
-            .map(|(index, (info, clear))| {
+            .map(|(index, (info, formats, clear))| {
                 chains
                     .images
                     .get(&chain::Id(index))
                     .map(|image| {
+                        let info = ImageInfo {
+                            usage: image.usage(),
+                            ..info.clone()
+                        };
+                        let format = factory
+                            .pick_image_format(formats.iter().cloned(), info)
+                            .ok_or_else(|| {
+                                failure::format_err!(
+                                    "None of formats {:?} is supported for image {:?}",
+                                    formats,
+                                    info
+                                )
+                            })?
+                            .format;
                         factory
-                            .create_image(
-                                ImageInfo {
-                                    usage: image.usage(),
-                                    ..info.clone()
-                                },
-                                Data,
-                            )
+                            .create_image(ImageInfo { format, ..info }, Data)
                             .map(|image| Some((image.into(), *clear)))
                     })
                     .unwrap_or(Ok(None))
@@ -247,7 +261,11 @@ where
 pub struct GraphBuilder<B: Backend, T: ?Sized> {
     nodes: Vec<Box<dyn NodeBuilder<B, T>>>,
     buffers: Vec<BufferInfo>,
-    images: Vec<(ImageInfo, Option<gfx_hal::command::ClearValue>)>,
+    images: Vec<(
+        ImageInfo,
+        Vec<gfx_hal::format::Format>,
+        Option<gfx_hal::command::ClearValue>,
+    )>,
     frames_in_flight: u32,
 }
 
@@ -283,15 +301,35 @@ where
         format: gfx_hal::format::Format,
         clear: Option<gfx_hal::command::ClearValue>,
     ) -> ImageId {
+        self.create_image_with_formats(kind, levels, Some(format), clear)
+    }
+
+    /// Create new image owned by graph.
+    /// First format from `formats` supported for the image usage
+    /// collected from the nodes is picked when graph is built.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `formats` is empty.
+    pub fn create_image_with_formats(
+        &mut self,
+        kind: gfx_hal::image::Kind,
+        levels: gfx_hal::image::Level,
+        formats: impl IntoIterator<Item = gfx_hal::format::Format>,
+        clear: Option<gfx_hal::command::ClearValue>,
+    ) -> ImageId {
+        let formats: Vec<_> = formats.into_iter().collect();
+        assert!(!formats.is_empty(), "At least one format must be specified");
         self.images.push((
             ImageInfo {
                 kind,
                 levels,
-                format,
+                format: formats[0],
                 tiling: gfx_hal::image::Tiling::Optimal,
                 view_caps: gfx_hal::image::ViewCapabilities::empty(),
                 usage: gfx_hal::image::Usage::empty(),
             },
+            formats,
             clear,
         ));
         ImageId(self.images.len() - 1)
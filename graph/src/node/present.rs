@@ -396,8 +396,18 @@ where
 
         let input_image = images.into_iter().next().unwrap();
 
-        let target = factory.create_target(
+        // Prefer format of the presented image so that it can be copied without conversion.
+        let input_format = ctx
+            .get_image(input_image.id)
+            .expect("Image does not exist")
+            .format();
+        let format = factory
+            .pick_surface_format(&self.surface, Some(input_format))
+            .unwrap_or_else(|| factory.get_surface_format(&self.surface));
+
+        let target = factory.create_target_with_format(
             self.surface,
+            format,
             self.image_count,
             self.present_mode,
             gfx_hal::image::Usage::TRANSFER_DST,
@@ -0,0 +1,542 @@
+use std::{collections::BTreeSet, ops::Range, ptr::NonNull};
+
+use {
+    crate::{
+        allocator::{Allocator, Kind},
+        block::Block,
+        mapping::*,
+        memory::*,
+        util::*,
+    },
+    gfx_hal::{Backend, Device as _},
+};
+
+/// Memory block allocated from `BuddyAllocator`
+#[derive(derivative::Derivative)]
+#[derivative(Debug)]
+pub struct BuddyBlock<B: Backend> {
+    // #[derivative(Debug(format_with = "super::memory_ptr_fmt"))]
+    memory: *const Memory<B>,
+    chunk_index: u32,
+    order: u32,
+    ptr: Option<NonNull<u8>>,
+    range: Range<u64>,
+    #[derivative(Debug = "ignore")]
+    relevant: relevant::Relevant,
+}
+
+unsafe impl<B> Send for BuddyBlock<B> where B: Backend {}
+unsafe impl<B> Sync for BuddyBlock<B> where B: Backend {}
+
+impl<B> BuddyBlock<B>
+where
+    B: Backend,
+{
+    fn shared_memory(&self) -> &Memory<B> {
+        // Memory won't be freed until last block created from it deallocated.
+        unsafe { &*self.memory }
+    }
+
+    fn size(&self) -> u64 {
+        self.range.end - self.range.start
+    }
+
+    fn dispose(self) {
+        self.relevant.dispose();
+    }
+}
+
+impl<B> Block<B> for BuddyBlock<B>
+where
+    B: Backend,
+{
+    #[inline]
+    fn properties(&self) -> gfx_hal::memory::Properties {
+        self.shared_memory().properties()
+    }
+
+    #[inline]
+    fn memory(&self) -> &B::Memory {
+        self.shared_memory().raw()
+    }
+
+    #[inline]
+    fn range(&self) -> Range<u64> {
+        self.range.clone()
+    }
+
+    #[inline]
+    fn map<'a>(
+        &'a mut self,
+        _device: &B::Device,
+        range: Range<u64>,
+    ) -> Result<MappedRange<'a, B>, gfx_hal::mapping::Error> {
+        debug_assert!(
+            range.start < range.end,
+            "Memory mapping region must have valid size"
+        );
+        if !self.shared_memory().host_visible() {
+            return Err(gfx_hal::mapping::Error::InvalidAccess);
+        }
+
+        if let Some(ptr) = self.ptr {
+            if let Some((ptr, range)) = mapped_sub_range(ptr, self.range.clone(), range) {
+                let mapping = unsafe { MappedRange::from_raw(self.shared_memory(), ptr, range) };
+                Ok(mapping)
+            } else {
+                Err(gfx_hal::mapping::Error::OutOfBounds)
+            }
+        } else {
+            Err(gfx_hal::mapping::Error::MappingFailed)
+        }
+    }
+
+    #[inline]
+    fn unmap(&mut self, _device: &B::Device) {}
+}
+
+/// Config for `BuddyAllocator`.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BuddyConfig {
+    /// Size of the smallest block.
+    /// All requests are rounded up to power of two not less than this value.
+    /// Must be power of two.
+    pub min_block_size: u64,
+
+    /// Size of memory objects allocated from device.
+    /// This is also the largest block size.
+    /// Must be power of two.
+    pub chunk_size: u64,
+}
+
+/// Buddy allocator.
+/// Suitable for medium and large allocations that don't fit `DynamicAllocator`.
+/// Every request is rounded up to power of two which bounds overhead by half of the block.
+/// Freed blocks are merged with their free buddies,
+/// and memory objects are returned to the system as soon as they become unused.
+#[derive(Debug)]
+pub struct BuddyAllocator<B: Backend> {
+    /// Memory type that this allocator allocates.
+    memory_type: gfx_hal::MemoryTypeId,
+
+    /// Memory properties of the memory type.
+    memory_properties: gfx_hal::memory::Properties,
+
+    /// Size of chunks allocated from device.
+    chunk_size: u64,
+
+    /// List of chunks.
+    chunks: veclist::VecList<Chunk<B>>,
+
+    /// Total chunks count.
+    total_chunks: u32,
+
+    /// Free blocks of all chunks.
+    free: FreeBlocks,
+}
+
+/// Free blocks of each order.
+/// Block of order `n` has size `min_block_size << n`.
+/// Blocks are identified by chunk index and offset within chunk.
+#[derive(Debug)]
+struct FreeBlocks {
+    /// Size of the smallest block.
+    min_block_size: u64,
+
+    /// Free blocks of each order.
+    orders: Vec<BTreeSet<(u32, u64)>>,
+}
+
+impl FreeBlocks {
+    fn new(min_block_size: u64, chunk_size: u64) -> Self {
+        let orders = (chunk_size / min_block_size).trailing_zeros() + 1;
+        FreeBlocks {
+            min_block_size,
+            orders: (0..orders).map(|_| BTreeSet::new()).collect(),
+        }
+    }
+
+    fn max_order(&self) -> u32 {
+        self.orders.len() as u32 - 1
+    }
+
+    fn block_size(&self, order: u32) -> u64 {
+        self.min_block_size << order
+    }
+
+    /// Order of the smallest block that fits `size` bytes aligned to `align`.
+    /// Blocks are aligned to their size within chunk.
+    /// Returns `None` if block would be larger than chunk.
+    fn order(&self, size: u64, align: u64) -> Option<u32> {
+        let block_size = size.max(align).max(self.min_block_size).next_power_of_two();
+        let order = (block_size / self.min_block_size).trailing_zeros();
+        if order > self.max_order() {
+            None
+        } else {
+            Some(order)
+        }
+    }
+
+    /// Put whole chunk into the list of free blocks of max order.
+    fn add_chunk(&mut self, chunk_index: u32) {
+        let max_order = self.max_order() as usize;
+        let inserted = self.orders[max_order].insert((chunk_index, 0));
+        debug_assert!(inserted);
+    }
+
+    /// Take free block of specified order.
+    /// Larger block is split if there is no free block of the order.
+    /// Returns `None` if there are no free blocks large enough.
+    fn take(&mut self, order: u32) -> Option<(u32, u64)> {
+        let mut split_order = (order..self.orders.len() as u32)
+            .find(|&order| !self.orders[order as usize].is_empty())?;
+
+        let (chunk_index, offset) = *self.orders[split_order as usize]
+            .iter()
+            .next()
+            .expect("Non-empty order was found");
+        self.orders[split_order as usize].remove(&(chunk_index, offset));
+
+        // Split block in halves putting upper half into free list until it has required size.
+        while split_order > order {
+            split_order -= 1;
+            let buddy_offset = offset + self.block_size(split_order);
+            let inserted = self.orders[split_order as usize].insert((chunk_index, buddy_offset));
+            debug_assert!(inserted);
+        }
+
+        Some((chunk_index, offset))
+    }
+
+    /// Put block back merging it with free buddies while possible.
+    /// Returns `true` if whole chunk became free.
+    /// Such chunk is not put into free list.
+    fn put(&mut self, chunk_index: u32, mut order: u32, mut offset: u64) -> bool {
+        while order < self.max_order() {
+            let buddy_offset = offset ^ self.block_size(order);
+            if !self.orders[order as usize].remove(&(chunk_index, buddy_offset)) {
+                break;
+            }
+            offset = offset.min(buddy_offset);
+            order += 1;
+        }
+
+        if order == self.max_order() {
+            debug_assert_eq!(offset, 0);
+            true
+        } else {
+            let inserted = self.orders[order as usize].insert((chunk_index, offset));
+            debug_assert!(inserted);
+            false
+        }
+    }
+}
+
+/// Memory object allocated from device.
+#[derive(Debug)]
+struct Chunk<B: Backend> {
+    memory: Box<Memory<B>>,
+    ptr: Option<NonNull<u8>>,
+}
+
+unsafe impl<B> Send for Chunk<B> where B: Backend {}
+unsafe impl<B> Sync for Chunk<B> where B: Backend {}
+
+impl<B> BuddyAllocator<B>
+where
+    B: Backend,
+{
+    /// Maximum allocation size.
+    pub fn max_allocation(&self) -> u64 {
+        self.chunk_size
+    }
+
+    /// Get number of memory objects allocated from device.
+    pub fn chunks(&self) -> u32 {
+        self.total_chunks
+    }
+
+    /// Create new `BuddyAllocator`
+    /// for `memory_type` with `memory_properties` specified,
+    /// with `BuddyConfig` provided.
+    pub fn new(
+        memory_type: gfx_hal::MemoryTypeId,
+        memory_properties: gfx_hal::memory::Properties,
+        config: BuddyConfig,
+    ) -> Self {
+        log::info!(
+            "Create new 'buddy' allocator: type: '{:?}', properties: '{:#?}' config: '{:#?}'",
+            memory_type,
+            memory_properties,
+            config
+        );
+
+        assert!(
+            config.min_block_size.is_power_of_two(),
+            "Minimal block size must be power of two"
+        );
+        assert!(
+            config.chunk_size.is_power_of_two(),
+            "Chunk size must be power of two"
+        );
+        assert!(
+            config.min_block_size <= config.chunk_size,
+            "Minimal block size can't be larger than chunk size"
+        );
+        if memory_properties.contains(gfx_hal::memory::Properties::CPU_VISIBLE) {
+            assert!(
+                fits_usize(config.chunk_size),
+                "Chunk size must fit usize for mapping"
+            );
+        }
+
+        BuddyAllocator {
+            memory_type,
+            memory_properties,
+            chunk_size: config.chunk_size,
+            chunks: veclist::VecList::new(),
+            total_chunks: 0,
+            free: FreeBlocks::new(config.min_block_size, config.chunk_size),
+        }
+    }
+
+    /// Allocate new chunk from device and put it into the list of free blocks of max order.
+    fn alloc_chunk(&mut self, device: &B::Device) -> Result<u64, gfx_hal::device::AllocationError> {
+        log::trace!("Allocate new chunk: size: {}", self.chunk_size);
+        let (memory, ptr) = unsafe {
+            // Valid memory type specified.
+            let raw = device.allocate_memory(self.memory_type, self.chunk_size)?;
+
+            let ptr = if self
+                .memory_properties
+                .contains(gfx_hal::memory::Properties::CPU_VISIBLE)
+            {
+                log::trace!("Map new memory object");
+                match device.map_memory(&raw, 0..self.chunk_size) {
+                    Ok(ptr) => Some(NonNull::new_unchecked(ptr)),
+                    Err(gfx_hal::mapping::Error::OutOfMemory(error)) => {
+                        device.free_memory(raw);
+                        return Err(error.into());
+                    }
+                    Err(_) => panic!("Unexpected mapping failure"),
+                }
+            } else {
+                None
+            };
+            let memory = Memory::from_raw(raw, self.chunk_size, self.memory_properties);
+            (memory, ptr)
+        };
+
+        let chunk_index = self.chunks.push(Chunk {
+            memory: Box::new(memory),
+            ptr,
+        });
+        assert!(fits_u32(chunk_index), "Number of chunks must fit in u32");
+        self.total_chunks += 1;
+
+        self.free.add_chunk(chunk_index as u32);
+        Ok(self.chunk_size)
+    }
+
+    /// Return unused chunk to the device.
+    fn free_chunk(&mut self, device: &B::Device, chunk_index: u32) -> u64 {
+        let chunk = self
+            .chunks
+            .pop(chunk_index as usize)
+            .expect("Chunk must exist");
+        log::trace!("Free chunk: {:#?}", chunk);
+        self.total_chunks -= 1;
+        let size = chunk.memory.size();
+        unsafe {
+            if chunk.ptr.is_some() {
+                device.unmap_memory(chunk.memory.raw());
+            }
+            device.free_memory(chunk.memory.into_raw());
+        }
+        size
+    }
+
+    /// Perform full cleanup of the memory allocated.
+    pub fn dispose(self) {
+        if self.total_chunks != 0 {
+            log::error!(
+                "Chunks are not freed during allocator disposal. Chunks: {:#?}",
+                self.chunks
+            );
+        }
+    }
+}
+
+impl<B> Allocator<B> for BuddyAllocator<B>
+where
+    B: Backend,
+{
+    type Block = BuddyBlock<B>;
+
+    fn kind() -> Kind {
+        Kind::Buddy
+    }
+
+    fn alloc(
+        &mut self,
+        device: &B::Device,
+        size: u64,
+        align: u64,
+    ) -> Result<(BuddyBlock<B>, u64), gfx_hal::device::AllocationError> {
+        assert!(align.is_power_of_two());
+        let order = self
+            .free
+            .order(size, align)
+            .expect("Block can't be larger than chunk");
+        let block_size = self.free.block_size(order);
+
+        log::trace!(
+            "Allocate buddy block: size: {}, align: {}, block size: {}, type: {}",
+            size,
+            align,
+            block_size,
+            self.memory_type.0
+        );
+
+        let mut allocated = 0;
+        let (chunk_index, offset) = match self.free.take(order) {
+            Some(block) => block,
+            None => {
+                allocated = self.alloc_chunk(device)?;
+                self.free.take(order).expect("New chunk was added")
+            }
+        };
+
+        let chunk = &self.chunks[chunk_index as usize];
+        let block_range = offset..offset + block_size;
+
+        Ok((
+            BuddyBlock {
+                memory: &*chunk.memory,
+                chunk_index,
+                order,
+                ptr: chunk.ptr.map(|ptr| {
+                    mapped_fitting_range(ptr, 0..self.chunk_size, block_range.clone())
+                        .expect("Block must be sub-range of chunk")
+                }),
+                range: block_range,
+                relevant: relevant::Relevant,
+            },
+            allocated,
+        ))
+    }
+
+    fn free(&mut self, device: &B::Device, block: BuddyBlock<B>) -> u64 {
+        log::trace!("Free block: {:#?}", block);
+        let chunk_index = block.chunk_index;
+        let order = block.order;
+        let offset = block.range.start;
+        debug_assert_eq!(block.size(), self.free.block_size(order));
+        block.dispose();
+
+        if self.free.put(chunk_index, order, offset) {
+            self.free_chunk(device, chunk_index)
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FreeBlocks;
+
+    /// Chunk of 1024 bytes with blocks from 64 bytes.
+    fn free_blocks() -> FreeBlocks {
+        let mut free = FreeBlocks::new(64, 1024);
+        free.add_chunk(0);
+        free
+    }
+
+    #[test]
+    fn orders() {
+        let free = free_blocks();
+        assert_eq!(free.max_order(), 4);
+        assert_eq!(free.order(1, 1), Some(0));
+        assert_eq!(free.order(64, 16), Some(0));
+        assert_eq!(free.order(65, 16), Some(1));
+        assert_eq!(free.order(16, 256), Some(2));
+        assert_eq!(free.order(1024, 1), Some(4));
+    }
+
+    #[test]
+    fn larger_than_chunk() {
+        let mut free = free_blocks();
+        assert_eq!(free.order(1025, 1), None);
+        assert_eq!(free.order(1, 2048), None);
+        // Larger block can't be taken even if chunk is free.
+        assert_eq!(free.take(5), None);
+    }
+
+    #[test]
+    fn split_and_merge() {
+        let mut free = free_blocks();
+        let block = free.take(0).unwrap();
+        assert_eq!(block, (0, 0));
+        // Upper halves of every split are free.
+        for (order, offset) in (0..4).zip(&[64, 128, 256, 512]) {
+            assert!(free.orders[order].contains(&(0, *offset)));
+        }
+
+        assert!(free.put(0, 0, 0));
+        assert!(free.orders.iter().all(|blocks| blocks.is_empty()));
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut free = free_blocks();
+        let mut blocks = (0..16).map(|_| free.take(0).unwrap()).collect::<Vec<_>>();
+        let mut offsets = blocks.iter().map(|&(_, offset)| offset).collect::<Vec<_>>();
+        offsets.sort();
+        assert_eq!(offsets, (0..16).map(|i| i * 64).collect::<Vec<_>>());
+        assert_eq!(free.take(0), None);
+
+        // Free in an order that merges only at the last block.
+        blocks.sort_by_key(|&(_, offset)| (offset / 64).reverse_bits());
+        let last = blocks.pop().unwrap();
+        for (chunk, offset) in blocks {
+            assert!(!free.put(chunk, 0, offset));
+        }
+        assert!(free.put(last.0, 0, last.1));
+        assert!(free.orders.iter().all(|blocks| blocks.is_empty()));
+    }
+
+    #[test]
+    fn merges_only_buddies() {
+        let mut free = free_blocks();
+        let a = free.take(1).unwrap();
+        let b = free.take(1).unwrap();
+        let c = free.take(1).unwrap();
+        assert_eq!((a.1, b.1, c.1), (0, 128, 256));
+
+        // `b` and `c` are adjacent but not buddies.
+        assert!(!free.put(0, 1, b.1));
+        assert!(free.orders[1].contains(&(0, 128)));
+
+        // `c` is merged with its free buddy at 384.
+        assert!(!free.put(0, 1, c.1));
+        assert!(free.orders[1].contains(&(0, 128)));
+        assert!(free.orders[2].contains(&(0, 256)));
+
+        assert!(free.put(0, 1, a.1));
+        assert!(free.orders.iter().all(|blocks| blocks.is_empty()));
+    }
+
+    #[test]
+    fn multiple_chunks() {
+        let mut free = free_blocks();
+        free.add_chunk(1);
+        let a = free.take(4).unwrap();
+        let b = free.take(4).unwrap();
+        assert_ne!(a.0, b.0);
+        assert_eq!(free.take(0), None);
+        assert!(free.put(b.0, 4, b.1));
+        assert!(free.put(a.0, 4, a.1));
+    }
+}
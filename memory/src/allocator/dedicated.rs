@@ -114,6 +114,7 @@ pub struct DedicatedAllocator {
     memory_type: gfx_hal::MemoryTypeId,
     memory_properties: gfx_hal::memory::Properties,
     used: u64,
+    count: u32,
 }
 
 impl DedicatedAllocator {
@@ -132,8 +133,14 @@ impl DedicatedAllocator {
             memory_type,
             memory_properties,
             used: 0,
+            count: 0,
         }
     }
+
+    /// Get number of memory objects allocated from device.
+    pub fn chunks(&self) -> u32 {
+        self.count
+    }
 }
 
 impl<B> Allocator<B> for DedicatedAllocator
@@ -162,6 +169,7 @@ where
         };
 
         self.used += size;
+        self.count += 1;
 
         Ok((DedicatedBlock::from_memory(memory), size))
     }
@@ -171,6 +179,7 @@ where
         block.unmap(device);
         let size = block.memory.size();
         self.used -= size;
+        self.count -= 1;
         unsafe {
             device.free_memory(block.memory.into_raw());
         }
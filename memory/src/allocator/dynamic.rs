@@ -183,6 +183,23 @@ where
         self.max_block_size
     }
 
+    /// Get number of memory objects allocated from device.
+    /// Chunks sub-allocated from bigger chunks are not counted.
+    pub fn chunks(&self) -> u32 {
+        self.sizes
+            .values()
+            .map(|size| {
+                (0..size.chunks.upper_bound())
+                    .filter_map(|index| size.chunks.get(index))
+                    .filter(|chunk| match chunk {
+                        Chunk::Dedicated(..) => true,
+                        Chunk::Dynamic(..) => false,
+                    })
+                    .count() as u32
+            })
+            .sum()
+    }
+
     /// Create new `DynamicAllocator`
     /// for `memory_type` with `memory_properties` specified,
     /// with `DynamicConfig` provided.
@@ -146,6 +146,11 @@ where
         self.linear_size / 2
     }
 
+    /// Get number of memory objects allocated from device.
+    pub fn chunks(&self) -> u32 {
+        self.lines.len() as u32
+    }
+
     /// Create new `LinearAllocator`
     /// for `memory_type` with `memory_properties` specified,
     /// with `LinearConfig` provided.
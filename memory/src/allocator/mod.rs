@@ -1,5 +1,6 @@
 //! This module provides `Allocator` trait and few allocators that implements the trait.
 
+mod buddy;
 mod dedicated;
 mod dynamic;
 mod linear;
@@ -7,6 +8,7 @@ mod linear;
 use crate::block::Block;
 
 pub use self::{
+    buddy::{BuddyAllocator, BuddyBlock, BuddyConfig},
     dedicated::{DedicatedAllocator, DedicatedBlock},
     dynamic::{DynamicAllocator, DynamicBlock, DynamicConfig},
     linear::{LinearAllocator, LinearBlock, LinearConfig},
@@ -14,6 +16,7 @@ pub use self::{
 
 /// Allocator kind.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
 pub enum Kind {
     /// Memory object per allocation.
     Dedicated,
@@ -21,6 +24,10 @@ pub enum Kind {
     /// General purpose allocator.
     Dynamic,
 
+    /// Power-of-two blocks allocator.
+    /// Suitable for medium and large allocations.
+    Buddy,
+
     /// Allocates linearly.
     /// Fast and low overhead.
     /// Suitable for one-time-use allocations.
@@ -11,9 +11,12 @@ pub(super) struct MemoryType<B: gfx_hal::Backend> {
     dedicated: DedicatedAllocator,
     linear: Option<LinearAllocator<B>>,
     dynamic: Option<DynamicAllocator<B>>,
+    buddy: Option<BuddyAllocator<B>>,
     // chunk: Option<ChunkAllocator>,
     used: u64,
     effective: u64,
+    /// Utilization per allocator kind.
+    kinds: [MemoryUtilization; 4],
 }
 
 impl<B> MemoryType<B>
@@ -40,8 +43,15 @@ where
             dynamic: config
                 .dynamic
                 .map(|config| DynamicAllocator::new(memory_type, properties, config)),
+            buddy: config
+                .buddy
+                .map(|config| BuddyAllocator::new(memory_type, properties, config)),
             used: 0,
             effective: 0,
+            kinds: [MemoryUtilization {
+                used: 0,
+                effective: 0,
+            }; 4],
         }
     }
 
@@ -63,6 +73,9 @@ where
         let (block, allocated) = self.alloc_impl(device, usage, size, align)?;
         self.effective += block.size();
         self.used += allocated;
+        let kind = &mut self.kinds[block.kind() as usize];
+        kind.effective += block.size();
+        kind.used += allocated;
         Ok((block, allocated))
     }
 
@@ -73,62 +86,71 @@ where
         size: u64,
         align: u64,
     ) -> Result<(BlockFlavor<B>, u64), gfx_hal::device::AllocationError> {
-        match (self.dynamic.as_mut(), self.linear.as_mut()) {
-            (Some(dynamic), Some(linear)) => {
-                if dynamic.max_allocation() >= size
-                    && usage.allocator_fitness(Kind::Dynamic)
-                        > usage.allocator_fitness(Kind::Linear)
-                {
-                    dynamic
-                        .alloc(device, size, align)
-                        .map(|(block, size)| (BlockFlavor::Dynamic(block), size))
-                } else if linear.max_allocation() >= size
-                    && usage.allocator_fitness(Kind::Linear) > 0
-                {
-                    linear
-                        .alloc(device, size, align)
-                        .map(|(block, size)| (BlockFlavor::Linear(block), size))
-                } else {
-                    self.dedicated
-                        .alloc(device, size, align)
-                        .map(|(block, size)| (BlockFlavor::Dedicated(block), size))
-                }
-            }
-            (Some(dynamic), None) => {
-                if dynamic.max_allocation() >= size && usage.allocator_fitness(Kind::Dynamic) > 0 {
-                    dynamic
-                        .alloc(device, size, align)
-                        .map(|(block, size)| (BlockFlavor::Dynamic(block), size))
-                } else {
-                    self.dedicated
-                        .alloc(device, size, align)
-                        .map(|(block, size)| (BlockFlavor::Dedicated(block), size))
-                }
-            }
-            (None, Some(linear)) => {
-                if linear.max_allocation() >= size && usage.allocator_fitness(Kind::Linear) > 0 {
-                    linear
-                        .alloc(device, size, align)
-                        .map(|(block, size)| (BlockFlavor::Linear(block), size))
-                } else {
-                    self.dedicated
-                        .alloc(device, size, align)
-                        .map(|(block, size)| (BlockFlavor::Dedicated(block), size))
-                }
-            }
-            (None, None) => self
+        // Pick sub-allocator that can serve the request and fits usage best.
+        // Dedicated allocator is used when no sub-allocator fits better.
+        let dedicated_fitness = usage.allocator_fitness(Kind::Dedicated);
+        let kind = [
+            (
+                Kind::Linear,
+                self.linear.as_ref().map(|a| a.max_allocation()),
+            ),
+            (Kind::Buddy, self.buddy.as_ref().map(|a| a.max_allocation())),
+            (
+                Kind::Dynamic,
+                self.dynamic.as_ref().map(|a| a.max_allocation()),
+            ),
+        ]
+        .iter()
+        .filter_map(|&(kind, max_allocation)| {
+            max_allocation
+                .filter(|&max_allocation| max_allocation >= size)
+                .map(|_| (kind, usage.allocator_fitness(kind)))
+        })
+        .filter(|&(_, fitness)| fitness > 0 && fitness >= dedicated_fitness)
+        .max_by_key(|&(_, fitness)| fitness)
+        .map_or(Kind::Dedicated, |(kind, _)| kind);
+
+        match kind {
+            Kind::Dedicated => self
                 .dedicated
                 .alloc(device, size, align)
                 .map(|(block, size)| (BlockFlavor::Dedicated(block), size)),
+            Kind::Dynamic => self
+                .dynamic
+                .as_mut()
+                .unwrap()
+                .alloc(device, size, align)
+                .map(|(block, size)| (BlockFlavor::Dynamic(block), size)),
+            Kind::Buddy => self
+                .buddy
+                .as_mut()
+                .unwrap()
+                .alloc(device, size, align)
+                .map(|(block, size)| (BlockFlavor::Buddy(block), size)),
+            Kind::Linear => self
+                .linear
+                .as_mut()
+                .unwrap()
+                .alloc(device, size, align)
+                .map(|(block, size)| (BlockFlavor::Linear(block), size)),
         }
     }
 
     pub(super) fn free(&mut self, device: &B::Device, block: BlockFlavor<B>) -> u64 {
-        match block {
+        let size = block.size();
+        let kind = block.kind();
+        let freed = match block {
             BlockFlavor::Dedicated(block) => self.dedicated.free(device, block),
             BlockFlavor::Linear(block) => self.linear.as_mut().unwrap().free(device, block),
             BlockFlavor::Dynamic(block) => self.dynamic.as_mut().unwrap().free(device, block),
-        }
+            BlockFlavor::Buddy(block) => self.buddy.as_mut().unwrap().free(device, block),
+        };
+        self.effective -= size;
+        self.used -= freed;
+        let kind = &mut self.kinds[kind as usize];
+        kind.effective -= size;
+        kind.used -= freed;
+        freed
     }
 
     pub(super) fn dispose(self, device: &B::Device) {
@@ -142,6 +164,10 @@ where
             dynamic.dispose();
             log::trace!("Dynamic allocator disposed");
         }
+        if let Some(buddy) = self.buddy {
+            buddy.dispose();
+            log::trace!("Buddy allocator disposed");
+        }
     }
 
     pub(super) fn utilization(&self) -> MemoryTypeUtilization {
@@ -154,4 +180,28 @@ where
             heap_index: self.heap_index,
         }
     }
+
+    pub(super) fn allocators_utilization(
+        &self,
+        type_index: usize,
+    ) -> Vec<MemoryAllocatorUtilization> {
+        let kind_utilization = |kind: Kind, chunks: u32| MemoryAllocatorUtilization {
+            utilization: self.kinds[kind as usize],
+            type_index,
+            kind,
+            chunks,
+        };
+
+        let mut allocators = vec![kind_utilization(Kind::Dedicated, self.dedicated.chunks())];
+        if let Some(linear) = &self.linear {
+            allocators.push(kind_utilization(Kind::Linear, linear.chunks()));
+        }
+        if let Some(dynamic) = &self.dynamic {
+            allocators.push(kind_utilization(Kind::Dynamic, dynamic.chunks()));
+        }
+        if let Some(buddy) = &self.buddy {
+            allocators.push(kind_utilization(Kind::Buddy, buddy.chunks()));
+        }
+        allocators
+    }
 }
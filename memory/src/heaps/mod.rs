@@ -44,6 +44,9 @@ pub struct HeapsConfig {
 
     /// Config for dynamic sub-allocator.
     pub dynamic: Option<DynamicConfig>,
+
+    /// Config for buddy sub-allocator.
+    pub buddy: Option<BuddyConfig>,
 }
 
 /// Heaps available on particular physical device.
@@ -208,6 +211,12 @@ where
         TotalMemoryUtilization {
             heaps: self.heaps.iter().map(MemoryHeap::utilization).collect(),
             types: self.types.iter().map(MemoryType::utilization).collect(),
+            allocators: self
+                .types
+                .iter()
+                .enumerate()
+                .flat_map(|(index, ty)| ty.allocators_utilization(index))
+                .collect(),
         }
     }
 }
@@ -234,6 +243,7 @@ enum BlockFlavor<B: gfx_hal::Backend> {
     Dedicated(DedicatedBlock<B>),
     Linear(LinearBlock<B>),
     Dynamic(DynamicBlock<B>),
+    Buddy(BuddyBlock<B>),
     // Chunk(ChunkBlock<B>),
 }
 
@@ -244,6 +254,7 @@ macro_rules! any_block {
             Dedicated($block) => $expr,
             Linear($block) => $expr,
             Dynamic($block) => $expr,
+            Buddy($block) => $expr,
             // Chunk($block) => $expr,
         }
     }};
@@ -253,6 +264,7 @@ macro_rules! any_block {
             Dedicated($block) => $expr,
             Linear($block) => $expr,
             Dynamic($block) => $expr,
+            Buddy($block) => $expr,
             // Chunk($block) => $expr,
         }
     }};
@@ -262,6 +274,7 @@ macro_rules! any_block {
             Dedicated($block) => $expr,
             Linear($block) => $expr,
             Dynamic($block) => $expr,
+            Buddy($block) => $expr,
             // Chunk($block) => $expr,
         }
     }};
@@ -271,6 +284,17 @@ impl<B> BlockFlavor<B>
 where
     B: gfx_hal::Backend,
 {
+    #[inline]
+    fn kind(&self) -> Kind {
+        use self::BlockFlavor::*;
+        match self {
+            Dedicated(_) => Kind::Dedicated,
+            Linear(_) => Kind::Linear,
+            Dynamic(_) => Kind::Dynamic,
+            Buddy(_) => Kind::Buddy,
+        }
+    }
+
     #[inline]
     fn size(&self) -> u64 {
         use self::BlockFlavor::*;
@@ -278,6 +302,7 @@ where
             Dedicated(block) => block.size(),
             Linear(block) => block.size(),
             Dynamic(block) => block.size(),
+            Buddy(block) => block.size(),
             // Chunk(block) => block.size(),
         }
     }
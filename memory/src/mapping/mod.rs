@@ -2,7 +2,10 @@ mod range;
 pub(crate) mod write;
 
 use {
-    crate::{memory::Memory, util::fits_usize},
+    crate::{
+        memory::Memory,
+        util::{aligned, fits_usize},
+    },
     gfx_hal::{Backend, Device as _},
     std::{ops::Range, ptr::NonNull},
 };
@@ -153,6 +156,8 @@ where
 
     /// Fetch writer to the sub-region.
     /// This writer will flush data on drop if written at least once.
+    /// Flush errors on drop are only logged,
+    /// use [`flush`](#method.flush) afterwards to handle them.
     ///
     /// # Safety
     ///
@@ -193,9 +198,10 @@ where
             slice,
             flush: if !self.coherent.0 {
                 Some(move || {
-                    device
-                        .flush_mapped_memory_ranges(Some((memory.raw(), range)))
-                        .expect("Should flush successfully");
+                    if let Err(err) = device.flush_mapped_memory_ranges(Some((memory.raw(), range)))
+                    {
+                        log::error!("Failed to flush mapped memory on drop: {}", err);
+                    }
                 })
             } else {
                 None
@@ -203,6 +209,66 @@ where
         })
     }
 
+    /// Flush sub-range of the mapping.
+    /// Makes host writes to the sub-range available to the device.
+    /// No-op if memory is coherent.
+    ///
+    /// `non_coherent_atom_size` must be taken from device limits.
+    /// Flushed range is extended to be multiple of it as required by the spec.
+    pub fn flush(
+        &self,
+        device: &B::Device,
+        range: Range<u64>,
+        non_coherent_atom_size: u64,
+    ) -> Result<(), gfx_hal::device::OutOfMemory> {
+        if self.coherent.0 {
+            return Ok(());
+        }
+
+        let range = self.atom_range(range, non_coherent_atom_size);
+        unsafe { device.flush_mapped_memory_ranges(Some((self.memory.raw(), range))) }
+    }
+
+    /// Invalidate sub-range of the mapping.
+    /// Makes device writes to the sub-range visible to the host.
+    /// No-op if memory is coherent.
+    ///
+    /// `non_coherent_atom_size` must be taken from device limits.
+    /// Invalidated range is extended to be multiple of it as required by the spec.
+    pub fn invalidate(
+        &self,
+        device: &B::Device,
+        range: Range<u64>,
+        non_coherent_atom_size: u64,
+    ) -> Result<(), gfx_hal::device::OutOfMemory> {
+        if self.coherent.0 {
+            return Ok(());
+        }
+
+        let range = self.atom_range(range, non_coherent_atom_size);
+        unsafe { device.invalidate_mapped_memory_ranges(Some((self.memory.raw(), range))) }
+    }
+
+    /// Convert sub-range of the mapping into memory object space
+    /// and align it to `non_coherent_atom_size`.
+    fn atom_range(&self, range: Range<u64>, non_coherent_atom_size: u64) -> Range<u64> {
+        assert!(
+            range.start < range.end,
+            "Memory mapping region must have valid size"
+        );
+        assert!(
+            non_coherent_atom_size.is_power_of_two(),
+            "Non-coherent atom size must be power of two"
+        );
+
+        let (_, range) = mapped_sub_range(self.ptr, self.range.clone(), range)
+            .expect("Range must fit into mapping");
+
+        let start = range.start - range.start % non_coherent_atom_size;
+        let end = aligned(range.end, non_coherent_atom_size).min(self.memory.size());
+        start..end
+    }
+
     /// Convert into mapped range with statically known coherency.
     pub fn coherent(self) -> Result<MappedRange<'a, B, Coherent>, MappedRange<'a, B, NonCoherent>> {
         if self.coherent.0 {
@@ -58,7 +58,8 @@ impl MemoryUsage for Data {
     fn allocator_fitness(&self, kind: Kind) -> u32 {
         match kind {
             Kind::Dedicated => 1,
-            Kind::Dynamic => 2,
+            Kind::Dynamic => 3,
+            Kind::Buddy => 2,
             Kind::Linear => 0,
         }
     }
@@ -89,7 +90,8 @@ impl MemoryUsage for Dynamic {
     fn allocator_fitness(&self, kind: Kind) -> u32 {
         match kind {
             Kind::Dedicated => 1,
-            Kind::Dynamic => 2,
+            Kind::Dynamic => 3,
+            Kind::Buddy => 2,
             Kind::Linear => 0,
         }
     }
@@ -119,8 +121,9 @@ impl MemoryUsage for Upload {
     fn allocator_fitness(&self, kind: Kind) -> u32 {
         match kind {
             Kind::Dedicated => 0,
-            Kind::Dynamic => 1,
-            Kind::Linear => 2,
+            Kind::Dynamic => 2,
+            Kind::Buddy => 1,
+            Kind::Linear => 3,
         }
     }
 }
@@ -149,14 +152,220 @@ impl MemoryUsage for Download {
     fn allocator_fitness(&self, kind: Kind) -> u32 {
         match kind {
             Kind::Dedicated => 0,
+            Kind::Dynamic => 2,
+            Kind::Buddy => 1,
+            Kind::Linear => 3,
+        }
+    }
+}
+
+/// Direct CPU writes into GPU memory.
+/// Used for frequently updated resources that are read by the GPU at full speed,
+/// when device exposes memory that is both device local and host visible
+/// (e.g. resizable BAR or unified memory).
+/// Host access is guaranteed.
+#[derive(Clone, Copy, Debug)]
+pub struct DeviceLocalHostVisible;
+
+impl MemoryUsage for DeviceLocalHostVisible {
+    fn properties_required(&self) -> gfx_hal::memory::Properties {
+        gfx_hal::memory::Properties::DEVICE_LOCAL | gfx_hal::memory::Properties::CPU_VISIBLE
+    }
+
+    #[inline]
+    fn memory_fitness(&self, properties: gfx_hal::memory::Properties) -> u32 {
+        assert!(properties.contains(gfx_hal::memory::Properties::DEVICE_LOCAL));
+        assert!(properties.contains(gfx_hal::memory::Properties::CPU_VISIBLE));
+        assert!(!properties.contains(gfx_hal::memory::Properties::LAZILY_ALLOCATED));
+
+        (properties.contains(gfx_hal::memory::Properties::COHERENT) as u32) << 1
+            | (!properties.contains(gfx_hal::memory::Properties::CPU_CACHED)) as u32
+    }
+
+    fn allocator_fitness(&self, kind: Kind) -> u32 {
+        match kind {
+            Kind::Dedicated => 1,
+            Kind::Dynamic => 3,
+            Kind::Buddy => 2,
+            Kind::Linear => 0,
+        }
+    }
+}
+
+/// GPU-only memory that may never be backed by physical storage.
+/// Optimal for transient attachments that live within single render pass.
+/// Prefers lazily allocated memory, falls back to regular device local memory.
+/// Host access is never required.
+#[derive(Clone, Copy, Debug)]
+pub struct Transient;
+
+impl MemoryUsage for Transient {
+    fn properties_required(&self) -> gfx_hal::memory::Properties {
+        gfx_hal::memory::Properties::DEVICE_LOCAL
+    }
+
+    #[inline]
+    fn memory_fitness(&self, properties: gfx_hal::memory::Properties) -> u32 {
+        assert!(properties.contains(gfx_hal::memory::Properties::DEVICE_LOCAL));
+        (properties.contains(gfx_hal::memory::Properties::LAZILY_ALLOCATED) as u32) << 3
+            | ((!properties.contains(gfx_hal::memory::Properties::CPU_VISIBLE)) as u32) << 2
+            | ((!properties.contains(gfx_hal::memory::Properties::CPU_CACHED)) as u32) << 1
+            | (!properties.contains(gfx_hal::memory::Properties::COHERENT)) as u32
+    }
+
+    fn allocator_fitness(&self, kind: Kind) -> u32 {
+        match kind {
+            Kind::Dedicated => 2,
             Kind::Dynamic => 1,
-            Kind::Linear => 2,
+            Kind::Buddy => 1,
+            Kind::Linear => 0,
+        }
+    }
+}
+
+/// Maximum number of preferences of `CustomUsage`.
+/// Each preference takes one bit of the fitness value.
+pub const MAX_CUSTOM_PREFERENCES: usize = 32;
+
+/// Error returned when `CustomUsage` is created with too many preferences.
+#[derive(Clone, Copy, Debug, failure::Fail)]
+#[fail(
+    display = "Custom memory usage can't have more than {} preferences, got {}",
+    _1, _0
+)]
+pub struct TooManyPreferences(pub usize, pub usize);
+
+/// User-defined memory usage.
+/// Unlike other usages this one can be constructed at runtime
+/// and loaded from config files in any format supported by serde ecosystem.
+///
+/// Memory fitness is calculated by checking preferences in order.
+/// Each entry that is satisfied by memory properties adds a bit to the fitness value,
+/// earlier entries have higher weight than all following entries combined.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde-1",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "CustomUsageDesc", into = "CustomUsageDesc")
+)]
+pub struct CustomUsage {
+    required: gfx_hal::memory::Properties,
+    preferences: [(gfx_hal::memory::Properties, bool); MAX_CUSTOM_PREFERENCES],
+    preferences_count: usize,
+    allocators: [u32; 4],
+}
+
+impl CustomUsage {
+    /// Create custom usage.
+    ///
+    /// `preferences` are properties paired with flag saying whether memory should have (`true`)
+    /// or should not have (`false`) them, from most to least important.
+    /// At most `MAX_CUSTOM_PREFERENCES` preferences are allowed.
+    ///
+    /// `allocators` are fitness values for allocator kinds.
+    /// Kinds not listed have zero fitness.
+    pub fn new(
+        required: gfx_hal::memory::Properties,
+        preferences: &[(gfx_hal::memory::Properties, bool)],
+        allocators: &[(Kind, u32)],
+    ) -> Result<Self, TooManyPreferences> {
+        if preferences.len() > MAX_CUSTOM_PREFERENCES {
+            return Err(TooManyPreferences(
+                preferences.len(),
+                MAX_CUSTOM_PREFERENCES,
+            ));
+        }
+
+        let mut usage = CustomUsage {
+            required,
+            preferences: [(gfx_hal::memory::Properties::empty(), false); MAX_CUSTOM_PREFERENCES],
+            preferences_count: preferences.len(),
+            allocators: [0; 4],
+        };
+        usage.preferences[..preferences.len()].copy_from_slice(preferences);
+        for &(kind, fitness) in allocators {
+            usage.allocators[kind_index(kind)] = fitness;
+        }
+        Ok(usage)
+    }
+
+    /// Properties memory must have.
+    pub fn required(&self) -> gfx_hal::memory::Properties {
+        self.required
+    }
+
+    /// Properties paired with flag saying whether memory should have (`true`)
+    /// or should not have (`false`) them, from most to least important.
+    pub fn preferences(&self) -> &[(gfx_hal::memory::Properties, bool)] {
+        &self.preferences[..self.preferences_count]
+    }
+}
+
+fn kind_index(kind: Kind) -> usize {
+    match kind {
+        Kind::Dedicated => 0,
+        Kind::Dynamic => 1,
+        Kind::Buddy => 2,
+        Kind::Linear => 3,
+    }
+}
+
+impl MemoryUsage for CustomUsage {
+    fn properties_required(&self) -> gfx_hal::memory::Properties {
+        self.required
+    }
+
+    fn memory_fitness(&self, properties: gfx_hal::memory::Properties) -> u32 {
+        assert!(properties.contains(self.required));
+
+        self.preferences()
+            .iter()
+            .fold(0, |fitness, &(preference, wanted)| {
+                (fitness << 1) | (properties.contains(preference) == wanted) as u32
+            })
+    }
+
+    fn allocator_fitness(&self, kind: Kind) -> u32 {
+        self.allocators[kind_index(kind)]
+    }
+}
+
+/// Serialized form of `CustomUsage`.
+#[cfg(feature = "serde-1")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CustomUsageDesc {
+    required: gfx_hal::memory::Properties,
+    preferences: Vec<(gfx_hal::memory::Properties, bool)>,
+    allocators: Vec<(Kind, u32)>,
+}
+
+#[cfg(feature = "serde-1")]
+impl std::convert::TryFrom<CustomUsageDesc> for CustomUsage {
+    type Error = TooManyPreferences;
+
+    fn try_from(desc: CustomUsageDesc) -> Result<Self, TooManyPreferences> {
+        CustomUsage::new(desc.required, &desc.preferences, &desc.allocators)
+    }
+}
+
+#[cfg(feature = "serde-1")]
+impl From<CustomUsage> for CustomUsageDesc {
+    fn from(usage: CustomUsage) -> Self {
+        CustomUsageDesc {
+            required: usage.required,
+            preferences: usage.preferences().to_vec(),
+            allocators: [Kind::Dedicated, Kind::Dynamic, Kind::Buddy, Kind::Linear]
+                .iter()
+                .map(|&kind| (kind, usage.allocator_fitness(kind)))
+                .filter(|&(_, fitness)| fitness != 0)
+                .collect(),
         }
     }
 }
 
 /// Well-known memory usage types.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
 pub enum MemoryUsageValue {
     /// See [`Data`]
     ///
@@ -177,6 +386,21 @@ pub enum MemoryUsageValue {
     ///
     /// [`Download`]: struct.Download.html
     Download,
+
+    /// See [`DeviceLocalHostVisible`]
+    ///
+    /// [`DeviceLocalHostVisible`]: struct.DeviceLocalHostVisible.html
+    DeviceLocalHostVisible,
+
+    /// See [`Transient`]
+    ///
+    /// [`Transient`]: struct.Transient.html
+    Transient,
+
+    /// See [`CustomUsage`]
+    ///
+    /// [`CustomUsage`]: struct.CustomUsage.html
+    Custom(CustomUsage),
 }
 
 /// Memory usage trait.
@@ -187,6 +411,11 @@ impl MemoryUsage for MemoryUsageValue {
             MemoryUsageValue::Dynamic => Dynamic.properties_required(),
             MemoryUsageValue::Upload => Upload.properties_required(),
             MemoryUsageValue::Download => Download.properties_required(),
+            MemoryUsageValue::DeviceLocalHostVisible => {
+                DeviceLocalHostVisible.properties_required()
+            }
+            MemoryUsageValue::Transient => Transient.properties_required(),
+            MemoryUsageValue::Custom(custom) => custom.properties_required(),
         }
     }
 
@@ -196,6 +425,11 @@ impl MemoryUsage for MemoryUsageValue {
             MemoryUsageValue::Dynamic => Dynamic.memory_fitness(properties),
             MemoryUsageValue::Upload => Upload.memory_fitness(properties),
             MemoryUsageValue::Download => Download.memory_fitness(properties),
+            MemoryUsageValue::DeviceLocalHostVisible => {
+                DeviceLocalHostVisible.memory_fitness(properties)
+            }
+            MemoryUsageValue::Transient => Transient.memory_fitness(properties),
+            MemoryUsageValue::Custom(custom) => custom.memory_fitness(properties),
         }
     }
 
@@ -205,6 +439,11 @@ impl MemoryUsage for MemoryUsageValue {
             MemoryUsageValue::Dynamic => Dynamic.allocator_fitness(kind),
             MemoryUsageValue::Upload => Upload.allocator_fitness(kind),
             MemoryUsageValue::Download => Download.allocator_fitness(kind),
+            MemoryUsageValue::DeviceLocalHostVisible => {
+                DeviceLocalHostVisible.allocator_fitness(kind)
+            }
+            MemoryUsageValue::Transient => Transient.allocator_fitness(kind),
+            MemoryUsageValue::Custom(custom) => custom.allocator_fitness(kind),
         }
     }
 }
@@ -1,10 +1,12 @@
 use {
+    crate::allocator::Kind,
     colorful::{core::color_string::CString, Color, Colorful as _},
     gfx_hal::memory::Properties,
 };
 
 /// Memory utilization stats.
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
 pub struct MemoryUtilization {
     /// Total number of bytes allocated.
     pub used: u64,
@@ -12,8 +14,22 @@ pub struct MemoryUtilization {
     pub effective: u64,
 }
 
+impl MemoryUtilization {
+    /// Get fragmentation ratio.
+    /// That is fraction of allocated bytes that are not effectively used.
+    /// Returns `0.0` if nothing is allocated.
+    pub fn fragmentation(&self) -> f64 {
+        if self.used > 0 {
+            1.0 - self.effective as f64 / self.used as f64
+        } else {
+            0.0
+        }
+    }
+}
+
 /// Memory utilization of one heap.
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
 pub struct MemoryHeapUtilization {
     /// Utilization.
     pub utilization: MemoryUtilization,
@@ -22,8 +38,26 @@ pub struct MemoryHeapUtilization {
     pub size: u64,
 }
 
+/// Memory utilization of one allocator.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemoryAllocatorUtilization {
+    /// Utilization.
+    pub utilization: MemoryUtilization,
+
+    /// Index of memory type the allocator allocates from.
+    pub type_index: usize,
+
+    /// Allocator kind.
+    pub kind: Kind,
+
+    /// Number of memory objects allocated from device by the allocator.
+    pub chunks: u32,
+}
+
 /// Memory utilization of one type.
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
 pub struct MemoryTypeUtilization {
     /// Utilization.
     pub utilization: MemoryUtilization,
@@ -37,12 +71,16 @@ pub struct MemoryTypeUtilization {
 
 /// Total memory utilization.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
 pub struct TotalMemoryUtilization {
     /// Utilization by types.
     pub types: Vec<MemoryTypeUtilization>,
 
     /// Utilization by heaps.
     pub heaps: Vec<MemoryHeapUtilization>,
+
+    /// Utilization by allocators configured for each memory type.
+    pub allocators: Vec<MemoryAllocatorUtilization>,
 }
 
 impl std::fmt::Display for TotalMemoryUtilization {
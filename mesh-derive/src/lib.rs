@@ -0,0 +1,187 @@
+//!
+//! Derive macros for `rendy-mesh`.
+//!
+
+#![warn(
+    missing_debug_implementations,
+    missing_copy_implementations,
+    missing_docs,
+    trivial_casts,
+    trivial_numeric_casts,
+    unused_extern_crates,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use {
+    proc_macro::TokenStream,
+    proc_macro2::TokenStream as TokenStream2,
+    quote::quote,
+    syn::{
+        parse_macro_input, spanned::Spanned, Data, DeriveInput, Error, Fields, Lit, Meta,
+        NestedMeta, Path,
+    },
+};
+
+/// Derive `AsVertex` and `WithAttribute` for each field
+/// of `#[repr(C)]` struct with fields implementing `AsAttribute`.
+///
+/// Generated code refers to `rendy_mesh` crate.
+/// Path to the crate can be changed with `#[as_vertex(crate = "path")]` attribute.
+#[proc_macro_derive(AsVertex, attributes(as_vertex))]
+pub fn derive_as_vertex(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match as_vertex(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn as_vertex(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    check_repr(input)?;
+    let krate = crate_path(input)?;
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields.named.iter().collect::<Vec<_>>(),
+            Fields::Unnamed(fields) => fields.unnamed.iter().collect::<Vec<_>>(),
+            Fields::Unit => Vec::new(),
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                input,
+                "`AsVertex` can be derived only for structs",
+            ))
+        }
+    };
+
+    if fields.is_empty() {
+        return Err(Error::new_spanned(
+            input,
+            "`AsVertex` can't be derived for struct without fields",
+        ));
+    }
+
+    let types = fields
+        .iter()
+        .map(|field| {
+            let ty = &field.ty;
+            quote!(#ty).to_string()
+        })
+        .collect::<Vec<_>>();
+    for (index, field) in fields.iter().enumerate() {
+        if types[..index].contains(&types[index]) {
+            return Err(Error::new(
+                field.span(),
+                "Attribute type can be used only once in the vertex format",
+            ));
+        }
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    // Offsets are computed the same way as `repr(C)` lays out fields:
+    // each field starts at the end of previous one rounded up to field's alignment.
+    let mut offset = quote!(0usize);
+    let mut with_attributes = Vec::new();
+    let mut attributes = Vec::new();
+    let mut names = Vec::new();
+    for field in &fields {
+        let ty = &field.ty;
+        let align = quote!(::std::mem::align_of::<#ty>());
+        let field_offset = quote!(((#offset) + #align - 1) / #align * #align);
+
+        with_attributes.push(quote! {
+            impl #impl_generics #krate::WithAttribute<#ty> for #name #ty_generics #where_clause {
+                const ATTRIBUTE: #krate::Attribute = #krate::Attribute {
+                    offset: (#field_offset) as u32,
+                    format: <#ty as #krate::AsAttribute>::FORMAT,
+                };
+            }
+        });
+        attributes.push(quote! {
+            <Self as #krate::WithAttribute<#ty>>::ATTRIBUTE
+        });
+        names.push(quote! {
+            <#ty as #krate::AsAttribute>::NAME
+        });
+
+        offset = quote!((#field_offset) + ::std::mem::size_of::<#ty>());
+    }
+
+    Ok(quote! {
+        impl #impl_generics #krate::AsVertex for #name #ty_generics #where_clause {
+            const VERTEX: #krate::VertexFormat<'static> = #krate::VertexFormat {
+                attributes: ::std::borrow::Cow::Borrowed(&[#(#attributes),*]),
+                stride: ::std::mem::size_of::<Self>() as u32,
+            };
+
+            const NAMES: &'static [&'static str] = &[#(#names),*];
+        }
+
+        #(#with_attributes)*
+    })
+}
+
+/// Check that struct has `#[repr(C)]` and isn't packed.
+fn check_repr(input: &DeriveInput) -> Result<(), Error> {
+    let mut repr_c = false;
+    for attr in &input.attrs {
+        match attr.parse_meta() {
+            Ok(Meta::List(list)) if list.ident == "repr" => {
+                for nested in &list.nested {
+                    match nested {
+                        NestedMeta::Meta(Meta::Word(word)) if word == "C" => repr_c = true,
+                        NestedMeta::Meta(meta) if meta.name() == "packed" => {
+                            return Err(Error::new_spanned(
+                                attr,
+                                "`AsVertex` can't be derived for packed structs",
+                            ));
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if repr_c {
+        Ok(())
+    } else {
+        Err(Error::new_spanned(
+            &input.ident,
+            "`AsVertex` can be derived only for structs with `#[repr(C)]`",
+        ))
+    }
+}
+
+/// Get path to `rendy_mesh` crate from `#[as_vertex(crate = "path")]` attribute.
+fn crate_path(input: &DeriveInput) -> Result<Path, Error> {
+    let mut path = syn::parse_quote!(rendy_mesh);
+    for attr in &input.attrs {
+        match attr.parse_meta() {
+            Ok(Meta::List(list)) if list.ident == "as_vertex" => {
+                for nested in &list.nested {
+                    match nested {
+                        NestedMeta::Meta(Meta::NameValue(value)) if value.ident == "crate" => {
+                            path = match &value.lit {
+                                Lit::Str(lit) => lit.parse()?,
+                                lit => return Err(Error::new_spanned(lit, "Expected path string")),
+                            };
+                        }
+                        nested => {
+                            return Err(Error::new_spanned(
+                                nested,
+                                "Unknown `as_vertex` attribute. Expected `crate = \"path\"`",
+                            ));
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(path)
+}
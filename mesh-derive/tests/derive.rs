@@ -0,0 +1,90 @@
+use rendy_mesh::{AsAttribute, AsVertex, Color, Position, TexCoord, WithAttribute};
+
+/// Attribute with alignment smaller than alignment of `Position`.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+struct Id(u16);
+
+impl AsAttribute for Id {
+    const NAME: &'static str = "id";
+    const SIZE: u32 = 2;
+    const FORMAT: gfx_hal::format::Format = gfx_hal::format::Format::R16Uint;
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, AsVertex)]
+struct PosColorTex {
+    position: Position,
+    color: Color,
+    tex_coord: TexCoord,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, AsVertex)]
+struct IdPos(Id, Position);
+
+fn offset_of<T, F>(vertex: &T, field: &F) -> u32 {
+    (field as *const F as usize - vertex as *const T as usize) as u32
+}
+
+#[test]
+fn offsets_match_struct_layout() {
+    let vertex = PosColorTex {
+        position: Position([0.0; 3]),
+        color: Color([0.0; 4]),
+        tex_coord: TexCoord([0.0; 2]),
+    };
+
+    let format = PosColorTex::VERTEX;
+    assert_eq!(format.stride as usize, std::mem::size_of::<PosColorTex>());
+    assert_eq!(
+        format
+            .attributes
+            .iter()
+            .map(|a| a.offset)
+            .collect::<Vec<_>>(),
+        vec![
+            offset_of(&vertex, &vertex.position),
+            offset_of(&vertex, &vertex.color),
+            offset_of(&vertex, &vertex.tex_coord),
+        ]
+    );
+    assert_eq!(
+        format
+            .attributes
+            .iter()
+            .map(|a| a.format)
+            .collect::<Vec<_>>(),
+        vec![Position::FORMAT, Color::FORMAT, TexCoord::FORMAT]
+    );
+    assert_eq!(
+        PosColorTex::NAMES,
+        &[Position::NAME, Color::NAME, TexCoord::NAME]
+    );
+    assert_eq!(
+        <PosColorTex as WithAttribute<Color>>::ATTRIBUTE.offset,
+        offset_of(&vertex, &vertex.color)
+    );
+}
+
+#[test]
+fn offsets_include_padding() {
+    let vertex = IdPos(Id(0), Position([0.0; 3]));
+
+    let format = IdPos::VERTEX;
+    assert_eq!(format.stride as usize, std::mem::size_of::<IdPos>());
+    assert_eq!(format.stride, 16);
+    assert_eq!(<IdPos as WithAttribute<Id>>::ATTRIBUTE.offset, 0);
+    assert_eq!(
+        <IdPos as WithAttribute<Position>>::ATTRIBUTE.offset,
+        offset_of(&vertex, &vertex.1)
+    );
+    assert_eq!(<IdPos as WithAttribute<Position>>::ATTRIBUTE.offset, 4);
+    assert_eq!(IdPos::NAMES, &[Id::NAME, Position::NAME]);
+}
+
+#[test]
+fn compile_errors() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+}
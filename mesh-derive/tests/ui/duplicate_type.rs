@@ -0,0 +1,10 @@
+use rendy_mesh::{AsVertex, Position};
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, AsVertex)]
+struct Vertex {
+    position: Position,
+    previous_position: Position,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use rendy_mesh::{AsVertex, Color, Position};
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, AsVertex)]
+struct Vertex {
+    position: Position,
+    color: Color,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use rendy_mesh::{AsVertex, Color, Position};
+
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, AsVertex)]
+struct Vertex {
+    position: Position,
+    color: Color,
+}
+
+fn main() {}
@@ -0,0 +1,328 @@
+//!
+//! Bounding volumes of meshes for culling.
+//!
+
+use {
+    crate::{
+        mesh::{MeshBuilder, Submesh},
+        vertex::Position,
+    },
+    std::cmp::Ordering,
+};
+
+/// Axis-aligned bounding box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Aabb {
+    /// Minimal coordinates of the box.
+    pub min: [f32; 3],
+
+    /// Maximal coordinates of the box.
+    pub max: [f32; 3],
+}
+
+impl Aabb {
+    /// Center of the box.
+    pub fn center(&self) -> [f32; 3] {
+        [
+            (self.min[0] + self.max[0]) * 0.5,
+            (self.min[1] + self.max[1]) * 0.5,
+            (self.min[2] + self.max[2]) * 0.5,
+        ]
+    }
+
+    /// Half of the box size along each axis.
+    pub fn half_extents(&self) -> [f32; 3] {
+        [
+            (self.max[0] - self.min[0]) * 0.5,
+            (self.max[1] - self.min[1]) * 0.5,
+            (self.max[2] - self.min[2]) * 0.5,
+        ]
+    }
+}
+
+/// Sphere that contains all vertices.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BoundingSphere {
+    /// Center of the sphere.
+    pub center: [f32; 3],
+
+    /// Radius of the sphere.
+    pub radius: f32,
+}
+
+/// Bounding volumes of mesh or submesh.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Bounds {
+    /// Axis-aligned bounding box.
+    pub aabb: Aabb,
+
+    /// Bounding sphere.
+    pub sphere: BoundingSphere,
+}
+
+impl Bounds {
+    /// Compute bounds of positions.
+    /// Returns `None` if there are no positions.
+    ///
+    /// Sphere is the smaller of the sphere around box center
+    /// and the one found with Ritter's algorithm.
+    pub fn from_positions(positions: &[Position]) -> Option<Self> {
+        let first = positions.first()?.0;
+
+        let mut aabb = Aabb {
+            min: first,
+            max: first,
+        };
+        for &Position(p) in positions {
+            for ((min, max), &value) in aabb.min.iter_mut().zip(&mut aabb.max).zip(&p) {
+                *min = min.min(value);
+                *max = max.max(value);
+            }
+        }
+
+        let center = aabb.center();
+        let box_sphere = BoundingSphere {
+            center,
+            radius: positions
+                .iter()
+                .map(|&Position(p)| distance(center, p))
+                .fold(0.0, f32::max),
+        };
+
+        let sphere = ritter_sphere(positions);
+        Some(Bounds {
+            aabb,
+            sphere: if sphere.radius < box_sphere.radius {
+                sphere
+            } else {
+                box_sphere
+            },
+        })
+    }
+}
+
+/// Approximate bounding sphere with Ritter's algorithm.
+/// `positions` must not be empty.
+fn ritter_sphere(positions: &[Position]) -> BoundingSphere {
+    let farthest = |from: [f32; 3]| {
+        positions
+            .iter()
+            .map(|&Position(p)| p)
+            .max_by(|&a, &b| {
+                distance(from, a)
+                    .partial_cmp(&distance(from, b))
+                    .unwrap_or(Ordering::Equal)
+            })
+            .unwrap()
+    };
+
+    let a = farthest(positions[0].0);
+    let b = farthest(a);
+    let mut center = [
+        (a[0] + b[0]) * 0.5,
+        (a[1] + b[1]) * 0.5,
+        (a[2] + b[2]) * 0.5,
+    ];
+    let mut radius = distance(a, b) * 0.5;
+
+    for &Position(p) in positions {
+        let d = distance(center, p);
+        if d > radius {
+            // Grow sphere to touch the point, keeping opposite side in place.
+            let new_radius = (radius + d) * 0.5;
+            let shift = (new_radius - radius) / d;
+            for (center, &value) in center.iter_mut().zip(&p) {
+                *center += (value - *center) * shift;
+            }
+            radius = new_radius;
+        }
+    }
+
+    // Compensate for rounding errors.
+    BoundingSphere {
+        center,
+        radius: positions
+            .iter()
+            .map(|&Position(p)| distance(center, p))
+            .fold(radius, f32::max),
+    }
+}
+
+fn distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    let d = [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+    (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt()
+}
+
+impl<'a> MeshBuilder<'a> {
+    /// Compute bounds of the mesh and all its submeshes from `Position` attribute
+    /// and store them in the builder.
+    pub fn with_computed_bounds(mut self) -> Result<Self, failure::Error> {
+        self.compute_bounds()?;
+        Ok(self)
+    }
+
+    /// Compute bounds of the mesh and all its submeshes from `Position` attribute
+    /// and store them in the builder.
+    ///
+    /// Bounds are not computed by `MeshBuilder::build` and `DynamicMesh::update`,
+    /// only bounds stored in the builder are passed to the mesh.
+    pub fn compute_bounds(&mut self) -> Result<&mut Self, failure::Error> {
+        let positions = self
+            .attribute::<Position>()
+            .ok_or_else(|| failure::format_err!("Mesh has no positions"))?;
+        let indices = self.read_indices();
+
+        let bounds = Bounds::from_positions(&positions);
+        let submeshes = self
+            .submeshes()
+            .iter()
+            .map(|submesh| submesh_bounds(submesh, &positions, indices.as_deref()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.set_bounds(bounds);
+        for (submesh, bounds) in self.submeshes_mut().iter_mut().zip(submeshes) {
+            submesh.bounds = bounds;
+        }
+        Ok(self)
+    }
+}
+
+/// Compute bounds of vertices referenced by submesh.
+pub(crate) fn submesh_bounds(
+    submesh: &Submesh,
+    positions: &[Position],
+    indices: Option<&[u32]>,
+) -> Result<Option<Bounds>, failure::Error> {
+    let vertex = |index: u32| {
+        let vertex = i64::from(index) + i64::from(submesh.vertex_offset);
+        positions
+            .get(vertex as usize)
+            .filter(|_| vertex >= 0)
+            .cloned()
+            .ok_or_else(|| {
+                failure::format_err!(
+                    "Submesh refers to vertex {} but mesh has only {} vertices",
+                    vertex,
+                    positions.len()
+                )
+            })
+    };
+
+    let range = submesh.range.start as usize..submesh.range.end as usize;
+    let submesh_positions = match indices {
+        Some(indices) => indices
+            .get(range.clone())
+            .ok_or_else(|| failure::format_err!("Submesh range {:?} is out of indices", range))?
+            .iter()
+            .map(|&index| vertex(index))
+            .collect::<Result<Vec<_>, _>>()?,
+        None => submesh
+            .range
+            .clone()
+            .map(vertex)
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+    Ok(Bounds::from_positions(&submesh_positions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(points: &[[f32; 3]]) -> Vec<Position> {
+        points.iter().map(|&p| Position(p)).collect()
+    }
+
+    #[test]
+    fn aabb_of_positions() {
+        let bounds = Bounds::from_positions(&positions(&[
+            [1.0, -2.0, 0.5],
+            [-1.0, 4.0, 0.0],
+            [0.0, 0.0, 3.5],
+        ]))
+        .unwrap();
+        assert_eq!(bounds.aabb.min, [-1.0, -2.0, 0.0]);
+        assert_eq!(bounds.aabb.max, [1.0, 4.0, 3.5]);
+        assert_eq!(bounds.aabb.center(), [0.0, 1.0, 1.75]);
+        assert_eq!(bounds.aabb.half_extents(), [1.0, 3.0, 1.75]);
+
+        assert_eq!(Bounds::from_positions(&[]), None);
+    }
+
+    #[test]
+    fn sphere_contains_all_positions() {
+        let grid = (0..5)
+            .flat_map(|x| (0..5).map(move |y| [x as f32, y as f32, (x * y % 3) as f32]))
+            .collect::<Vec<_>>();
+        let sets = [
+            vec![[0.0, 0.0, 0.0]],
+            vec![[1.0, 0.0, 0.0], [-1.0, 0.0, 0.0]],
+            vec![
+                [1.0, 0.0, 0.0],
+                [-1.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0],
+                [0.0, -1.0, 0.0],
+                [0.0, 0.0, 1.0],
+                [0.0, 0.0, -1.0],
+            ],
+            grid,
+        ];
+        for set in &sets {
+            let sphere = Bounds::from_positions(&positions(set)).unwrap().sphere;
+            for &p in set {
+                assert!(
+                    distance(sphere.center, p) <= sphere.radius,
+                    "{:?} is outside of {:?}",
+                    p,
+                    sphere
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sphere_with_nan_does_not_panic() {
+        let _ = Bounds::from_positions(&positions(&[
+            [0.0, 0.0, 0.0],
+            [f32::NAN, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+        ]));
+    }
+
+    #[test]
+    fn submesh_bounds_with_vertex_offset() {
+        let points = positions(&[
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [10.0, 10.0, 10.0],
+            [11.0, 10.0, 10.0],
+            [10.0, 12.0, 10.0],
+        ]);
+        let submesh = |range, vertex_offset| Submesh {
+            range,
+            vertex_offset,
+            material: 0,
+            bounds: None,
+        };
+
+        let indices = [0, 1, 2];
+        let bounds = submesh_bounds(&submesh(0..3, 3), &points, Some(&indices))
+            .unwrap()
+            .unwrap();
+        assert_eq!(bounds.aabb.min, [10.0, 10.0, 10.0]);
+        assert_eq!(bounds.aabb.max, [11.0, 12.0, 10.0]);
+
+        let bounds = submesh_bounds(&submesh(1..3, 3), &points, None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(bounds.aabb.min, [10.0, 10.0, 10.0]);
+        assert_eq!(bounds.aabb.max, [11.0, 12.0, 10.0]);
+
+        assert!(submesh_bounds(&submesh(0..3, 4), &points, Some(&indices)).is_err());
+        assert!(submesh_bounds(&submesh(0..3, -1), &points, None).is_err());
+    }
+}
@@ -0,0 +1,194 @@
+//!
+//! Meshes with content updated by host, e.g. every frame.
+//!
+
+use std::ops::Range;
+
+use gfx_hal::format::Format;
+
+use crate::{
+    bounds::Bounds,
+    command::{EncoderCommon, Graphics, RenderPassEncoder, Supports},
+    factory::Factory,
+    memory::Dynamic,
+    mesh::{Incompatible, MeshBuilder, Streams, Submesh, STREAM_ALIGN},
+    resource::{Buffer, BufferInfo, Escape},
+    vertex::VertexFormat,
+};
+
+/// Mesh which content can be replaced after creation.
+///
+/// Mesh data is stored in host-visible memory with separate buffer for each frame in flight,
+/// so that content for the next frame can be written while previous frames are still drawn.
+/// Buffers grow as needed and never shrink.
+#[derive(Debug)]
+pub struct DynamicMesh<B: gfx_hal::Backend> {
+    frames: Vec<DynamicFrame<B>>,
+}
+
+#[derive(Debug)]
+struct DynamicFrame<B: gfx_hal::Backend> {
+    buffer: Option<Escape<Buffer<B>>>,
+    streams: Option<Streams>,
+    prim: gfx_hal::Primitive,
+}
+
+impl<B> DynamicMesh<B>
+where
+    B: gfx_hal::Backend,
+{
+    /// Create empty mesh with storage for specified number of frames in flight.
+    /// No memory is allocated until the first update.
+    pub fn new(frames: usize) -> Self {
+        DynamicMesh {
+            frames: (0..frames)
+                .map(|_| DynamicFrame {
+                    buffer: None,
+                    streams: None,
+                    prim: gfx_hal::Primitive::TriangleList,
+                })
+                .collect(),
+        }
+    }
+
+    /// Number of frames in flight this mesh has storage for.
+    pub fn frames(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// gfx_hal::Primitive type of the frame's content.
+    /// It is taken from the `MeshBuilder` of the last update of the frame.
+    pub fn primitive(&self, frame: usize) -> gfx_hal::Primitive {
+        self.frames[frame].prim
+    }
+
+    /// Get size of storage of the frame in bytes.
+    pub fn capacity(&self, frame: usize) -> u64 {
+        self.frames[frame]
+            .buffer
+            .as_ref()
+            .map_or(0, |buffer| buffer.size())
+    }
+
+    /// Get number of vertices in the frame's content.
+    pub fn len(&self, frame: usize) -> u32 {
+        self.frames[frame].streams.as_ref().map_or(0, Streams::len)
+    }
+
+    /// Get submeshes of the frame's content.
+    pub fn submeshes(&self, frame: usize) -> &[Submesh] {
+        self.frames[frame]
+            .streams
+            .as_ref()
+            .map_or(&[], Streams::submeshes)
+    }
+
+    /// Get bounds of the frame's content.
+    pub fn bounds(&self, frame: usize) -> Option<&Bounds> {
+        self.frames[frame]
+            .streams
+            .as_ref()
+            .and_then(Streams::bounds)
+    }
+
+    /// Replace content of the frame with vertices and indices from `builder`.
+    /// Storage of the frame is reallocated if it is too small.
+    ///
+    /// # Safety
+    ///
+    /// Storage of the frame must not be used by device,
+    /// e.g. command buffers submitted for this frame must be complete.
+    pub unsafe fn update(
+        &mut self,
+        frame: usize,
+        builder: &MeshBuilder<'_>,
+        factory: &Factory<B>,
+    ) -> Result<(), failure::Error> {
+        let (data, streams) = builder.pack(0)?;
+        let frame = &mut self.frames[frame];
+
+        let size = data.len() as u64;
+        let reallocate = match frame.buffer {
+            Some(ref buffer) => buffer.size() < size,
+            None => true,
+        };
+        if reallocate {
+            // Old buffer escapes to the factory which destroys it when device is done with it.
+            frame.buffer = Some(factory.create_buffer(
+                BufferInfo {
+                    size: size.next_power_of_two().max(STREAM_ALIGN),
+                    usage: gfx_hal::buffer::Usage::VERTEX | gfx_hal::buffer::Usage::INDEX,
+                },
+                Dynamic,
+            )?);
+        }
+
+        let buffer = frame.buffer.as_mut().unwrap();
+        factory.upload_visible_buffer(buffer, 0, &data)?;
+        frame.streams = Some(streams);
+        frame.prim = builder.primitive();
+        Ok(())
+    }
+
+    /// Get vertex formats for graphics pipeline that reads specified attributes
+    /// from the frame's content.
+    /// See `Mesh::vertex_formats`.
+    pub fn vertex_formats(
+        &self,
+        frame: usize,
+        attributes: &[(&str, Format)],
+    ) -> Result<Vec<VertexFormat<'static>>, Incompatible> {
+        self.frames[frame]
+            .streams
+            .as_ref()
+            .ok_or(Incompatible)?
+            .vertex_formats(attributes)
+    }
+
+    /// Bind buffers of the frame to specified attribute locations.
+    /// See `Mesh::bind`.
+    ///
+    /// Frame that was never updated is incompatible with any format.
+    pub fn bind<C>(
+        &self,
+        frame: usize,
+        formats: &[VertexFormat<'_>],
+        encoder: &mut EncoderCommon<'_, B, C>,
+    ) -> Result<u32, Incompatible>
+    where
+        C: Supports<Graphics>,
+    {
+        let frame = &self.frames[frame];
+        match (&frame.buffer, &frame.streams) {
+            (Some(buffer), Some(streams)) => streams.bind(buffer, formats, encoder),
+            _ => Err(Incompatible),
+        }
+    }
+
+    /// Draw submesh of the frame's content.
+    /// Frame must be bound with `bind` first.
+    pub fn draw_submesh(
+        &self,
+        frame: usize,
+        index: usize,
+        instances: Range<u32>,
+        encoder: &mut RenderPassEncoder<'_, B>,
+    ) {
+        if let Some(streams) = &self.frames[frame].streams {
+            streams.draw_submesh(index, instances, encoder);
+        }
+    }
+
+    /// Draw all submeshes of the frame's content.
+    /// Frame must be bound with `bind` first.
+    pub fn draw(
+        &self,
+        frame: usize,
+        instances: Range<u32>,
+        encoder: &mut RenderPassEncoder<'_, B>,
+    ) {
+        if let Some(streams) = &self.frames[frame].streams {
+            streams.draw(instances, encoder);
+        }
+    }
+}
@@ -1,2 +1,5 @@
+pub mod binary;
+#[cfg(feature = "gltf")]
+pub mod gltf;
 #[cfg(feature = "obj")]
 pub mod obj;
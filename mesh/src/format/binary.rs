@@ -0,0 +1,514 @@
+//! Compact binary format for caching cooked meshes.
+//!
+//! File starts with header describing vertex formats, attribute names, index type,
+//! primitive type, submeshes and bounds. Header is followed by raw vertex and index data,
+//! each stream aligned to 16 bytes from the start of the file.
+//! All values are little-endian.
+//!
+//! Attribute formats are stored as discriminants of `gfx_hal::format::Format`,
+//! which are numbered in declaration order starting from 1 in gfx-hal 0.1.
+//! `BINARY_MESH_VERSION` must be bumped whenever gfx-hal update changes this numbering.
+//!
+//! Meshes are loaded without copying vertex and index data,
+//! so that `MeshBuilder` can borrow streams directly from memory-mapped file.
+
+use {
+    crate::{
+        bounds::{Aabb, BoundingSphere, Bounds},
+        mesh::{align_stream, MeshBuilder, Submesh, STREAM_ALIGN},
+        vertex::{Attribute, Position, VertexFormat},
+    },
+    gfx_hal::{format::Format, IndexType, Primitive},
+    std::{borrow::Cow, convert::TryInto, io::Write},
+};
+
+/// Magic bytes at the start of the binary mesh.
+pub const BINARY_MESH_MAGIC: [u8; 4] = *b"RMSH";
+
+/// Version of binary mesh format written by `write_binary`.
+/// Only this version can be loaded.
+pub const BINARY_MESH_VERSION: u32 = 1;
+
+/// Write mesh in binary format.
+///
+/// Bounds of the mesh and submeshes are written as stored in the builder.
+/// Mesh bounds are computed from `Position` attribute if not stored.
+pub fn write_binary(
+    builder: &MeshBuilder<'_>,
+    mut writer: impl Write,
+) -> Result<(), failure::Error> {
+    check_endianness()?;
+
+    let bounds = builder.bounds().cloned().or_else(|| {
+        builder
+            .attribute::<Position>()
+            .and_then(|positions| Bounds::from_positions(&positions))
+    });
+
+    // Header has the same size regardless of stream offsets.
+    let header_size = header(builder, bounds.as_ref(), &[], 0)?.len() as u64;
+
+    let mut offsets = Vec::new();
+    let mut size = align_stream(header_size);
+    for (vertices, _, _) in builder.raw_vertices() {
+        offsets.push(size);
+        size = align_stream(size + vertices.len() as u64);
+    }
+    let index_offset = size;
+
+    let header = header(builder, bounds.as_ref(), &offsets, index_offset)?;
+    debug_assert_eq!(header.len() as u64, header_size);
+    writer.write_all(&header)?;
+
+    let mut written = header_size;
+    let streams = builder
+        .raw_vertices()
+        .map(|(vertices, _, _)| vertices)
+        .chain(builder.raw_indices().map(|(indices, _)| indices));
+    for stream in streams {
+        let padding = align_stream(written) - written;
+        writer.write_all(&[0; STREAM_ALIGN as usize][..padding as usize])?;
+        writer.write_all(stream)?;
+        written += padding + stream.len() as u64;
+    }
+    Ok(())
+}
+
+/// Write mesh in binary format into new vector.
+pub fn to_binary(builder: &MeshBuilder<'_>) -> Result<Vec<u8>, failure::Error> {
+    let mut bytes = Vec::new();
+    write_binary(builder, &mut bytes)?;
+    Ok(bytes)
+}
+
+/// Load mesh from binary format.
+///
+/// Vertex and index data are borrowed from `bytes` without copying,
+/// e.g. from memory-mapped file that outlives the builder.
+/// Use `MeshBuilder::into_owned` to release `bytes`.
+pub fn load_from_binary(bytes: &[u8]) -> Result<MeshBuilder<'_>, failure::Error> {
+    check_endianness()?;
+
+    let mut reader = Reader { bytes, pos: 0 };
+    if reader.bytes(4)? != BINARY_MESH_MAGIC {
+        failure::bail!("Not a binary mesh");
+    }
+    let version = reader.u32()?;
+    if version != BINARY_MESH_VERSION {
+        failure::bail!(
+            "Unsupported binary mesh version {}. Expected {}",
+            version,
+            BINARY_MESH_VERSION
+        );
+    }
+
+    let mut builder = MeshBuilder::new();
+    builder.set_prim_type(read_primitive(&mut reader)?);
+
+    let index_type = match reader.u32()? {
+        0 => None,
+        1 => Some(IndexType::U16),
+        2 => Some(IndexType::U32),
+        index_type => failure::bail!("Invalid index type {}", index_type),
+    };
+    let vertex_buffer_count = reader.u32()?;
+    let submesh_count = reader.u32()?;
+    builder.set_bounds(read_bounds(&mut reader)?);
+
+    let index_offset = reader.u64()?;
+    let index_size = reader.u64()?;
+
+    for _ in 0..vertex_buffer_count {
+        let offset = reader.u64()?;
+        let size = reader.u64()?;
+        let stride = reader.u32()?;
+        let attribute_count = reader.u32()?;
+        if stride == 0 {
+            failure::bail!("Vertex buffer has zero stride");
+        }
+        if size % u64::from(stride) != 0 {
+            failure::bail!(
+                "Vertex buffer size {} is not a multiple of stride {}",
+                size,
+                stride
+            );
+        }
+
+        let mut attributes = Vec::new();
+        let mut names = Vec::<Cow<'static, str>>::new();
+        for _ in 0..attribute_count {
+            let format = read_format(reader.u32()?)?;
+            let attribute_offset = reader.u32()?;
+            let name_len = reader.u32()? as usize;
+            let name = std::str::from_utf8(reader.bytes(name_len)?)?;
+            let attribute_size = u64::from(format.surface_desc().bits / 8);
+            if u64::from(attribute_offset) + attribute_size > u64::from(stride) {
+                failure::bail!(
+                    "Attribute {:?} of format {:?} at offset {} is out of vertex stride {}",
+                    name,
+                    format,
+                    attribute_offset,
+                    stride
+                );
+            }
+            attributes.push(Attribute {
+                offset: attribute_offset,
+                format,
+            });
+            names.push(Cow::Owned(name.to_owned()));
+        }
+
+        // Vertex buffers without names are written with empty names.
+        if names.iter().all(|name| name.is_empty()) {
+            names.clear();
+        }
+
+        builder.add_raw_vertices(
+            Cow::Borrowed(stream(bytes, offset, size)?),
+            VertexFormat {
+                attributes: attributes.into(),
+                stride,
+            },
+            names,
+        );
+    }
+
+    for _ in 0..submesh_count {
+        let start = reader.u32()?;
+        let end = reader.u32()?;
+        let vertex_offset = reader.u32()? as i32;
+        let material = reader.u32()? as usize;
+        let bounds = read_bounds(&mut reader)?;
+        builder.add_submesh(Submesh {
+            range: start..end,
+            vertex_offset,
+            material,
+            bounds,
+        });
+    }
+
+    if let Some(index_type) = index_type {
+        let index_stride = match index_type {
+            IndexType::U16 => 2,
+            IndexType::U32 => 4,
+        };
+        if index_size % index_stride != 0 {
+            failure::bail!(
+                "Index buffer size {} is not a multiple of index size {}",
+                index_size,
+                index_stride
+            );
+        }
+        builder.set_raw_indices(
+            Cow::Borrowed(stream(bytes, index_offset, index_size)?),
+            index_type,
+        );
+    }
+
+    Ok(builder)
+}
+
+fn check_endianness() -> Result<(), failure::Error> {
+    if cfg!(target_endian = "big") {
+        failure::bail!("Binary meshes are supported only on little-endian targets");
+    }
+    Ok(())
+}
+
+/// Serialize header with specified offsets of vertex buffers and indices.
+fn header(
+    builder: &MeshBuilder<'_>,
+    bounds: Option<&Bounds>,
+    offsets: &[u64],
+    index_offset: u64,
+) -> Result<Vec<u8>, failure::Error> {
+    let mut header = BINARY_MESH_MAGIC.to_vec();
+    put_u32(&mut header, BINARY_MESH_VERSION);
+
+    let (primitive, patch_size) = match builder.primitive() {
+        Primitive::PointList => (0, 0),
+        Primitive::LineList => (1, 0),
+        Primitive::LineStrip => (2, 0),
+        Primitive::TriangleList => (3, 0),
+        Primitive::TriangleStrip => (4, 0),
+        Primitive::LineListAdjacency => (5, 0),
+        Primitive::LineStripAdjacency => (6, 0),
+        Primitive::TriangleListAdjacency => (7, 0),
+        Primitive::TriangleStripAdjacency => (8, 0),
+        Primitive::PatchList(size) => (9, u32::from(size)),
+    };
+    put_u32(&mut header, primitive);
+    put_u32(&mut header, patch_size);
+
+    let indices = builder.raw_indices();
+    put_u32(
+        &mut header,
+        match indices {
+            None => 0,
+            Some((_, IndexType::U16)) => 1,
+            Some((_, IndexType::U32)) => 2,
+        },
+    );
+    put_u32(&mut header, builder.raw_vertices().count() as u32);
+    put_u32(&mut header, builder.submeshes().len() as u32);
+    put_bounds(&mut header, bounds);
+
+    put_u64(&mut header, index_offset);
+    put_u64(
+        &mut header,
+        indices.map_or(0, |(indices, _)| indices.len() as u64),
+    );
+
+    for (index, (vertices, format, names)) in builder.raw_vertices().enumerate() {
+        put_u64(&mut header, offsets.get(index).cloned().unwrap_or(0));
+        put_u64(&mut header, vertices.len() as u64);
+        put_u32(&mut header, format.stride);
+        put_u32(&mut header, format.attributes.len() as u32);
+        for (attribute_index, attribute) in format.attributes.iter().enumerate() {
+            let name = names.get(attribute_index).map_or("", |name| &**name);
+            put_u32(&mut header, attribute.format as u32);
+            put_u32(&mut header, attribute.offset);
+            put_u32(&mut header, name.len() as u32);
+            header.extend_from_slice(name.as_bytes());
+        }
+    }
+
+    for submesh in builder.submeshes() {
+        put_u32(&mut header, submesh.range.start);
+        put_u32(&mut header, submesh.range.end);
+        put_u32(&mut header, submesh.vertex_offset as u32);
+        put_u32(&mut header, submesh.material.try_into()?);
+        put_bounds(&mut header, submesh.bounds.as_ref());
+    }
+
+    Ok(header)
+}
+
+fn put_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(bytes: &mut Vec<u8>, value: u64) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_bounds(bytes: &mut Vec<u8>, bounds: Option<&Bounds>) {
+    put_u32(bytes, bounds.is_some() as u32);
+    let values = bounds.map_or([0.0; 10], |bounds| {
+        let (min, max) = (bounds.aabb.min, bounds.aabb.max);
+        let (center, radius) = (bounds.sphere.center, bounds.sphere.radius);
+        [
+            min[0], min[1], min[2], max[0], max[1], max[2], center[0], center[1], center[2], radius,
+        ]
+    });
+    for value in &values {
+        put_u32(bytes, value.to_bits());
+    }
+}
+
+/// Cursor over header bytes.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], failure::Error> {
+        let bytes = self
+            .bytes
+            .get(self.pos..)
+            .and_then(|bytes| bytes.get(..len))
+            .ok_or_else(|| failure::format_err!("Unexpected end of binary mesh header"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, failure::Error> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, failure::Error> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into()?))
+    }
+
+    fn f32(&mut self) -> Result<f32, failure::Error> {
+        Ok(f32::from_bits(self.u32()?))
+    }
+}
+
+fn read_primitive(reader: &mut Reader<'_>) -> Result<Primitive, failure::Error> {
+    let primitive = reader.u32()?;
+    let patch_size = reader.u32()?;
+    Ok(match primitive {
+        0 => Primitive::PointList,
+        1 => Primitive::LineList,
+        2 => Primitive::LineStrip,
+        3 => Primitive::TriangleList,
+        4 => Primitive::TriangleStrip,
+        5 => Primitive::LineListAdjacency,
+        6 => Primitive::LineStripAdjacency,
+        7 => Primitive::TriangleListAdjacency,
+        8 => Primitive::TriangleStripAdjacency,
+        9 => Primitive::PatchList(patch_size.try_into()?),
+        _ => failure::bail!("Invalid primitive type {}", primitive),
+    })
+}
+
+fn read_format(format: u32) -> Result<Format, failure::Error> {
+    if format == 0 || format as usize >= gfx_hal::format::NUM_FORMATS {
+        failure::bail!("Invalid attribute format {}", format);
+    }
+    // `Format` is `repr(u32)` with variants numbered from 1 to `NUM_FORMATS` exclusive.
+    // See module documentation about dependency on this numbering.
+    Ok(unsafe { std::mem::transmute::<u32, Format>(format) })
+}
+
+fn read_bounds(reader: &mut Reader<'_>) -> Result<Option<Bounds>, failure::Error> {
+    let present = reader.u32()? != 0;
+    let mut values = [0.0; 10];
+    for value in &mut values {
+        *value = reader.f32()?;
+    }
+    Ok(if present {
+        Some(Bounds {
+            aabb: Aabb {
+                min: [values[0], values[1], values[2]],
+                max: [values[3], values[4], values[5]],
+            },
+            sphere: BoundingSphere {
+                center: [values[6], values[7], values[8]],
+                radius: values[9],
+            },
+        })
+    } else {
+        None
+    })
+}
+
+/// Get stream data from binary mesh.
+fn stream(bytes: &[u8], offset: u64, size: u64) -> Result<&[u8], failure::Error> {
+    offset
+        .checked_add(size)
+        .filter(|&end| end <= bytes.len() as u64)
+        .map(|end| &bytes[offset as usize..end as usize])
+        .ok_or_else(|| {
+            failure::format_err!(
+                "Stream at {}..{} is out of binary mesh of {} bytes",
+                offset,
+                offset.saturating_add(size),
+                bytes.len()
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::vertex::{AsAttribute, Normal, PosTex, TexCoord},
+    };
+
+    /// Offset of the first vertex buffer description in the header of `mesh()`.
+    const VERTEX_BUFFER: usize = 88;
+
+    fn mesh() -> MeshBuilder<'static> {
+        let vertex = |x, y| PosTex {
+            position: Position([x, y, 0.0]),
+            tex_coord: TexCoord([x, y]),
+        };
+        let mut mesh = MeshBuilder::new()
+            .with_vertices(vec![
+                vertex(0.0, 0.0),
+                vertex(1.0, 0.0),
+                vertex(0.0, 1.0),
+                vertex(1.0, 1.0),
+            ])
+            .with_vertices(vec![Normal([0.0, 0.0, 1.0]); 4])
+            .with_indices(vec![0u16, 1, 2, 2, 1, 3])
+            .with_submesh(Submesh {
+                range: 0..3,
+                vertex_offset: 0,
+                material: 0,
+                bounds: None,
+            })
+            .with_submesh(Submesh {
+                range: 3..6,
+                vertex_offset: 0,
+                material: 1,
+                bounds: None,
+            });
+        mesh.set_prim_type(Primitive::PatchList(3));
+        mesh.compute_bounds().unwrap();
+        mesh
+    }
+
+    fn set_u32(bytes: &mut [u8], offset: usize, value: u32) {
+        bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn set_u64(bytes: &mut [u8], offset: usize, value: u64) {
+        bytes[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+    }
+
+    #[test]
+    fn round_trip() {
+        let mesh = mesh();
+        let bytes = to_binary(&mesh).unwrap();
+        let loaded = load_from_binary(&bytes).unwrap();
+
+        assert_eq!(loaded.primitive(), mesh.primitive());
+        assert_eq!(loaded.bounds(), mesh.bounds());
+        assert_eq!(loaded.submeshes(), mesh.submeshes());
+        assert_eq!(loaded.raw_indices(), mesh.raw_indices());
+        assert_eq!(
+            loaded.raw_vertices().collect::<Vec<_>>(),
+            mesh.raw_vertices().collect::<Vec<_>>()
+        );
+        for (vertices, _, _) in loaded.raw_vertices() {
+            let offset = vertices.as_ptr() as usize - bytes.as_ptr() as usize;
+            assert_eq!(offset as u64 % STREAM_ALIGN, 0);
+        }
+        assert_eq!(to_binary(&loaded).unwrap(), bytes);
+    }
+
+    #[test]
+    fn rejects_attribute_out_of_stride() {
+        let mut bytes = to_binary(&mesh()).unwrap();
+        // Offset of the `TexCoord` attribute, so that it ends past the 20 bytes stride.
+        set_u32(
+            &mut bytes,
+            VERTEX_BUFFER + 24 + 12 + Position::NAME.len() + 4,
+            16,
+        );
+        assert!(load_from_binary(&bytes).is_err());
+    }
+
+    #[test]
+    fn rejects_partial_streams() {
+        let bytes = to_binary(&mesh()).unwrap();
+
+        let mut partial_vertex = bytes.clone();
+        set_u64(&mut partial_vertex, VERTEX_BUFFER + 8, 4 * 20 - 4);
+        assert!(load_from_binary(&partial_vertex).is_err());
+
+        let mut partial_index = bytes.clone();
+        set_u64(&mut partial_index, VERTEX_BUFFER - 8, 6 * 2 - 1);
+        assert!(load_from_binary(&partial_index).is_err());
+    }
+
+    #[test]
+    fn format_numbering() {
+        // Format numbering of gfx-hal is part of the binary format.
+        assert_eq!(Format::Rg4Unorm as u32, 1);
+        assert_eq!(Format::Rgba8Unorm as u32, 37);
+        assert_eq!(Format::Rg32Float as u32, 103);
+        assert_eq!(Format::Rgb32Float as u32, 106);
+        assert_eq!(Format::Rgba32Float as u32, 109);
+        assert_eq!(
+            read_format(Format::Rgb32Float as u32).unwrap(),
+            Format::Rgb32Float
+        );
+        assert!(read_format(0).is_err());
+        assert!(read_format(gfx_hal::format::NUM_FORMATS as u32).is_err());
+    }
+}
@@ -0,0 +1,341 @@
+//! Loading mesh data from glTF 2.0 format.
+
+use {
+    crate::{
+        mesh::MeshBuilder,
+        vertex::{Color, Joints, Normal, Position, Tangent, TexCoord, Weights},
+    },
+    ::gltf::{
+        buffer::Source,
+        mesh::{util::ReadIndices, Mode},
+    },
+    std::path::Path,
+};
+
+/// Mesh loaded from glTF document.
+#[derive(Debug)]
+pub struct GltfMesh {
+    /// Name of the mesh.
+    pub name: Option<String>,
+
+    /// Primitives of the mesh.
+    pub primitives: Vec<GltfPrimitive>,
+}
+
+/// Single primitive of glTF mesh.
+#[derive(Debug)]
+pub struct GltfPrimitive {
+    /// Index of the material in glTF document.
+    pub material: Option<usize>,
+
+    /// Builder with all vertex attributes and indices of the primitive.
+    ///
+    /// Each attribute is stored in separate vertex buffer.
+    /// Multiple texture coordinate, color, joint and weight sets
+    /// are added in order of their set indices.
+    pub mesh: MeshBuilder<'static>,
+}
+
+/// Load meshes from glTF or binary glTF (`.glb`) file.
+/// External buffers are resolved relative to file's directory.
+pub fn load_from_gltf_file(path: impl AsRef<Path>) -> Result<Vec<GltfMesh>, failure::Error> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path)?;
+    load_from_gltf(&bytes, path.parent())
+}
+
+/// Load meshes from glTF or binary glTF (`.glb`) data.
+///
+/// Buffers may be embedded as binary chunk or base64 data URIs.
+/// External buffers are resolved relative to `base` directory
+/// and can't be loaded if it is `None`.
+pub fn load_from_gltf(bytes: &[u8], base: Option<&Path>) -> Result<Vec<GltfMesh>, failure::Error> {
+    let ::gltf::Gltf { document, mut blob } = ::gltf::Gltf::from_slice(bytes)?;
+
+    let buffers = document
+        .buffers()
+        .map(|buffer| {
+            let data = match buffer.source() {
+                Source::Bin => blob.take().ok_or_else(|| {
+                    failure::format_err!("Buffer {} refers to missing binary chunk", buffer.index())
+                })?,
+                Source::Uri(uri) if uri.starts_with("data:") => {
+                    const BASE64: &str = ";base64,";
+                    let start = uri.find(BASE64).ok_or_else(|| {
+                        failure::format_err!(
+                            "Buffer {} has unsupported data URI. Only base64 encoding is supported",
+                            buffer.index()
+                        )
+                    })?;
+                    decode_base64(&uri[start + BASE64.len()..])?
+                }
+                Source::Uri(uri) => {
+                    let base = base.ok_or_else(|| {
+                        failure::format_err!(
+                            "Buffer {} refers to external file '{}' but base path is not provided",
+                            buffer.index(),
+                            uri
+                        )
+                    })?;
+                    std::fs::read(base.join(uri))?
+                }
+            };
+
+            if data.len() < buffer.length() {
+                failure::bail!(
+                    "Buffer {} has {} bytes, but {} bytes expected",
+                    buffer.index(),
+                    data.len(),
+                    buffer.length()
+                );
+            }
+            Ok(data)
+        })
+        .collect::<Result<Vec<_>, failure::Error>>()?;
+
+    document
+        .meshes()
+        .map(|mesh| {
+            Ok(GltfMesh {
+                name: mesh.name().map(String::from),
+                primitives: mesh
+                    .primitives()
+                    .map(|primitive| {
+                        Ok(GltfPrimitive {
+                            material: primitive.material().index(),
+                            mesh: load_primitive(&primitive, &buffers).map_err(|err| {
+                                failure::format_err!(
+                                    "Failed to load primitive {} of mesh {}: {}",
+                                    primitive.index(),
+                                    mesh.index(),
+                                    err
+                                )
+                            })?,
+                        })
+                    })
+                    .collect::<Result<_, failure::Error>>()?,
+            })
+        })
+        .collect()
+}
+
+fn load_primitive(
+    primitive: &::gltf::Primitive<'_>,
+    buffers: &[Vec<u8>],
+) -> Result<MeshBuilder<'static>, failure::Error> {
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(Vec::as_slice));
+
+    let positions = reader
+        .read_positions()
+        .ok_or_else(|| failure::format_err!("Primitive has no positions"))?
+        .map(Position)
+        .collect::<Vec<_>>();
+    let count = positions.len();
+
+    let check = |name: &str, len: usize| {
+        if len != count {
+            failure::bail!("Primitive has {} {} but {} positions", len, name, count);
+        }
+        Ok(())
+    };
+
+    let mut builder = MeshBuilder::new();
+    builder.add_vertices(positions);
+
+    if let Some(normals) = reader.read_normals() {
+        let normals = normals.map(Normal).collect::<Vec<_>>();
+        check("normals", normals.len())?;
+        builder.add_vertices(normals);
+    }
+
+    if let Some(tangents) = reader.read_tangents() {
+        let tangents = tangents.map(Tangent).collect::<Vec<_>>();
+        check("tangents", tangents.len())?;
+        builder.add_vertices(tangents);
+    }
+
+    let mut set = 0;
+    while let Some(tex_coords) = reader.read_tex_coords(set) {
+        let tex_coords = tex_coords.into_f32().map(TexCoord).collect::<Vec<_>>();
+        check("texture coordinates", tex_coords.len())?;
+        builder.add_vertices(tex_coords);
+        set += 1;
+    }
+
+    let mut set = 0;
+    while let Some(colors) = reader.read_colors(set) {
+        let colors = colors.into_rgba_f32().map(Color).collect::<Vec<_>>();
+        check("colors", colors.len())?;
+        builder.add_vertices(colors);
+        set += 1;
+    }
+
+    let mut set = 0;
+    while let Some(joints) = reader.read_joints(set) {
+        let joints = joints.into_u16().map(Joints).collect::<Vec<_>>();
+        check("joints", joints.len())?;
+        builder.add_vertices(joints);
+        set += 1;
+    }
+
+    let mut set = 0;
+    while let Some(weights) = reader.read_weights(set) {
+        let weights = weights.into_f32().map(Weights).collect::<Vec<_>>();
+        check("weights", weights.len())?;
+        builder.add_vertices(weights);
+        set += 1;
+    }
+
+    builder.sort_vertices();
+
+    let (mut indices, wide) = match reader.read_indices() {
+        Some(ReadIndices::U8(indices)) => (Some(indices.map(u32::from).collect::<Vec<_>>()), false),
+        Some(ReadIndices::U16(indices)) => (Some(indices.map(u32::from).collect()), false),
+        Some(ReadIndices::U32(indices)) => (Some(indices.collect()), true),
+        None => (None, count > u16::MAX as usize + 1),
+    };
+
+    if let Some(&index) = indices
+        .iter()
+        .flatten()
+        .find(|&&index| index as usize >= count)
+    {
+        failure::bail!("Primitive has index {} but only {} vertices", index, count);
+    }
+
+    // Line loops and triangle fans are not supported by `gfx_hal::Primitive`
+    // and are converted into line strips and triangle lists respectively.
+    let prim = match primitive.mode() {
+        Mode::Points => gfx_hal::Primitive::PointList,
+        Mode::Lines => gfx_hal::Primitive::LineList,
+        Mode::LineStrip => gfx_hal::Primitive::LineStrip,
+        Mode::Triangles => gfx_hal::Primitive::TriangleList,
+        Mode::TriangleStrip => gfx_hal::Primitive::TriangleStrip,
+        Mode::LineLoop => {
+            let mut loop_indices = indices.unwrap_or_else(|| (0..count as u32).collect());
+            if let Some(&first) = loop_indices.first() {
+                loop_indices.push(first);
+            }
+            indices = Some(loop_indices);
+            gfx_hal::Primitive::LineStrip
+        }
+        Mode::TriangleFan => {
+            let fan_indices = indices.unwrap_or_else(|| (0..count as u32).collect());
+            indices = Some(
+                fan_indices
+                    .windows(2)
+                    .skip(1)
+                    .flat_map(|edge| vec![fan_indices[0], edge[0], edge[1]])
+                    .collect(),
+            );
+            gfx_hal::Primitive::TriangleList
+        }
+    };
+    builder.set_prim_type(prim);
+
+    match indices {
+        Some(indices) if wide => {
+            builder.set_indices(indices);
+        }
+        Some(indices) => {
+            builder.set_indices(
+                indices
+                    .into_iter()
+                    .map(|index| index as u16)
+                    .collect::<Vec<_>>(),
+            );
+        }
+        None => {}
+    }
+
+    Ok(builder)
+}
+
+/// Decode standard base64 with optional padding.
+fn decode_base64(encoded: &str) -> Result<Vec<u8>, failure::Error> {
+    let padding = encoded
+        .bytes()
+        .rev()
+        .take_while(|&byte| byte == b'=')
+        .count();
+    let encoded = &encoded[..encoded.len() - padding];
+    // `is_multiple_of` requires newer compiler.
+    #[allow(clippy::manual_is_multiple_of)]
+    if padding > 2 || (padding > 0 && (encoded.len() + padding) % 4 != 0) {
+        failure::bail!("Invalid base64 padding");
+    }
+
+    let mut data = Vec::with_capacity(encoded.len() / 4 * 3 + 2);
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for byte in encoded.bytes() {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => failure::bail!("Invalid base64 character '{}'", byte as char),
+        };
+        acc = (acc << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            data.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+
+    // Single trailing character can't encode a whole byte.
+    if bits >= 6 {
+        failure::bail!("Invalid base64 length");
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_base64;
+
+    #[test]
+    fn decodes_rfc4648_vectors() {
+        let vectors: &[(&str, &[u8])] = &[
+            ("", b""),
+            ("Zg==", b"f"),
+            ("Zm8=", b"fo"),
+            ("Zm9v", b"foo"),
+            ("Zm9vYg==", b"foob"),
+            ("Zm9vYmE=", b"fooba"),
+            ("Zm9vYmFy", b"foobar"),
+        ];
+        for &(encoded, decoded) in vectors {
+            assert_eq!(decode_base64(encoded).unwrap(), decoded, "{}", encoded);
+        }
+    }
+
+    #[test]
+    fn decodes_without_padding() {
+        assert_eq!(decode_base64("Zg").unwrap(), b"f");
+        assert_eq!(decode_base64("Zm8").unwrap(), b"fo");
+    }
+
+    #[test]
+    fn decodes_all_alphabet() {
+        let data = (0..=255u8).collect::<Vec<_>>();
+        let encoded = "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8gISIjJCUmJygpKissLS4vMDEy\
+                       MzQ1Njc4OTo7PD0+P0BBQkNERUZHSElKS0xNTk9QUVJTVFVWV1hZWltcXV5fYGFiY2Rl\
+                       ZmdoaWprbG1ub3BxcnN0dXZ3eHl6e3x9fn+AgYKDhIWGh4iJiouMjY6PkJGSk5SVlpeY\
+                       mZqbnJ2en6ChoqOkpaanqKmqq6ytrq+wsbKztLW2t7i5uru8vb6/wMHCw8TFxsfIycrL\
+                       zM3Oz9DR0tPU1dbX2Nna29zd3t/g4eLj5OXm5+jp6uvs7e7v8PHy8/T19vf4+fr7/P3+/w==";
+        assert_eq!(decode_base64(encoded).unwrap(), data);
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        assert!(decode_base64("Zm9v!").is_err());
+        assert!(decode_base64("Zm9vY").is_err());
+        assert!(decode_base64("Zg=").is_err());
+        assert!(decode_base64("Zg===").is_err());
+        assert!(decode_base64("Z=g=").is_err());
+    }
+}
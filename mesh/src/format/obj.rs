@@ -5,21 +5,29 @@ use {
         mesh::MeshBuilder,
         vertex::{Normal, PosNormTex, Position, TexCoord},
     },
+    std::{collections::HashMap, path::Path},
     wavefront_obj::obj,
 };
 
 /// Load mesh data from obj.
+///
+/// All objects are merged into single un-indexed triangle list.
+/// Use `load_meshes_from_obj` to keep objects, groups and materials separate.
 pub fn load_from_obj(bytes: &[u8]) -> Result<MeshBuilder<'static>, failure::Error> {
+    let set = parse_obj(bytes)?;
+    let posnormtex = from_data(set);
+    Ok(MeshBuilder::new().with_vertices(posnormtex))
+}
+
+fn parse_obj(bytes: &[u8]) -> Result<obj::ObjSet, failure::Error> {
     let string = std::str::from_utf8(bytes)?;
-    let set = obj::parse(string).map_err(|e| {
+    obj::parse(string).map_err(|e| {
         failure::format_err!(
             "Error during parsing obj-file at line '{}': {}",
             e.line_number,
             e.message
         )
-    })?;
-    let posnormtex = from_data(set);
-    Ok(MeshBuilder::new().with_vertices(posnormtex))
+    })
 }
 
 fn convert(
@@ -63,8 +71,6 @@ fn from_data(obj_set: obj::ObjSet) -> Vec<PosNormTex> {
     // Takes a list of objects that contain geometries that contain shapes that contain
     // vertex/texture/normal indices into the main list of vertices, and converts to a
     // flat vec of `PosNormTex` objects.
-    // TODO: Doesn't differentiate between objects in a `*.obj` file, treats
-    // them all as a single mesh.
     let vertices = obj_set.objects.iter().flat_map(|object| {
         object.geometry.iter().flat_map(move |geometry| {
             geometry
@@ -82,3 +88,583 @@ fn from_data(obj_set: obj::ObjSet) -> Vec<PosNormTex> {
     }
     result
 }
+
+/// Configuration for loading separate meshes from obj.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ObjConfig {
+    /// Compute smooth normals for vertices that don't specify one.
+    /// Normals are computed from triangles only.
+    /// Vertices without normals are left with zero normals otherwise.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub compute_normals: bool,
+}
+
+/// Mesh of single object, group, material and primitive type from obj.
+#[derive(Debug)]
+pub struct ObjMesh {
+    /// Name of the object.
+    pub object: String,
+
+    /// Names of the groups shapes of the mesh belong to.
+    pub groups: Vec<String>,
+
+    /// Name of the material assigned with `usemtl`.
+    pub material: Option<String>,
+
+    /// Indexed `PosNormTex` vertices.
+    /// Points, lines and triangles are placed in separate meshes.
+    pub mesh: MeshBuilder<'static>,
+}
+
+/// Load meshes from obj.
+///
+/// Separate mesh is produced for each object, group, material
+/// and primitive type in order of their appearance.
+/// Vertices with same position, texture coordinate and normal indices are deduplicated.
+pub fn load_meshes_from_obj(
+    bytes: &[u8],
+    config: &ObjConfig,
+) -> Result<Vec<ObjMesh>, failure::Error> {
+    let set = parse_obj(bytes)?;
+
+    let mut meshes = Vec::new();
+    for object in &set.objects {
+        let mut splits: Vec<Split> = Vec::new();
+        let mut lookup = HashMap::new();
+
+        for geometry in &object.geometry {
+            for shape in &geometry.shapes {
+                let (prim, vtns) = match shape.primitive {
+                    obj::Primitive::Point(v) => (gfx_hal::Primitive::PointList, vec![v]),
+                    obj::Primitive::Line(v1, v2) => (gfx_hal::Primitive::LineList, vec![v1, v2]),
+                    obj::Primitive::Triangle(v1, v2, v3) => {
+                        (gfx_hal::Primitive::TriangleList, vec![v1, v2, v3])
+                    }
+                };
+
+                let key = (&geometry.material_name, &shape.groups, prim);
+                let index = *lookup.entry(key).or_insert_with(|| {
+                    splits.push(Split {
+                        groups: shape.groups.clone(),
+                        material: geometry.material_name.clone(),
+                        prim,
+                        vertices: Vec::new(),
+                        sources: Vec::new(),
+                        indices: Vec::new(),
+                        lookup: HashMap::new(),
+                    });
+                    splits.len() - 1
+                });
+
+                let split = &mut splits[index];
+                for (vi, ti, ni) in vtns {
+                    let index = match split.lookup.get(&(vi, ti, ni)) {
+                        Some(&index) => index,
+                        None => {
+                            let index = split.vertices.len() as u32;
+                            split.vertices.push(convert(object, vi, ti, ni));
+                            split.sources.push((vi, ti, ni));
+                            split.lookup.insert((vi, ti, ni), index);
+                            index
+                        }
+                    };
+                    split.indices.push(index);
+                }
+            }
+        }
+
+        for mut split in splits {
+            if config.compute_normals {
+                split.compute_normals();
+            }
+
+            let mut mesh = MeshBuilder::new()
+                .with_vertices(split.vertices)
+                .with_prim_type(split.prim);
+            if split.sources.len() <= u16::MAX as usize + 1 {
+                mesh.set_indices(
+                    split
+                        .indices
+                        .into_iter()
+                        .map(|index| index as u16)
+                        .collect::<Vec<_>>(),
+                );
+            } else {
+                mesh.set_indices(split.indices);
+            }
+
+            meshes.push(ObjMesh {
+                object: object.name.clone(),
+                groups: split.groups,
+                material: split.material,
+                mesh,
+            });
+        }
+    }
+
+    Ok(meshes)
+}
+
+/// Shapes of single object sharing group, material and primitive type.
+struct Split {
+    groups: Vec<String>,
+    material: Option<String>,
+    prim: gfx_hal::Primitive,
+    vertices: Vec<PosNormTex>,
+    sources: Vec<obj::VTNIndex>,
+    indices: Vec<u32>,
+    lookup: HashMap<obj::VTNIndex, u32>,
+}
+
+impl Split {
+    /// Compute area weighted normals for vertices without normals.
+    /// Normals are accumulated per position so that vertices
+    /// differing only in texture coordinates get the same normal.
+    fn compute_normals(&mut self) {
+        if self.prim != gfx_hal::Primitive::TriangleList
+            || self.sources.iter().all(|&(_, _, ni)| ni.is_some())
+        {
+            return;
+        }
+
+        let mut normals = HashMap::new();
+        for triangle in self.indices.chunks_exact(3) {
+            let [a, b, c] = [
+                self.vertices[triangle[0] as usize].position.0,
+                self.vertices[triangle[1] as usize].position.0,
+                self.vertices[triangle[2] as usize].position.0,
+            ];
+            let normal = cross(sub(b, a), sub(c, a));
+            for &index in triangle {
+                let (vi, _, ni) = self.sources[index as usize];
+                if ni.is_none() {
+                    let sum = normals.entry(vi).or_insert([0.0; 3]);
+                    *sum = add(*sum, normal);
+                }
+            }
+        }
+
+        for (vertex, &(vi, _, ni)) in self.vertices.iter_mut().zip(&self.sources) {
+            if ni.is_some() {
+                continue;
+            }
+            if let Some(&[x, y, z]) = normals.get(&vi) {
+                let length = (x * x + y * y + z * z).sqrt();
+                if length > 0.0 {
+                    vertex.normal = Normal([x / length, y / length, z / length]);
+                }
+            }
+        }
+    }
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+/// Material description from mtl file.
+/// Texture maps are stored as paths relative to the mtl file.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ObjMaterial {
+    /// Name of the material as referenced by `usemtl`.
+    pub name: String,
+
+    /// Ambient color (`Ka`).
+    pub ambient: [f32; 3],
+
+    /// Diffuse color (`Kd`).
+    pub diffuse: [f32; 3],
+
+    /// Specular color (`Ks`).
+    pub specular: [f32; 3],
+
+    /// Emissive color (`Ke`).
+    pub emissive: [f32; 3],
+
+    /// Specular exponent (`Ns`).
+    pub shininess: f32,
+
+    /// Index of refraction (`Ni`).
+    pub optical_density: Option<f32>,
+
+    /// Opacity (`d`, or `1 - Tr`).
+    pub alpha: f32,
+
+    /// Illumination model (`illum`).
+    pub illumination: Option<u32>,
+
+    /// Ambient color texture (`map_Ka`).
+    pub ambient_map: Option<String>,
+
+    /// Diffuse color texture (`map_Kd`).
+    pub diffuse_map: Option<String>,
+
+    /// Specular color texture (`map_Ks`).
+    pub specular_map: Option<String>,
+
+    /// Emissive color texture (`map_Ke`).
+    pub emissive_map: Option<String>,
+
+    /// Specular exponent texture (`map_Ns`).
+    pub shininess_map: Option<String>,
+
+    /// Opacity texture (`map_d`).
+    pub alpha_map: Option<String>,
+
+    /// Bump map (`map_Bump` or `bump`).
+    pub bump_map: Option<String>,
+
+    /// Normal map (`norm`).
+    pub normal_map: Option<String>,
+}
+
+impl ObjMaterial {
+    /// Create material with default parameters.
+    pub fn new(name: impl Into<String>) -> Self {
+        ObjMaterial {
+            name: name.into(),
+            ambient: [0.0; 3],
+            diffuse: [1.0; 3],
+            specular: [0.0; 3],
+            emissive: [0.0; 3],
+            shininess: 0.0,
+            optical_density: None,
+            alpha: 1.0,
+            illumination: None,
+            ambient_map: None,
+            diffuse_map: None,
+            specular_map: None,
+            emissive_map: None,
+            shininess_map: None,
+            alpha_map: None,
+            bump_map: None,
+            normal_map: None,
+        }
+    }
+}
+
+/// Load materials from mtl.
+/// Unknown statements are ignored.
+pub fn load_materials_from_mtl(bytes: &[u8]) -> Result<Vec<ObjMaterial>, failure::Error> {
+    let string = std::str::from_utf8(bytes)?;
+    let mut materials: Vec<ObjMaterial> = Vec::new();
+
+    for (number, line) in string.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+        let statement = match tokens.next() {
+            Some(statement) => statement,
+            None => continue,
+        };
+        let args = tokens.collect::<Vec<_>>();
+
+        let error = |message: &str| {
+            failure::format_err!(
+                "Error during parsing mtl-file at line '{}': {}",
+                number + 1,
+                message
+            )
+        };
+        let scalar = |args: &[&str]| -> Result<f32, failure::Error> {
+            args.first()
+                .and_then(|arg| arg.parse().ok())
+                .ok_or_else(|| error(&format!("Expected number for '{}'", statement)))
+        };
+        let color = |args: &[&str]| -> Result<[f32; 3], failure::Error> {
+            match *args {
+                [r] => {
+                    let r = r.parse().map_err(|_| error("Invalid color"))?;
+                    Ok([r, r, r])
+                }
+                [r, g, b, ..] => Ok([
+                    r.parse().map_err(|_| error("Invalid color"))?,
+                    g.parse().map_err(|_| error("Invalid color"))?,
+                    b.parse().map_err(|_| error("Invalid color"))?,
+                ]),
+                _ => Err(error(&format!("Expected color for '{}'", statement))),
+            }
+        };
+        let map = |args: &[&str]| -> Result<String, failure::Error> {
+            texture_path(args)
+                .ok_or_else(|| error(&format!("Expected file name for '{}'", statement)))
+        };
+
+        if statement == "newmtl" {
+            let name = args
+                .first()
+                .ok_or_else(|| error("Expected material name"))?;
+            materials.push(ObjMaterial::new(*name));
+            continue;
+        }
+
+        let material = match materials.last_mut() {
+            Some(material) => material,
+            None => return Err(error(&format!("'{}' before 'newmtl'", statement))),
+        };
+
+        match statement {
+            "Ka" => material.ambient = color(&args)?,
+            "Kd" => material.diffuse = color(&args)?,
+            "Ks" => material.specular = color(&args)?,
+            "Ke" => material.emissive = color(&args)?,
+            "Ns" => material.shininess = scalar(&args)?,
+            "Ni" => material.optical_density = Some(scalar(&args)?),
+            "d" => material.alpha = scalar(&args)?,
+            "Tr" => material.alpha = 1.0 - scalar(&args)?,
+            "illum" => {
+                material.illumination = Some(
+                    args.first()
+                        .and_then(|arg| arg.parse().ok())
+                        .ok_or_else(|| error("Expected illumination model"))?,
+                )
+            }
+            "map_Ka" => material.ambient_map = Some(map(&args)?),
+            "map_Kd" => material.diffuse_map = Some(map(&args)?),
+            "map_Ks" => material.specular_map = Some(map(&args)?),
+            "map_Ke" => material.emissive_map = Some(map(&args)?),
+            "map_Ns" => material.shininess_map = Some(map(&args)?),
+            "map_d" => material.alpha_map = Some(map(&args)?),
+            "map_Bump" | "map_bump" | "bump" => material.bump_map = Some(map(&args)?),
+            "norm" => material.normal_map = Some(map(&args)?),
+            _ => {}
+        }
+    }
+
+    Ok(materials)
+}
+
+/// Skip texture options and join remaining arguments into file name,
+/// so that paths containing spaces are preserved.
+fn texture_path(args: &[&str]) -> Option<String> {
+    let mut args = args.iter().peekable();
+    while let Some(&&option) = args.peek() {
+        let count = match option {
+            "-blendu" | "-blendv" | "-bm" | "-boost" | "-cc" | "-clamp" | "-imfchan"
+            | "-texres" | "-type" => 1,
+            "-mm" => 2,
+            // Up to three numbers may follow.
+            "-o" | "-s" | "-t" => 0,
+            _ => break,
+        };
+        args.next();
+        for _ in 0..count {
+            args.next();
+        }
+        if count == 0 {
+            for _ in 0..3 {
+                match args.peek() {
+                    Some(arg) if arg.parse::<f32>().is_ok() => {
+                        args.next();
+                    }
+                    _ => break,
+                }
+            }
+        }
+    }
+
+    let path = args.cloned().collect::<Vec<_>>().join(" ");
+    if path.is_empty() {
+        None
+    } else {
+        Some(path)
+    }
+}
+
+/// Load meshes from obj file and materials from mtl files it references with `mtllib`.
+/// Material libraries are resolved relative to obj file's directory.
+pub fn load_obj_file(
+    path: impl AsRef<Path>,
+    config: &ObjConfig,
+) -> Result<(Vec<ObjMesh>, Vec<ObjMaterial>), failure::Error> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path)?;
+    let meshes = load_meshes_from_obj(&bytes, config)?;
+
+    let base = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut materials = Vec::new();
+    for line in std::str::from_utf8(&bytes)?.lines() {
+        let mut tokens = line.split_whitespace();
+        if tokens.next() == Some("mtllib") {
+            for library in tokens {
+                let bytes = std::fs::read(base.join(library))?;
+                materials.extend(load_materials_from_mtl(&bytes)?);
+            }
+        }
+    }
+
+    Ok((meshes, materials))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_objects_and_materials() {
+        let obj = b"\
+o first
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+usemtl red
+f 1 2 3
+f 1 3 4
+usemtl blue
+f 1 2 4
+o second
+v 0 0 1
+v 1 0 1
+v 1 1 1
+usemtl red
+f 5 6 7
+";
+        let meshes = load_meshes_from_obj(obj, &ObjConfig::default()).unwrap();
+        assert_eq!(meshes.len(), 3);
+        assert_eq!(meshes[0].object, "first");
+        assert_eq!(meshes[0].material, Some("red".to_owned()));
+        assert_eq!(meshes[1].object, "first");
+        assert_eq!(meshes[1].material, Some("blue".to_owned()));
+        assert_eq!(meshes[2].object, "second");
+        assert_eq!(meshes[2].material, Some("red".to_owned()));
+
+        // Two triangles sharing an edge are deduplicated into 4 vertices.
+        assert_eq!(meshes[0].mesh.vertex_count(), 4);
+        assert_eq!(meshes[0].mesh.read_indices().unwrap().len(), 6);
+        assert_eq!(meshes[1].mesh.vertex_count(), 3);
+        assert_eq!(meshes[2].mesh.vertex_count(), 3);
+    }
+
+    #[test]
+    fn splits_primitive_types() {
+        // Parser reads `l` with single vertex as point.
+        let obj = b"\
+v 0 0 0
+v 1 0 0
+v 1 1 0
+f 1 2 3
+l 1 2
+l 3
+";
+        let meshes = load_meshes_from_obj(obj, &ObjConfig::default()).unwrap();
+        let prims = meshes
+            .iter()
+            .map(|mesh| mesh.mesh.primitive())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            prims,
+            [
+                gfx_hal::Primitive::TriangleList,
+                gfx_hal::Primitive::LineList,
+                gfx_hal::Primitive::PointList,
+            ]
+        );
+        assert_eq!(meshes[1].mesh.read_indices().unwrap(), [0, 1]);
+        assert_eq!(meshes[2].mesh.read_indices().unwrap(), [0]);
+    }
+
+    #[test]
+    fn computes_missing_normals() {
+        let obj = b"\
+v 0 0 0
+v 1 0 0
+v 0 1 0
+vn 1 0 0
+f 1 2 3
+f 1//1 2//1 3//1
+";
+        let mut split = Split {
+            groups: Vec::new(),
+            material: None,
+            prim: gfx_hal::Primitive::TriangleList,
+            vertices: Vec::new(),
+            sources: Vec::new(),
+            indices: Vec::new(),
+            lookup: HashMap::new(),
+        };
+        let set = parse_obj(obj).unwrap();
+        let object = &set.objects[0];
+        for shape in &object.geometry[0].shapes {
+            if let obj::Primitive::Triangle(v1, v2, v3) = shape.primitive {
+                for &(vi, ti, ni) in &[v1, v2, v3] {
+                    split.indices.push(split.vertices.len() as u32);
+                    split.vertices.push(convert(object, vi, ti, ni));
+                    split.sources.push((vi, ti, ni));
+                }
+            }
+        }
+
+        split.compute_normals();
+        for vertex in &split.vertices[..3] {
+            assert_eq!(vertex.normal, Normal([0.0, 0.0, 1.0]));
+        }
+        // Normals specified in file are kept.
+        for vertex in &split.vertices[3..] {
+            assert_eq!(vertex.normal, Normal([1.0, 0.0, 0.0]));
+        }
+    }
+
+    #[test]
+    fn parses_materials() {
+        let mtl = b"\
+# Comment
+newmtl first
+Kd 0.5 0.25 1
+d 0.5
+map_Kd -o 0.5 0.5 -clamp on textures/diffuse map.png
+
+newmtl second
+Kd 0.75
+Tr 0.25
+";
+        let materials = load_materials_from_mtl(mtl).unwrap();
+        assert_eq!(materials.len(), 2);
+
+        assert_eq!(materials[0].name, "first");
+        assert_eq!(materials[0].diffuse, [0.5, 0.25, 1.0]);
+        assert_eq!(materials[0].alpha, 0.5);
+        assert_eq!(
+            materials[0].diffuse_map,
+            Some("textures/diffuse map.png".to_owned())
+        );
+
+        assert_eq!(materials[1].name, "second");
+        assert_eq!(materials[1].diffuse, [0.75; 3]);
+        assert_eq!(materials[1].alpha, 0.75);
+        assert_eq!(materials[1].diffuse_map, None);
+    }
+
+    #[test]
+    fn rejects_statement_before_newmtl() {
+        let error = load_materials_from_mtl(b"Kd 1 1 1\nnewmtl first\n").unwrap_err();
+        assert!(error.to_string().contains("'Kd' before 'newmtl'"));
+    }
+
+    #[test]
+    fn texture_path_options() {
+        assert_eq!(texture_path(&["a.png"]), Some("a.png".to_owned()));
+        assert_eq!(
+            texture_path(&["-s", "2", "a.png"]),
+            Some("a.png".to_owned())
+        );
+        assert_eq!(
+            texture_path(&["-mm", "0", "1", "-bm", "0.5", "b", "c.png"]),
+            Some("b c.png".to_owned())
+        );
+        assert_eq!(texture_path(&["-o", "1"]), None);
+    }
+}
@@ -0,0 +1,491 @@
+//!
+//! Generate normals and tangents for meshes that lack them.
+//!
+
+use {
+    crate::{
+        mesh::MeshBuilder,
+        vertex::{Normal, Position, Tangent, TexCoord},
+    },
+    std::collections::HashMap,
+};
+
+/// Method of normals computation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NormalMode {
+    /// Normal of a vertex is the sum of normals of all triangles
+    /// sharing vertex position, weighted by triangle area.
+    Smooth,
+
+    /// Every triangle gets own vertices with normal of the triangle.
+    /// Indices are removed from the mesh.
+    Flat,
+}
+
+impl<'a> MeshBuilder<'a> {
+    /// Compute normals from positions.
+    /// Existing normals are replaced, otherwise normals are added as new vertex buffer.
+    pub fn with_computed_normals(mut self, mode: NormalMode) -> Result<Self, failure::Error> {
+        self.compute_normals(mode)?;
+        Ok(self)
+    }
+
+    /// Compute normals from positions.
+    /// Existing normals are replaced, otherwise normals are added as new vertex buffer.
+    /// Mesh must be a triangle list with `Position` attribute.
+    pub fn compute_normals(&mut self, mode: NormalMode) -> Result<&mut Self, failure::Error> {
+        // Validate before unwelding.
+        self.triangles()?;
+        if mode == NormalMode::Flat {
+            self.unweld();
+        }
+
+        let triangles = self.triangles()?;
+        let positions = self
+            .attribute::<Position>()
+            .ok_or_else(|| failure::format_err!("Mesh has no positions"))?;
+
+        let normals = match mode {
+            NormalMode::Smooth => {
+                let mut groups = HashMap::new();
+                let group = positions
+                    .iter()
+                    .map(|&Position([x, y, z])| {
+                        let next = groups.len();
+                        *groups
+                            .entry((x.to_bits(), y.to_bits(), z.to_bits()))
+                            .or_insert(next)
+                    })
+                    .collect::<Vec<_>>();
+
+                let mut sums = vec![[0.0; 3]; groups.len()];
+                for triangle in triangles.chunks_exact(3) {
+                    let normal = triangle_normal(&positions, triangle);
+                    for &index in triangle {
+                        let sum = &mut sums[group[index as usize]];
+                        *sum = add(*sum, normal);
+                    }
+                }
+
+                group
+                    .iter()
+                    .map(|&group| Normal(normalize(sums[group])))
+                    .collect::<Vec<_>>()
+            }
+            NormalMode::Flat => {
+                let mut normals = vec![Normal([0.0; 3]); positions.len()];
+                for triangle in triangles.chunks_exact(3) {
+                    let normal = normalize(triangle_normal(&positions, triangle));
+                    for &index in triangle {
+                        normals[index as usize] = Normal(normal);
+                    }
+                }
+                normals
+            }
+        };
+
+        if !self.replace_attribute(&normals) {
+            self.add_vertices(normals);
+            self.sort_vertices();
+        }
+        Ok(self)
+    }
+
+    /// Compute tangents from positions, normals and texture coordinates.
+    /// Existing tangents are replaced, otherwise tangents are added as new vertex buffer.
+    pub fn with_computed_tangents(mut self) -> Result<Self, failure::Error> {
+        self.compute_tangents()?;
+        Ok(self)
+    }
+
+    /// Compute tangents from positions, normals and texture coordinates.
+    /// Existing tangents are replaced, otherwise tangents are added as new vertex buffer.
+    /// Mesh must be a triangle list with `Position`, `Normal` and `TexCoord` attributes.
+    ///
+    /// Tangents are computed the same way as MikkTSpace does:
+    /// per-triangle tangents are projected onto the tangent plane of the vertex
+    /// and summed weighted by the angle of the triangle corner.
+    /// Vertices with same position, normal and texture coordinate are treated as one.
+    /// Unlike MikkTSpace, vertex shared by triangles with opposite texture space orientation
+    /// is not split, and gets the orientation with the most weight.
+    pub fn compute_tangents(&mut self) -> Result<&mut Self, failure::Error> {
+        let triangles = self.triangles()?;
+        let positions = self
+            .attribute::<Position>()
+            .ok_or_else(|| failure::format_err!("Mesh has no positions"))?;
+        let normals = self
+            .attribute::<Normal>()
+            .ok_or_else(|| failure::format_err!("Mesh has no normals"))?;
+        let tex_coords = self
+            .attribute::<TexCoord>()
+            .ok_or_else(|| failure::format_err!("Mesh has no texture coordinates"))?;
+
+        let mut groups = HashMap::new();
+        let group = (0..positions.len())
+            .map(|index| {
+                let next = groups.len();
+                let Position([px, py, pz]) = positions[index];
+                let Normal([nx, ny, nz]) = normals[index];
+                let TexCoord([u, v]) = tex_coords[index];
+                *groups
+                    .entry(
+                        [px, py, pz, nx, ny, nz, u, v]
+                            .iter()
+                            .map(|value| value.to_bits())
+                            .collect::<Vec<_>>(),
+                    )
+                    .or_insert(next)
+            })
+            .collect::<Vec<_>>();
+
+        // Sums of tangents and weights for preserved and flipped orientation.
+        let mut sums = vec![[([0.0; 3], 0.0); 2]; groups.len()];
+        let mut weights = vec![[0.0f32; 2]; positions.len()];
+
+        for triangle in triangles.chunks_exact(3) {
+            let p = |i: usize| positions[triangle[i] as usize].0;
+            let t = |i: usize| tex_coords[triangle[i] as usize].0;
+
+            let d1 = sub(p(1), p(0));
+            let d2 = sub(p(2), p(0));
+            let t21 = [t(1)[0] - t(0)[0], t(1)[1] - t(0)[1]];
+            let t31 = [t(2)[0] - t(0)[0], t(2)[1] - t(0)[1]];
+
+            let area = t21[0] * t31[1] - t21[1] * t31[0];
+            let orientation = if area > 0.0 { 0 } else { 1 };
+            let mut os = sub(scale(d1, t31[1]), scale(d2, t21[1]));
+            if area != 0.0 {
+                os = scale(normalize(os), if area > 0.0 { 1.0 } else { -1.0 });
+            }
+
+            for (corner, &index) in triangle.iter().enumerate() {
+                let index = index as usize;
+                let n = normals[index].0;
+
+                let tangent = normalize(project(n, os));
+                let edge1 = normalize(project(n, sub(p((corner + 2) % 3), p(corner))));
+                let edge2 = normalize(project(n, sub(p((corner + 1) % 3), p(corner))));
+                let angle = dot(edge1, edge2).clamp(-1.0, 1.0).acos();
+
+                let (sum, weight) = &mut sums[group[index]][orientation];
+                *sum = add(*sum, scale(tangent, angle));
+                *weight += angle;
+                weights[index][orientation] += angle;
+            }
+        }
+
+        let tangents = (0..positions.len())
+            .map(|index| {
+                let sums = &sums[group[index]];
+                // Prefer orientation of triangles using this vertex,
+                // then orientation of triangles using same vertex elsewhere.
+                let orientation = if weights[index][1] > weights[index][0]
+                    || (weights[index][1] == weights[index][0] && sums[1].1 > sums[0].1)
+                {
+                    1
+                } else {
+                    0
+                };
+                let [x, y, z] = normalize(sums[orientation].0);
+                Tangent([x, y, z, if orientation == 0 { 1.0 } else { -1.0 }])
+            })
+            .collect::<Vec<_>>();
+
+        if !self.replace_attribute(&tangents) {
+            self.add_vertices(tangents);
+            self.sort_vertices();
+        }
+        Ok(self)
+    }
+
+    /// Get vertex indices of triangles.
+    fn triangles(&self) -> Result<Vec<u32>, failure::Error> {
+        if self.primitive() != gfx_hal::Primitive::TriangleList {
+            failure::bail!(
+                "Mesh must be a triangle list, but it is {:?}",
+                self.primitive()
+            );
+        }
+
+        let count = self.vertex_count();
+        let indices = self
+            .read_indices()
+            .unwrap_or_else(|| (0..count - count % 3).collect());
+        if indices.len() % 3 != 0 {
+            failure::bail!(
+                "Triangle list has {} indices which is not multiple of 3",
+                indices.len()
+            );
+        }
+        if let Some(index) = indices.iter().find(|&&index| index >= count) {
+            failure::bail!("Mesh has index {} but only {} vertices", index, count);
+        }
+        Ok(indices)
+    }
+}
+
+fn triangle_normal(positions: &[Position], triangle: &[u32]) -> [f32; 3] {
+    let p0 = positions[triangle[0] as usize].0;
+    let p1 = positions[triangle[1] as usize].0;
+    let p2 = positions[triangle[2] as usize].0;
+    cross(sub(p1, p0), sub(p2, p0))
+}
+
+/// Project vector onto plane with unit normal `n`.
+fn project(n: [f32; 3], v: [f32; 3]) -> [f32; 3] {
+    sub(v, scale(n, dot(n, v)))
+}
+
+fn add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale(a: [f32; 3], s: f32) -> [f32; 3] {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+/// Normalize vector. Zero vector is left as is.
+fn normalize(a: [f32; 3]) -> [f32; 3] {
+    let length = dot(a, a).sqrt();
+    if length > 0.0 {
+        scale(a, 1.0 / length)
+    } else {
+        a
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::Submesh;
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-5, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    fn quad() -> MeshBuilder<'static> {
+        MeshBuilder::new()
+            .with_vertices(vec![
+                Position([0.0, 0.0, 0.0]),
+                Position([1.0, 0.0, 0.0]),
+                Position([1.0, 1.0, 0.0]),
+                Position([0.0, 1.0, 0.0]),
+            ])
+            .with_vertices(vec![
+                TexCoord([0.0, 0.0]),
+                TexCoord([1.0, 0.0]),
+                TexCoord([1.0, 1.0]),
+                TexCoord([0.0, 1.0]),
+            ])
+            .with_indices(vec![0u16, 1, 2, 0, 2, 3])
+    }
+
+    /// Cube with corners at `±1` and each face split into 4 triangles around its center,
+    /// so that every corner is shared by the same area of each adjacent face.
+    fn cube() -> MeshBuilder<'static> {
+        let mut positions = Vec::new();
+        for &x in &[-1.0, 1.0] {
+            for &y in &[-1.0, 1.0] {
+                for &z in &[-1.0, 1.0] {
+                    positions.push(Position([x, y, z]));
+                }
+            }
+        }
+        let corner = |x: usize, y: usize, z: usize| (x * 4 + y * 2 + z) as u16;
+
+        let mut indices = Vec::new();
+        for axis in 0..3 {
+            for &side in &[0, 1] {
+                let mut center = [0.0; 3];
+                center[axis] = if side == 0 { -1.0 } else { 1.0 };
+                let center_index = positions.len() as u16;
+                positions.push(Position(center));
+
+                // Corners of the face in counter-clockwise order looking from outside.
+                let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+                let mut ring = [[0, 0], [1, 0], [1, 1], [0, 1]];
+                if side == 0 {
+                    ring.reverse();
+                }
+                let ring = ring
+                    .iter()
+                    .map(|&[a, b]| {
+                        let mut c = [0; 3];
+                        c[axis] = side;
+                        c[u] = a;
+                        c[v] = b;
+                        corner(c[0], c[1], c[2])
+                    })
+                    .collect::<Vec<_>>();
+                for i in 0..4 {
+                    indices.extend_from_slice(&[center_index, ring[i], ring[(i + 1) % 4]]);
+                }
+            }
+        }
+
+        MeshBuilder::new()
+            .with_vertices(positions)
+            .with_indices(indices)
+    }
+
+    #[test]
+    fn quad_normals() {
+        for &mode in &[NormalMode::Smooth, NormalMode::Flat] {
+            let mesh = quad().with_computed_normals(mode).unwrap();
+            let normals = mesh.attribute::<Normal>().unwrap();
+            let expected = if mode == NormalMode::Flat { 6 } else { 4 };
+            assert_eq!(normals.len(), expected);
+            for Normal(normal) in normals {
+                assert_close(&normal, &[0.0, 0.0, 1.0]);
+            }
+        }
+    }
+
+    #[test]
+    fn cube_smooth_normals() {
+        let mesh = cube().with_computed_normals(NormalMode::Smooth).unwrap();
+        let positions = mesh.attribute::<Position>().unwrap();
+        let normals = mesh.attribute::<Normal>().unwrap();
+        let d = 1.0 / 3.0f32.sqrt();
+        for (Position(position), Normal(normal)) in positions.into_iter().zip(normals) {
+            let sum = position.iter().map(|c| c.abs()).sum::<f32>();
+            let expected = if sum == 3.0 {
+                // Corner.
+                [position[0] * d, position[1] * d, position[2] * d]
+            } else {
+                // Face center.
+                position
+            };
+            assert_close(&normal, &expected);
+        }
+    }
+
+    #[test]
+    fn cube_flat_normals() {
+        let mesh = cube().with_computed_normals(NormalMode::Flat).unwrap();
+        assert!(mesh.read_indices().is_none());
+        let positions = mesh.attribute::<Position>().unwrap();
+        let normals = mesh.attribute::<Normal>().unwrap();
+        assert_eq!(normals.len(), 72);
+        for (triangle, normals) in positions.chunks(3).zip(normals.chunks(3)) {
+            // First vertex of each triangle is face center, which equals face normal.
+            for Normal(normal) in normals {
+                assert_close(normal, &triangle[0].0);
+            }
+        }
+    }
+
+    #[test]
+    fn quad_tangents() {
+        let mesh = quad()
+            .with_computed_normals(NormalMode::Smooth)
+            .unwrap()
+            .with_computed_tangents()
+            .unwrap();
+        for Tangent(tangent) in mesh.attribute::<Tangent>().unwrap() {
+            assert_close(&tangent, &[1.0, 0.0, 0.0, 1.0]);
+        }
+    }
+
+    #[test]
+    fn mirrored_tangents() {
+        // Two quads side by side, texture is mirrored on the right one.
+        let mesh = MeshBuilder::new()
+            .with_vertices(vec![
+                Position([-1.0, 0.0, 0.0]),
+                Position([0.0, 0.0, 0.0]),
+                Position([0.0, 1.0, 0.0]),
+                Position([-1.0, 1.0, 0.0]),
+                Position([0.0, 0.0, 0.0]),
+                Position([1.0, 0.0, 0.0]),
+                Position([1.0, 1.0, 0.0]),
+                Position([0.0, 1.0, 0.0]),
+            ])
+            .with_vertices(vec![
+                TexCoord([0.0, 0.0]),
+                TexCoord([1.0, 0.0]),
+                TexCoord([1.0, 1.0]),
+                TexCoord([0.0, 1.0]),
+                TexCoord([1.0, 0.0]),
+                TexCoord([0.0, 0.0]),
+                TexCoord([0.0, 1.0]),
+                TexCoord([1.0, 1.0]),
+            ])
+            .with_indices(vec![0u16, 1, 2, 0, 2, 3, 4, 5, 6, 4, 6, 7])
+            .with_computed_normals(NormalMode::Flat)
+            .unwrap()
+            .with_computed_tangents()
+            .unwrap();
+
+        // Triangles are unwelded in order, so first 6 vertices belong to the left quad.
+        let tangents = mesh.attribute::<Tangent>().unwrap();
+        assert_eq!(tangents.len(), 12);
+        for (index, Tangent(tangent)) in tangents.into_iter().enumerate() {
+            if index < 6 {
+                assert_close(&tangent, &[1.0, 0.0, 0.0, 1.0]);
+            } else {
+                assert_close(&tangent, &[-1.0, 0.0, 0.0, -1.0]);
+            }
+        }
+    }
+
+    #[test]
+    fn recompute_replaces_existing() {
+        let mut mesh = quad();
+        mesh.add_vertices(vec![Normal([1.0, 0.0, 0.0]); 4]);
+        mesh.compute_normals(NormalMode::Smooth).unwrap();
+        mesh.compute_tangents().unwrap();
+        mesh.compute_tangents().unwrap();
+
+        assert_eq!(mesh.raw_vertices().count(), 4);
+        for Normal(normal) in mesh.attribute::<Normal>().unwrap() {
+            assert_close(&normal, &[0.0, 0.0, 1.0]);
+        }
+    }
+
+    #[test]
+    fn stored_bounds_reset_with_positions() {
+        let mut mesh = quad().with_submesh(Submesh {
+            range: 0..3,
+            vertex_offset: 0,
+            material: 0,
+            bounds: None,
+        });
+        mesh.compute_bounds().unwrap();
+        mesh.compute_normals(NormalMode::Smooth).unwrap();
+        assert!(mesh.bounds().is_some());
+        assert!(mesh.submeshes().iter().all(|s| s.bounds.is_some()));
+
+        mesh.replace_attribute(&[Position([0.0, 0.0, 0.0]); 4]);
+        assert!(mesh.bounds().is_none());
+        assert!(mesh.submeshes().iter().all(|s| s.bounds.is_none()));
+    }
+
+    #[test]
+    fn rejects_incomplete_triangles() {
+        let mut mesh = quad().with_indices(vec![0u16, 1, 2, 3]);
+        assert!(mesh.compute_normals(NormalMode::Smooth).is_err());
+        mesh.set_prim_type(gfx_hal::Primitive::LineList);
+        assert!(mesh.compute_normals(NormalMode::Flat).is_err());
+    }
+}
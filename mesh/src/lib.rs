@@ -22,8 +22,18 @@ use rendy_memory as memory;
 use rendy_resource as resource;
 use rendy_util as util;
 
+mod bounds;
+mod dynamic;
 mod format;
+mod generate;
 mod mesh;
+mod optimize;
+mod pool;
 mod vertex;
 
-pub use crate::{format::*, mesh::*, vertex::*};
+pub use crate::{
+    bounds::*, dynamic::*, format::*, generate::*, mesh::*, optimize::*, pool::*, vertex::*,
+};
+
+#[cfg(feature = "derive")]
+pub use rendy_mesh_derive::AsVertex;
@@ -2,31 +2,64 @@
 //! Manage vertex and index buffers of single objects with ease.
 //!
 
-use std::{borrow::Cow, cmp::min, mem::size_of};
+use std::{borrow::Cow, cmp::min, mem::size_of, ops::Range};
+
+use gfx_hal::format::Format;
 
 use crate::{
-    command::{EncoderCommon, Graphics, QueueId, Supports},
+    bounds::Bounds,
+    command::{EncoderCommon, Graphics, QueueId, RenderPassEncoder, Supports},
     factory::{BufferState, Factory},
     memory::Data,
-    resource::{Buffer, BufferInfo, Escape},
+    resource::{Buffer, BufferInfo, Handle},
     util::cast_cow,
-    vertex::{AsVertex, VertexFormat},
+    vertex::{AsAttribute, AsVertex, Attribute, Position, Query, VertexFormat},
 };
 
-/// Vertex buffer with it's format
+/// Alignment of vertex streams and indices packed into mesh buffer.
+pub(crate) const STREAM_ALIGN: u64 = 16;
+
+/// Round `value` up to multiple of `STREAM_ALIGN`.
+pub(crate) fn align_stream(value: u64) -> u64 {
+    (value + STREAM_ALIGN - 1) & !(STREAM_ALIGN - 1)
+}
+
+/// Vertex stream with it's format and offset in mesh buffer.
 #[derive(Debug)]
-pub struct VertexBuffer<B: gfx_hal::Backend> {
-    buffer: Escape<Buffer<B>>,
+pub struct VertexBuffer {
+    offset: u64,
     format: VertexFormat<'static>,
+    names: Vec<Cow<'static, str>>,
 }
 
-/// Index buffer with it's type
-#[derive(Debug)]
-pub struct IndexBuffer<B: gfx_hal::Backend> {
-    buffer: Escape<Buffer<B>>,
+/// Indices with their type and offset in mesh buffer.
+#[derive(Clone, Copy, Debug)]
+pub struct IndexBuffer {
+    offset: u64,
     index_type: gfx_hal::IndexType,
 }
 
+/// Part of the mesh drawn with single draw call.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Submesh {
+    /// Range of indices to draw.
+    /// Range of vertices if mesh has no indices.
+    pub range: Range<u32>,
+
+    /// Value added to indices before fetching vertices.
+    /// Added to the vertex range if mesh has no indices.
+    pub vertex_offset: i32,
+
+    /// Material slot of the submesh.
+    pub material: usize,
+
+    /// Bounds of vertices referenced by the submesh.
+    /// Computed by `MeshBuilder::compute_bounds`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub bounds: Option<Bounds>,
+}
+
 /// Abstracts over two types of indices and their absence.
 #[derive(Debug)]
 pub enum Indices<'a> {
@@ -83,6 +116,10 @@ pub struct MeshBuilder<'a> {
     vertices: smallvec::SmallVec<[RawVertices<'a>; 16]>,
     indices: Option<RawIndices<'a>>,
     prim: gfx_hal::Primitive,
+    #[cfg_attr(feature = "serde", serde(default))]
+    submeshes: Vec<Submesh>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    bounds: Option<Bounds>,
 }
 
 #[derive(Clone, Debug)]
@@ -91,6 +128,8 @@ struct RawVertices<'a> {
     #[cfg_attr(feature = "serde", serde(with = "serde_bytes"))]
     vertices: Cow<'a, [u8]>,
     format: VertexFormat<'static>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    names: Vec<Cow<'static, str>>,
 }
 
 #[derive(Clone, Debug)]
@@ -108,6 +147,8 @@ impl<'a> MeshBuilder<'a> {
             vertices: smallvec::SmallVec::new(),
             indices: None,
             prim: gfx_hal::Primitive::TriangleList,
+            submeshes: Vec::new(),
+            bounds: None,
         }
     }
 
@@ -121,6 +162,7 @@ impl<'a> MeshBuilder<'a> {
                 .map(|v| RawVertices {
                     vertices: Cow::Owned(v.vertices.into_owned()),
                     format: v.format,
+                    names: v.names,
                 })
                 .collect(),
             indices: self.indices.map(|i| RawIndices {
@@ -128,6 +170,8 @@ impl<'a> MeshBuilder<'a> {
                 index_type: i.index_type,
             }),
             prim: self.prim,
+            submeshes: self.submeshes,
+            bounds: self.bounds,
         }
     }
 
@@ -175,13 +219,309 @@ impl<'a> MeshBuilder<'a> {
         V: AsVertex + 'a,
         D: Into<Cow<'a, [V]>>,
     {
+        let count = self.vertex_count();
         self.vertices.push(RawVertices {
             vertices: cast_cow(vertices.into()),
             format: V::VERTEX,
+            names: V::NAMES.iter().map(|&name| Cow::Borrowed(name)).collect(),
         });
+        if V::NAMES.contains(&Position::NAME) || self.vertex_count() != count {
+            self.invalidate_bounds();
+        }
+        self
+    }
+
+    /// Sort vertex buffers by their formats.
+    /// Relative order of buffers with equal formats is preserved.
+    pub fn sort_vertices(&mut self) -> &mut Self {
+        self.vertices.sort_by(|a, b| a.format.cmp(&b.format));
         self
     }
 
+    /// Get number of vertices.
+    /// It is the smallest number of vertices among all vertex buffers.
+    pub fn vertex_count(&self) -> u32 {
+        self.vertices
+            .iter()
+            .map(|raw| raw.vertices.len() as u32 / raw.format.stride)
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Read values of attribute `A` from the first vertex buffer that contains it.
+    /// Attributes are matched by name and format.
+    pub fn attribute<A>(&self) -> Option<Vec<A>>
+    where
+        A: AsAttribute,
+    {
+        assert_eq!(size_of::<A>(), A::SIZE as usize);
+        let count = self.vertex_count() as usize;
+        let (raw, attribute) = self.find_attribute(A::NAME, A::FORMAT)?;
+        let offset = attribute.offset as usize;
+        let stride = raw.format.stride as usize;
+        Some(
+            (0..count)
+                .map(|index| {
+                    let bytes = &raw.vertices[index * stride + offset..][..size_of::<A>()];
+                    // Attribute types are plain data of `A::SIZE` bytes.
+                    unsafe { std::ptr::read_unaligned(bytes.as_ptr() as *const A) }
+                })
+                .collect(),
+        )
+    }
+
+    fn find_attribute(&self, name: &str, format: Format) -> Option<(&RawVertices<'a>, Attribute)> {
+        self.vertices.iter().find_map(|raw| {
+            raw.names
+                .iter()
+                .zip(raw.format.attributes.iter())
+                .find(|(n, attribute)| *n == name && attribute.format == format)
+                .map(|(_, &attribute)| (raw, attribute))
+        })
+    }
+
+    /// Add vertex buffer with layout of vertex format `V`.
+    /// Attributes are copied from existing vertex buffers, matched by name and format.
+    pub fn with_layout<V>(mut self) -> Result<Self, failure::Error>
+    where
+        V: AsVertex,
+    {
+        self.add_layout::<V>()?;
+        Ok(self)
+    }
+
+    /// Add vertex buffer with layout of vertex format `V`.
+    /// Attributes are copied from existing vertex buffers, matched by name and format.
+    pub fn add_layout<V>(&mut self) -> Result<&mut Self, failure::Error>
+    where
+        V: AsVertex,
+    {
+        self.add_format(V::NAMES, V::VERTEX)
+    }
+
+    /// Add vertex buffer with stride of vertex format `V`
+    /// containing only attributes `Q` queried from it.
+    /// Bytes of other attributes of `V` are zeroed.
+    /// Attributes are copied from existing vertex buffers, matched by name and format.
+    pub fn with_queried<V, Q>(mut self) -> Result<Self, failure::Error>
+    where
+        V: Query<Q>,
+    {
+        self.add_queried::<V, Q>()?;
+        Ok(self)
+    }
+
+    /// Add vertex buffer with stride of vertex format `V`
+    /// containing only attributes `Q` queried from it.
+    /// Bytes of other attributes of `V` are zeroed.
+    /// Attributes are copied from existing vertex buffers, matched by name and format.
+    pub fn add_queried<V, Q>(&mut self) -> Result<&mut Self, failure::Error>
+    where
+        V: Query<Q>,
+    {
+        let mut queried = V::QUERIED_ATTRIBUTES.to_vec();
+        queried.sort_by_key(|&(_, attribute)| attribute.offset);
+        let names = queried.iter().map(|&(name, _)| name).collect::<Vec<_>>();
+        let format = VertexFormat {
+            attributes: queried.iter().map(|&(_, attribute)| attribute).collect(),
+            stride: V::VERTEX.stride,
+        };
+        self.add_format(&names, format)
+    }
+
+    /// Add vertex buffer with specified format and names of its attributes.
+    /// Attributes are copied from existing vertex buffers, matched by name and format.
+    pub fn add_format(
+        &mut self,
+        names: &[&'static str],
+        format: VertexFormat<'static>,
+    ) -> Result<&mut Self, failure::Error> {
+        if names.len() != format.attributes.len() {
+            failure::bail!(
+                "Vertex format has {} attributes but {} names provided",
+                format.attributes.len(),
+                names.len()
+            );
+        }
+
+        let count = self.vertex_count() as usize;
+        let stride = format.stride as usize;
+        let mut vertices = vec![0; count * stride];
+        for (&name, attribute) in names.iter().zip(format.attributes.iter()) {
+            let size = (attribute.format.surface_desc().bits / 8) as usize;
+            if attribute.offset as usize + size > stride {
+                failure::bail!(
+                    "Attribute '{}' at offset {} doesn't fit into vertex of size {}",
+                    name,
+                    attribute.offset,
+                    stride
+                );
+            }
+
+            let (raw, source) = self.find_attribute(name, attribute.format).ok_or_else(|| {
+                failure::format_err!(
+                    "Mesh has no attribute '{}' with format {:?}",
+                    name,
+                    attribute.format
+                )
+            })?;
+            let source_stride = raw.format.stride as usize;
+            for index in 0..count {
+                let src = index * source_stride + source.offset as usize;
+                let dst = index * stride + attribute.offset as usize;
+                vertices[dst..dst + size].copy_from_slice(&raw.vertices[src..src + size]);
+            }
+        }
+
+        self.vertices.push(RawVertices {
+            vertices: Cow::Owned(vertices),
+            format,
+            names: names.iter().map(|&name| Cow::Borrowed(name)).collect(),
+        });
+        self.sort_vertices();
+        Ok(self)
+    }
+
+    /// Overwrite attribute `A` in all vertex buffers that contain it,
+    /// matched by name and format.
+    /// Returns `false` if no vertex buffer contains the attribute.
+    pub(crate) fn replace_attribute<A>(&mut self, values: &[A]) -> bool
+    where
+        A: AsAttribute,
+    {
+        assert_eq!(size_of::<A>(), A::SIZE as usize);
+        let size = size_of::<A>();
+        let mut replaced = false;
+        for raw in &mut self.vertices {
+            let attribute = match raw
+                .names
+                .iter()
+                .zip(raw.format.attributes.iter())
+                .find(|(name, attribute)| *name == A::NAME && attribute.format == A::FORMAT)
+            {
+                Some((_, attribute)) => *attribute,
+                None => continue,
+            };
+            let stride = raw.format.stride as usize;
+            let vertices = raw.vertices.to_mut();
+            for (index, value) in values.iter().enumerate() {
+                let dst = index * stride + attribute.offset as usize;
+                // Attribute types are plain data of `A::SIZE` bytes.
+                let bytes =
+                    unsafe { std::slice::from_raw_parts(value as *const A as *const u8, size) };
+                vertices[dst..dst + size].copy_from_slice(bytes);
+            }
+            replaced = true;
+        }
+        if replaced && A::NAME == Position::NAME {
+            self.invalidate_bounds();
+        }
+        replaced
+    }
+
+    /// Read indices widened to `u32`.
+    pub(crate) fn read_indices(&self) -> Option<Vec<u32>> {
+        self.indices.as_ref().map(|raw| match raw.index_type {
+            gfx_hal::IndexType::U16 => raw
+                .indices
+                .chunks_exact(2)
+                .map(|bytes| u32::from(u16::from_ne_bytes([bytes[0], bytes[1]])))
+                .collect(),
+            gfx_hal::IndexType::U32 => raw
+                .indices
+                .chunks_exact(4)
+                .map(|bytes| u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                .collect(),
+        })
+    }
+
+    /// Expand vertex buffers so that each index refers to its own vertex and remove indices.
+    /// All indices must be less than `vertex_count`.
+    pub(crate) fn unweld(&mut self) {
+        let indices = match self.read_indices() {
+            Some(indices) => indices,
+            None => return,
+        };
+        self.remap_vertices(&indices);
+        self.indices = None;
+    }
+
+    /// Replace vertices in all vertex buffers with vertices at specified old indices.
+    /// All indices must be less than `vertex_count`.
+    /// Indices of the mesh are left intact.
+    pub(crate) fn remap_vertices(&mut self, old_indices: &[u32]) {
+        for raw in &mut self.vertices {
+            let stride = raw.format.stride as usize;
+            let mut vertices = Vec::with_capacity(old_indices.len() * stride);
+            for &index in old_indices {
+                vertices.extend_from_slice(&raw.vertices[index as usize * stride..][..stride]);
+            }
+            raw.vertices = Cow::Owned(vertices);
+        }
+    }
+
+    /// Get bytes of vertex from all vertex buffers.
+    pub(crate) fn vertex_bytes(&self, index: u32) -> impl Iterator<Item = &[u8]> {
+        self.vertices.iter().map(move |raw| {
+            let stride = raw.format.stride as usize;
+            &raw.vertices[index as usize * stride..][..stride]
+        })
+    }
+
+    /// Iterate over raw bytes of vertex buffers with their formats and attribute names.
+    pub(crate) fn raw_vertices(
+        &self,
+    ) -> impl Iterator<Item = (&[u8], &VertexFormat<'static>, &[Cow<'static, str>])> {
+        self.vertices
+            .iter()
+            .map(|raw| (&*raw.vertices, &raw.format, &*raw.names))
+    }
+
+    /// Add vertex buffer from raw bytes.
+    pub(crate) fn add_raw_vertices(
+        &mut self,
+        vertices: Cow<'a, [u8]>,
+        format: VertexFormat<'static>,
+        names: Vec<Cow<'static, str>>,
+    ) -> &mut Self {
+        self.vertices.push(RawVertices {
+            vertices,
+            format,
+            names,
+        });
+        self
+    }
+
+    /// Get raw bytes of indices with their type.
+    pub(crate) fn raw_indices(&self) -> Option<(&[u8], gfx_hal::IndexType)> {
+        self.indices
+            .as_ref()
+            .map(|raw| (&*raw.indices, raw.index_type))
+    }
+
+    /// Set indices from raw bytes.
+    pub(crate) fn set_raw_indices(
+        &mut self,
+        indices: Cow<'a, [u8]>,
+        index_type: gfx_hal::IndexType,
+    ) -> &mut Self {
+        self.indices = Some(RawIndices {
+            indices,
+            index_type,
+        });
+        self
+    }
+
+    /// Type of indices if mesh has them.
+    pub fn index_type(&self) -> Option<gfx_hal::IndexType> {
+        self.indices.as_ref().map(|raw| raw.index_type)
+    }
+
+    /// gfx_hal::Primitive type of the mesh.
+    pub fn primitive(&self) -> gfx_hal::Primitive {
+        self.prim
+    }
+
     /// Sets the primitive type of the mesh.
     ///
     /// By default, meshes are constructed as triangle lists.
@@ -198,89 +538,211 @@ impl<'a> MeshBuilder<'a> {
         self
     }
 
+    /// Add submesh to the mesh.
+    ///
+    /// If no submeshes are added, mesh gets single submesh
+    /// with all indices (or vertices) and material slot `0`.
+    pub fn with_submesh(mut self, submesh: Submesh) -> Self {
+        self.add_submesh(submesh);
+        self
+    }
+
+    /// Add submesh to the mesh.
+    ///
+    /// If no submeshes are added, mesh gets single submesh
+    /// with all indices (or vertices) and material slot `0`.
+    pub fn add_submesh(&mut self, submesh: Submesh) -> &mut Self {
+        self.submeshes.push(submesh);
+        self
+    }
+
+    /// Submeshes added to the mesh.
+    pub fn submeshes(&self) -> &[Submesh] {
+        &self.submeshes
+    }
+
+    pub(crate) fn submeshes_mut(&mut self) -> &mut [Submesh] {
+        &mut self.submeshes
+    }
+
+    /// Bounds of the mesh stored in the builder.
+    /// Stored bounds of the mesh and submeshes are reset when positions are
+    /// added or replaced, or when added vertices change the vertex count.
+    pub fn bounds(&self) -> Option<&Bounds> {
+        self.bounds.as_ref()
+    }
+
+    pub(crate) fn set_bounds(&mut self, bounds: Option<Bounds>) {
+        self.bounds = bounds;
+    }
+
+    /// Reset stored bounds of the mesh and submeshes.
+    fn invalidate_bounds(&mut self) {
+        self.bounds = None;
+        for submesh in &mut self.submeshes {
+            submesh.bounds = None;
+        }
+    }
+
+    /// Offsets of vertex buffers and indices packed into single buffer
+    /// and total size of packed data.
+    fn layout(&self) -> (smallvec::SmallVec<[u64; 16]>, Option<u64>, u64) {
+        let mut size = 0;
+        let mut push = |len: usize| {
+            let offset = size;
+            size = align_stream(offset + len as u64);
+            offset
+        };
+        let vertices = self
+            .vertices
+            .iter()
+            .map(|raw| push(raw.vertices.len()))
+            .collect();
+        let indices = self.indices.as_ref().map(|raw| push(raw.indices.len()));
+        (vertices, indices, size)
+    }
+
+    /// Size of buffer region required to build the mesh.
+    pub(crate) fn packed_size(&self) -> u64 {
+        self.layout().2
+    }
+
     /// Builds and returns the new mesh.
+    ///
+    /// All vertex buffers and indices are packed into single buffer.
     pub fn build<B>(&self, queue: QueueId, factory: &Factory<B>) -> Result<Mesh<B>, failure::Error>
     where
         B: gfx_hal::Backend,
     {
-        let mut len = u32::max_value();
+        let size = self.packed_size().max(STREAM_ALIGN);
+        let mut usage = gfx_hal::buffer::Usage::VERTEX | gfx_hal::buffer::Usage::TRANSFER_DST;
+        if self.indices.is_some() {
+            usage |= gfx_hal::buffer::Usage::INDEX;
+        }
+        let buffer = factory.create_buffer(BufferInfo { size, usage }, Data)?;
+        unsafe {
+            // New buffer can't be touched by device yet.
+            self.build_in(buffer.into(), 0..size, queue, factory)
+        }
+    }
+
+    /// Upload packed mesh data into `region` of `buffer` and create mesh referring to it.
+    ///
+    /// # Safety
+    ///
+    /// `region` must be at least `packed_size` bytes, aligned to `STREAM_ALIGN`
+    /// and must not be accessed by device or used by other meshes.
+    pub(crate) unsafe fn build_in<B>(
+        &self,
+        buffer: Handle<Buffer<B>>,
+        region: Range<u64>,
+        queue: QueueId,
+        factory: &Factory<B>,
+    ) -> Result<Mesh<B>, failure::Error>
+    where
+        B: gfx_hal::Backend,
+    {
+        debug_assert!(region.start % STREAM_ALIGN == 0);
+        let (data, streams) = self.pack(region.start)?;
+        debug_assert!(region.end - region.start >= data.len() as u64);
+
+        if !data.is_empty() {
+            factory.upload_buffer(
+                &buffer,
+                region.start,
+                &data,
+                None,
+                BufferState::new(queue)
+                    .with_access(
+                        gfx_hal::buffer::Access::VERTEX_BUFFER_READ
+                            | gfx_hal::buffer::Access::INDEX_BUFFER_READ,
+                    )
+                    .with_stage(gfx_hal::pso::PipelineStage::VERTEX_INPUT),
+            )?;
+        }
+
         Ok(Mesh {
-            vbufs: self
-                .vertices
-                .iter()
-                .map(|RawVertices { vertices, format }| {
-                    len = min(len, vertices.len() as u32 / format.stride);
-                    Ok(VertexBuffer {
-                        buffer: {
-                            let mut buffer = factory.create_buffer(
-                                BufferInfo {
-                                    size: vertices.len() as _,
-                                    usage: gfx_hal::buffer::Usage::VERTEX
-                                        | gfx_hal::buffer::Usage::TRANSFER_DST,
-                                },
-                                Data,
-                            )?;
-                            unsafe {
-                                // New buffer can't be touched by device yet.
-                                factory.upload_buffer(
-                                    &mut buffer,
-                                    0,
-                                    vertices,
-                                    None,
-                                    BufferState::new(queue)
-                                        .with_access(gfx_hal::buffer::Access::VERTEX_BUFFER_READ)
-                                        .with_stage(gfx_hal::pso::PipelineStage::VERTEX_INPUT),
-                                )?;
-                            }
-                            buffer
-                        },
-                        format: format.clone(),
-                    })
-                })
-                .collect::<Result<_, failure::Error>>()?,
-            ibuf: match self.indices {
-                None => None,
-                Some(RawIndices {
-                    ref indices,
-                    index_type,
-                }) => {
-                    let stride = match index_type {
-                        gfx_hal::IndexType::U16 => size_of::<u16>(),
-                        gfx_hal::IndexType::U32 => size_of::<u32>(),
-                    };
-                    len = indices.len() as u32 / stride as u32;
-                    Some(IndexBuffer {
-                        buffer: {
-                            let mut buffer = factory.create_buffer(
-                                BufferInfo {
-                                    size: indices.len() as _,
-                                    usage: gfx_hal::buffer::Usage::INDEX
-                                        | gfx_hal::buffer::Usage::TRANSFER_DST,
-                                },
-                                Data,
-                            )?;
-                            unsafe {
-                                // New buffer can't be touched by device yet.
-                                factory.upload_buffer(
-                                    &mut buffer,
-                                    0,
-                                    indices,
-                                    None,
-                                    BufferState::new(queue)
-                                        .with_access(gfx_hal::buffer::Access::INDEX_BUFFER_READ)
-                                        .with_stage(gfx_hal::pso::PipelineStage::VERTEX_INPUT),
-                                )?;
-                            }
-                            buffer
-                        },
-                        index_type,
-                    })
-                }
-            },
+            buffer,
+            region,
+            streams,
             prim: self.prim,
-            len,
         })
     }
+
+    /// Pack vertex buffers and indices into single chunk of data
+    /// that is placed at `base` offset of the buffer.
+    pub(crate) fn pack(&self, base: u64) -> Result<(Vec<u8>, Streams), failure::Error> {
+        let (vertex_offsets, index_offset, size) = self.layout();
+        let mut data = vec![0; size as usize];
+        let mut len = u32::MAX;
+
+        let vbufs = self
+            .vertices
+            .iter()
+            .zip(vertex_offsets)
+            .map(|(raw, offset)| {
+                len = min(len, raw.vertices.len() as u32 / raw.format.stride);
+                data[offset as usize..][..raw.vertices.len()].copy_from_slice(&raw.vertices);
+                VertexBuffer {
+                    offset: base + offset,
+                    format: raw.format.clone(),
+                    names: raw.names.clone(),
+                }
+            })
+            .collect();
+
+        let ibuf = self
+            .indices
+            .as_ref()
+            .zip(index_offset)
+            .map(|(raw, offset)| {
+                let stride = match raw.index_type {
+                    gfx_hal::IndexType::U16 => size_of::<u16>(),
+                    gfx_hal::IndexType::U32 => size_of::<u32>(),
+                };
+                len = raw.indices.len() as u32 / stride as u32;
+                data[offset as usize..][..raw.indices.len()].copy_from_slice(&raw.indices);
+                IndexBuffer {
+                    offset: base + offset,
+                    index_type: raw.index_type,
+                }
+            });
+
+        // Bounds are not computed here as it is too costly to do on every build.
+        let bounds = self.bounds;
+
+        let submeshes = if self.submeshes.is_empty() {
+            vec![Submesh {
+                range: 0..len,
+                vertex_offset: 0,
+                material: 0,
+                bounds,
+            }]
+        } else {
+            let out_of_range = |submesh: &&Submesh| {
+                submesh.range.start > submesh.range.end || submesh.range.end > len
+            };
+            if let Some(submesh) = self.submeshes.iter().find(out_of_range) {
+                failure::bail!(
+                    "Submesh range {:?} is out of mesh range {:?}",
+                    submesh.range,
+                    0..len
+                );
+            }
+            self.submeshes.clone()
+        };
+
+        Ok((
+            data,
+            Streams {
+                vbufs,
+                ibuf,
+                len,
+                submeshes,
+                bounds,
+            },
+        ))
+    }
 }
 
 impl<'a, V> From<Vec<V>> for MeshBuilder<'a>
@@ -292,14 +754,26 @@ where
     }
 }
 
-/// Single mesh is a collection of buffers that provides available attributes.
-/// Exactly one mesh is used per drawing call in common.
+/// Single mesh is a collection of vertex buffers that provides available attributes.
+/// Vertex buffers and indices are packed into single buffer
+/// which may be shared with other meshes allocated from `MeshPool`.
+/// Mesh is drawn with one drawing call per submesh.
 #[derive(Debug)]
 pub struct Mesh<B: gfx_hal::Backend> {
-    vbufs: Vec<VertexBuffer<B>>,
-    ibuf: Option<IndexBuffer<B>>,
+    buffer: Handle<Buffer<B>>,
+    region: Range<u64>,
+    streams: Streams,
     prim: gfx_hal::Primitive,
+}
+
+/// Vertex buffers, indices and submeshes placed in single buffer.
+#[derive(Debug)]
+pub(crate) struct Streams {
+    vbufs: Vec<VertexBuffer>,
+    ibuf: Option<IndexBuffer>,
     len: u32,
+    submeshes: Vec<Submesh>,
+    bounds: Option<Bounds>,
 }
 
 impl<B> Mesh<B>
@@ -318,29 +792,174 @@ where
 
     /// Get number of vertices in mesh.
     pub fn len(&self) -> u32 {
-        self.len
+        self.streams.len
+    }
+
+    /// Get submeshes of the mesh.
+    pub fn submeshes(&self) -> &[Submesh] {
+        &self.streams.submeshes
+    }
+
+    /// Get bounds of the mesh.
+    /// `None` unless bounds were stored in the builder with `MeshBuilder::compute_bounds`.
+    pub fn bounds(&self) -> Option<&Bounds> {
+        self.streams.bounds.as_ref()
+    }
+
+    /// Buffer with mesh data and region of it occupied by the mesh.
+    pub(crate) fn region(&self) -> (&Handle<Buffer<B>>, Range<u64>) {
+        (&self.buffer, self.region.clone())
+    }
+
+    /// Draw submesh with specified index.
+    /// Mesh must be bound with `bind` first.
+    pub fn draw_submesh(
+        &self,
+        index: usize,
+        instances: Range<u32>,
+        encoder: &mut RenderPassEncoder<'_, B>,
+    ) {
+        self.streams.draw_submesh(index, instances, encoder)
+    }
+
+    /// Draw all submeshes.
+    /// Mesh must be bound with `bind` first.
+    pub fn draw(&self, instances: Range<u32>, encoder: &mut RenderPassEncoder<'_, B>) {
+        self.streams.draw(instances, encoder)
+    }
+
+    /// Get vertex formats for graphics pipeline that reads specified attributes from this mesh.
+    ///
+    /// Attributes are `(name, format)` pairs, e.g. from `Query::QUERIED_ATTRIBUTES`.
+    /// One format is returned for each vertex buffer that provides requested attributes.
+    /// Formats include only requested attributes with offsets relative to the first of them
+    /// and stride of the buffer, so that unused attributes are skipped.
+    /// Mesh can be bound with returned formats using `bind`.
+    pub fn vertex_formats(
+        &self,
+        attributes: &[(&str, Format)],
+    ) -> Result<Vec<VertexFormat<'static>>, Incompatible> {
+        self.streams.vertex_formats(attributes)
     }
 
     /// Bind buffers to specified attribute locations.
-    pub fn bind<'a, C>(
-        &'a self,
+    ///
+    /// Vertex buffer is compatible with requested format if it has same stride
+    /// and contains all requested attributes at offsets shifted by the same amount.
+    /// Such buffer is bound with that amount as offset, skipping preceding attributes.
+    /// Each vertex buffer is bound at most once.
+    pub fn bind<C>(
+        &self,
         formats: &[VertexFormat<'_>],
         encoder: &mut EncoderCommon<'_, B, C>,
     ) -> Result<u32, Incompatible>
     where
         C: Supports<Graphics>,
     {
-        debug_assert!(is_slice_sorted(formats));
-        debug_assert!(is_slice_sorted_by_key(&self.vbufs, |vbuf| &vbuf.format));
+        self.streams.bind(&self.buffer, formats, encoder)
+    }
+}
+
+impl Streams {
+    pub(crate) fn len(&self) -> u32 {
+        self.len
+    }
+
+    pub(crate) fn submeshes(&self) -> &[Submesh] {
+        &self.submeshes
+    }
+
+    pub(crate) fn bounds(&self) -> Option<&Bounds> {
+        self.bounds.as_ref()
+    }
+
+    pub(crate) fn draw_submesh<B>(
+        &self,
+        index: usize,
+        instances: Range<u32>,
+        encoder: &mut RenderPassEncoder<'_, B>,
+    ) where
+        B: gfx_hal::Backend,
+    {
+        let submesh = &self.submeshes[index];
+        if self.ibuf.is_some() {
+            encoder.draw_indexed(submesh.range.clone(), submesh.vertex_offset, instances);
+        } else {
+            let offset =
+                |vertex: u32| (i64::from(vertex) + i64::from(submesh.vertex_offset)) as u32;
+            encoder.draw(
+                offset(submesh.range.start)..offset(submesh.range.end),
+                instances,
+            );
+        }
+    }
+
+    pub(crate) fn draw<B>(&self, instances: Range<u32>, encoder: &mut RenderPassEncoder<'_, B>)
+    where
+        B: gfx_hal::Backend,
+    {
+        for index in 0..self.submeshes.len() {
+            self.draw_submesh(index, instances.clone(), encoder);
+        }
+    }
 
+    pub(crate) fn vertex_formats(
+        &self,
+        attributes: &[(&str, Format)],
+    ) -> Result<Vec<VertexFormat<'static>>, Incompatible> {
+        let mut buffers = vec![Vec::new(); self.vbufs.len()];
+        for &(name, format) in attributes {
+            let (index, attribute) = self
+                .vbufs
+                .iter()
+                .enumerate()
+                .find_map(|(index, vbuf)| {
+                    vbuf.names
+                        .iter()
+                        .zip(vbuf.format.attributes.iter())
+                        .find(|(n, attribute)| *n == name && attribute.format == format)
+                        .map(|(_, &attribute)| (index, attribute))
+                })
+                .ok_or(Incompatible)?;
+            buffers[index].push(attribute);
+        }
+
+        Ok(buffers
+            .into_iter()
+            .zip(&self.vbufs)
+            .filter(|(attributes, _)| !attributes.is_empty())
+            .map(|(mut attributes, vbuf)| {
+                attributes.sort_by_key(|attribute| attribute.offset);
+                let base = attributes[0].offset;
+                for attribute in &mut attributes {
+                    attribute.offset -= base;
+                }
+                VertexFormat {
+                    attributes: attributes.into(),
+                    stride: vbuf.format.stride,
+                }
+            })
+            .collect())
+    }
+
+    pub(crate) fn bind<B, C>(
+        &self,
+        buffer: &Buffer<B>,
+        formats: &[VertexFormat<'_>],
+        encoder: &mut EncoderCommon<'_, B, C>,
+    ) -> Result<u32, Incompatible>
+    where
+        B: gfx_hal::Backend,
+        C: Supports<Graphics>,
+    {
         let mut vertex = smallvec::SmallVec::<[_; 16]>::new();
+        let mut used = smallvec::SmallVec::<[_; 16]>::from_elem(false, self.vbufs.len());
 
-        let mut next = 0;
         for format in formats {
-            if let Some(index) = find_compatible_buffer(&self.vbufs[next..], format) {
+            if let Some((index, offset)) = find_compatible_buffer(&self.vbufs, &used, format) {
                 // Ensure buffer is valid
-                vertex.push((self.vbufs[index].buffer.raw(), 0));
-                next = index + 1;
+                vertex.push((buffer.raw(), self.vbufs[index].offset + u64::from(offset)));
+                used[index] = true;
             } else {
                 // Can't bind
                 return Err(Incompatible);
@@ -348,7 +967,7 @@ where
         }
         match self.ibuf.as_ref() {
             Some(ibuf) => {
-                encoder.bind_index_buffer(ibuf.buffer.raw(), 0, ibuf.index_type);
+                encoder.bind_index_buffer(buffer.raw(), ibuf.offset, ibuf.index_type);
                 encoder.bind_vertex_buffers(0, vertex.iter().cloned());
             }
             None => {
@@ -364,44 +983,51 @@ where
 #[derive(Clone, Copy, Debug)]
 pub struct Incompatible;
 
-/// Helper function to find buffer with compatible format.
-fn find_compatible_buffer<B>(vbufs: &[VertexBuffer<B>], format: &VertexFormat<'_>) -> Option<usize>
-where
-    B: gfx_hal::Backend,
-{
-    debug_assert!(is_slice_sorted_by_key(&*format.attributes, |a| a.offset));
-    for (i, vbuf) in vbufs.iter().enumerate() {
-        debug_assert!(is_slice_sorted_by_key(&*vbuf.format.attributes, |a| a.offset));
-        if is_compatible(&vbuf.format, format) {
-            return Some(i);
-        }
-    }
-    None
+/// Helper function to find unused buffer with compatible format.
+/// Returns index of the buffer and offset to bind it with.
+fn find_compatible_buffer(
+    vbufs: &[VertexBuffer],
+    used: &[bool],
+    format: &VertexFormat<'_>,
+) -> Option<(usize, u32)> {
+    debug_assert!(is_slice_sorted_by_key(&format.attributes, |a| a.offset));
+    vbufs
+        .iter()
+        .zip(used)
+        .enumerate()
+        .filter(|(_, (_, &used))| !used)
+        .find_map(|(i, (vbuf, _))| {
+            debug_assert!(is_slice_sorted_by_key(&vbuf.format.attributes, |a| a.offset));
+            compatible_offset(&vbuf.format, format).map(|offset| (i, offset))
+        })
 }
 
-/// Check is vertex format `left` is compatible with `right`.
-/// `left` must have same `stride` and contain all attributes from `right`.
-fn is_compatible(left: &VertexFormat<'_>, right: &VertexFormat<'_>) -> bool {
+/// Check if vertex format `left` is compatible with `right`
+/// and find offset of `right` attributes within `left` vertex.
+/// `left` must have same `stride` and contain all attributes from `right`
+/// with offsets shifted by the same amount.
+fn compatible_offset(left: &VertexFormat<'_>, right: &VertexFormat<'_>) -> Option<u32> {
     if left.stride != right.stride {
-        return false;
+        return None;
     }
 
-    // Don't start searching from index 0 because attributes are sorted
-    let mut skip = 0;
-    right.attributes.iter().all(|r| {
-        left.attributes[skip..]
-            .iter()
-            .position(|l| *l == *r)
-            .map_or(false, |p| {
-                skip += p;
-                true
-            })
-    })
-}
+    let first = match right.attributes.first() {
+        Some(first) => first,
+        None => return Some(0),
+    };
 
-/// Chech if slice o f ordered values is sorted.
-fn is_slice_sorted<T: Ord>(slice: &[T]) -> bool {
-    is_slice_sorted_by_key(slice, |i| i)
+    left.attributes
+        .iter()
+        .filter(|l| l.format == first.format && l.offset >= first.offset)
+        .map(|l| l.offset - first.offset)
+        .filter(|&shift| {
+            right.attributes.iter().all(|r| {
+                left.attributes
+                    .iter()
+                    .any(|l| l.format == r.format && l.offset == r.offset + shift)
+            })
+        })
+        .min()
 }
 
 /// Check if slice is sorted using ordered key and key extractor
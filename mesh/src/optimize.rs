@@ -0,0 +1,514 @@
+//!
+//! Optimize meshes for faster rendering.
+//!
+
+use {
+    crate::mesh::{Indices, MeshBuilder},
+    std::{
+        borrow::Cow,
+        cmp::Ordering,
+        collections::{BinaryHeap, HashMap},
+        ops::Range,
+    },
+};
+
+/// Optimization passes to run with `MeshBuilder::optimize`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OptimizeConfig {
+    /// Merge vertices that are equal in all vertex buffers.
+    /// Meshes without indices get them.
+    pub deduplicate: bool,
+
+    /// Reorder triangles to reuse vertices from post-transform cache,
+    /// using Tom Forsyth's linear-speed vertex cache optimization.
+    /// Only triangle lists are reordered.
+    pub vertex_cache: bool,
+
+    /// Reorder vertices in order they are fetched by indices,
+    /// and remove vertices that are not referenced.
+    pub vertex_fetch: bool,
+
+    /// Use `u16` indices when number of vertices allows.
+    pub narrow_indices: bool,
+
+    /// Size of simulated post-transform cache.
+    pub cache_size: u32,
+}
+
+impl Default for OptimizeConfig {
+    fn default() -> Self {
+        OptimizeConfig {
+            deduplicate: true,
+            vertex_cache: true,
+            vertex_fetch: true,
+            narrow_indices: true,
+            cache_size: 32,
+        }
+    }
+}
+
+/// Statistics collected by `MeshBuilder::optimize`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OptimizeStats {
+    /// Number of vertices before optimization.
+    pub vertices_before: u32,
+
+    /// Number of vertices after optimization.
+    pub vertices_after: u32,
+
+    /// Average cache miss ratio before optimization.
+    /// `None` if mesh is not a triangle list or has no triangles.
+    pub acmr_before: Option<f32>,
+
+    /// Average cache miss ratio after optimization.
+    /// `None` if mesh is not a triangle list or has no triangles.
+    pub acmr_after: Option<f32>,
+}
+
+impl<'a> MeshBuilder<'a> {
+    /// Run optimization passes enabled in `config`.
+    ///
+    /// Index ranges of submeshes are preserved,
+    /// triangles are reordered only within their submesh.
+    /// Submeshes must not overlap and must have zero vertex offsets.
+    pub fn optimize(&mut self, config: &OptimizeConfig) -> Result<OptimizeStats, failure::Error> {
+        let count = self.vertex_count();
+        let mut stats = OptimizeStats {
+            vertices_before: count,
+            vertices_after: count,
+            acmr_before: self.acmr(config.cache_size),
+            acmr_after: None,
+        };
+
+        let mut indices = match self.read_indices() {
+            Some(indices) => indices,
+            None if config.deduplicate => (0..count).collect(),
+            None => {
+                stats.acmr_after = stats.acmr_before;
+                return Ok(stats);
+            }
+        };
+        if let Some(index) = indices.iter().find(|&&index| index >= count) {
+            failure::bail!("Mesh has index {} but only {} vertices", index, count);
+        }
+
+        let ranges = self.submesh_ranges(indices.len() as u32)?;
+
+        if config.deduplicate {
+            let mut unique = HashMap::new();
+            let mut old_indices = Vec::new();
+            let remap = (0..count)
+                .map(|index| {
+                    let bytes = self.vertex_bytes(index).collect::<Vec<_>>().concat();
+                    let next = unique.len() as u32;
+                    *unique.entry(bytes).or_insert_with(|| {
+                        old_indices.push(index);
+                        next
+                    })
+                })
+                .collect::<Vec<_>>();
+            for index in &mut indices {
+                *index = remap[*index as usize];
+            }
+            self.remap_vertices(&old_indices);
+        }
+
+        if config.vertex_cache && self.primitive() == gfx_hal::Primitive::TriangleList {
+            for range in ranges {
+                let range = range.start as usize..range.end as usize;
+                let triangles = range.len() - range.len() % 3;
+                optimize_vertex_cache(
+                    &mut indices[range.start..range.start + triangles],
+                    config.cache_size,
+                );
+            }
+        }
+
+        if config.vertex_fetch {
+            let mut remap = vec![u32::MAX; self.vertex_count() as usize];
+            let mut old_indices = Vec::new();
+            for index in &mut indices {
+                let new = &mut remap[*index as usize];
+                if *new == u32::MAX {
+                    *new = old_indices.len() as u32;
+                    old_indices.push(*index);
+                }
+                *index = *new;
+            }
+            self.remap_vertices(&old_indices);
+        }
+
+        let count = self.vertex_count();
+        let narrow = count <= u32::from(u16::MAX) + 1
+            && (config.narrow_indices || self.index_type() == Some(gfx_hal::IndexType::U16));
+        if narrow {
+            let indices = indices.into_iter().map(|index| index as u16).collect();
+            self.set_indices(Indices::U16(Cow::Owned(indices)));
+        } else {
+            self.set_indices(Indices::U32(Cow::Owned(indices)));
+        }
+
+        stats.vertices_after = count;
+        stats.acmr_after = self.acmr(config.cache_size);
+        Ok(stats)
+    }
+
+    /// Compute average cache miss ratio, i.e. number of vertices transformed per triangle,
+    /// simulating FIFO post-transform cache of specified size.
+    /// Returns `None` if mesh is not a triangle list or has no triangles.
+    pub fn acmr(&self, cache_size: u32) -> Option<f32> {
+        if self.primitive() != gfx_hal::Primitive::TriangleList {
+            return None;
+        }
+
+        let indices = self
+            .read_indices()
+            .unwrap_or_else(|| (0..self.vertex_count()).collect());
+        let triangles = indices.len() / 3;
+        if triangles == 0 {
+            return None;
+        }
+
+        let mut cache = std::collections::VecDeque::with_capacity(cache_size as usize);
+        let mut misses = 0;
+        for &index in &indices[..triangles * 3] {
+            if !cache.contains(&index) {
+                misses += 1;
+                if cache.len() == cache_size as usize {
+                    cache.pop_front();
+                }
+                cache.push_back(index);
+            }
+        }
+        Some(misses as f32 / triangles as f32)
+    }
+
+    /// Get index ranges of submeshes sorted by start.
+    /// Whole index range if there are no submeshes.
+    fn submesh_ranges(&self, len: u32) -> Result<Vec<Range<u32>>, failure::Error> {
+        if self.submeshes().is_empty() {
+            return Ok(std::iter::once(0..len).collect());
+        }
+
+        if self
+            .submeshes()
+            .iter()
+            .any(|submesh| submesh.vertex_offset != 0)
+        {
+            failure::bail!("Optimization of submeshes with vertex offset is not supported");
+        }
+
+        let mut ranges = self
+            .submeshes()
+            .iter()
+            .map(|submesh| submesh.range.clone())
+            .collect::<Vec<_>>();
+        ranges.sort_by_key(|range| range.start);
+        if let Some(range) = ranges.iter().find(|range| range.end > len) {
+            failure::bail!(
+                "Submesh range {:?} is out of mesh range {:?}",
+                range,
+                0..len
+            );
+        }
+        if let Some(pair) = ranges.windows(2).find(|pair| pair[0].end > pair[1].start) {
+            failure::bail!("Submesh ranges {:?} and {:?} overlap", pair[0], pair[1]);
+        }
+        Ok(ranges)
+    }
+}
+
+const LAST_TRIANGLE_SCORE: f32 = 0.75;
+const CACHE_DECAY_POWER: f32 = 1.5;
+const VALENCE_BOOST_SCALE: f32 = 2.0;
+const VALENCE_BOOST_POWER: f32 = 0.5;
+
+/// Score of vertex with specified position in LRU cache
+/// and number of triangles left to emit that use the vertex.
+fn vertex_score(position: Option<usize>, valence: u32, cache_size: usize) -> f32 {
+    if valence == 0 {
+        return -1.0;
+    }
+
+    let cache_score = match position {
+        None => 0.0,
+        Some(position) if position < 3 => LAST_TRIANGLE_SCORE,
+        Some(position) => {
+            let scale = 1.0 / (cache_size - 3) as f32;
+            (1.0 - (position - 3) as f32 * scale).powf(CACHE_DECAY_POWER)
+        }
+    };
+    cache_score + VALENCE_BOOST_SCALE * (valence as f32).powf(-VALENCE_BOOST_POWER)
+}
+
+/// Reorder triangles using Tom Forsyth's algorithm.
+/// Triangles are emitted greedily by score of their vertices,
+/// which favors vertices in simulated LRU cache and vertices with few triangles left.
+fn optimize_vertex_cache(indices: &mut [u32], cache_size: u32) {
+    let cache_size = (cache_size as usize).max(4);
+
+    // Use local vertex numbers to keep memory proportional to range size.
+    let mut local = HashMap::new();
+    let triangles = indices
+        .chunks_exact(3)
+        .map(|triangle| {
+            let mut vertices = [0; 3];
+            for (vertex, &index) in vertices.iter_mut().zip(triangle) {
+                let next = local.len();
+                *vertex = *local.entry(index).or_insert(next);
+            }
+            vertices
+        })
+        .collect::<Vec<[usize; 3]>>();
+    let vertex_count = local.len();
+
+    // Triangles using each vertex.
+    let mut valence = vec![0u32; vertex_count];
+    for triangle in &triangles {
+        for &vertex in triangle {
+            valence[vertex] += 1;
+        }
+    }
+    let mut offsets = Vec::with_capacity(vertex_count + 1);
+    offsets.push(0);
+    for &count in &valence {
+        offsets.push(offsets.last().unwrap() + count as usize);
+    }
+    let mut adjacency = vec![0; offsets[vertex_count]];
+    let mut filled = offsets.clone();
+    for (index, triangle) in triangles.iter().enumerate() {
+        for &vertex in triangle {
+            adjacency[filled[vertex]] = index;
+            filled[vertex] += 1;
+        }
+    }
+
+    let mut score = (0..vertex_count)
+        .map(|vertex| vertex_score(None, valence[vertex], cache_size))
+        .collect::<Vec<_>>();
+    let mut triangle_score = triangles
+        .iter()
+        .map(|triangle| triangle.iter().map(|&vertex| score[vertex]).sum::<f32>())
+        .collect::<Vec<_>>();
+    let mut emitted = vec![false; triangles.len()];
+
+    // Queue of triangles by score to pick from when cache has no candidates.
+    // Entries are pushed whenever score changes, outdated entries are skipped when popped.
+    let mut queue = triangle_score
+        .iter()
+        .enumerate()
+        .map(|(index, &score)| ScoredTriangle { score, index })
+        .collect::<BinaryHeap<_>>();
+
+    let mut order = Vec::with_capacity(triangles.len());
+    let mut cache = Vec::<usize>::with_capacity(cache_size + 3);
+    let mut best = None;
+
+    while order.len() < triangles.len() {
+        let next = match best {
+            Some(next) => next,
+            None => loop {
+                // No candidates in cache. Pick best of not emitted triangles.
+                let entry = queue.pop().unwrap();
+                if !emitted[entry.index] && entry.score == triangle_score[entry.index] {
+                    break entry.index;
+                }
+            },
+        };
+
+        emitted[next] = true;
+        order.push(next);
+
+        for &vertex in &triangles[next] {
+            // Remove triangle from vertex adjacency.
+            let start = offsets[vertex];
+            let end = start + valence[vertex] as usize;
+            let slot = (start..end).find(|&slot| adjacency[slot] == next).unwrap();
+            adjacency.swap(slot, end - 1);
+            valence[vertex] -= 1;
+
+            // Move vertex to the front of the cache.
+            if let Some(index) = cache.iter().position(|&cached| cached == vertex) {
+                cache.remove(index);
+            }
+            cache.insert(0, vertex);
+        }
+
+        // Vertices pushed out of the cache lose their cache score.
+        for &vertex in cache.iter().skip(cache_size) {
+            score[vertex] = vertex_score(None, valence[vertex], cache_size);
+            let start = offsets[vertex];
+            for &triangle in &adjacency[start..start + valence[vertex] as usize] {
+                triangle_score[triangle] = triangles[triangle].iter().map(|&v| score[v]).sum();
+                queue.push(ScoredTriangle {
+                    score: triangle_score[triangle],
+                    index: triangle,
+                });
+            }
+        }
+        cache.truncate(cache_size);
+
+        for (index, &vertex) in cache.iter().enumerate() {
+            score[vertex] = vertex_score(Some(index), valence[vertex], cache_size);
+        }
+
+        best = None;
+        let mut best_score = -1.0;
+        for &vertex in &cache {
+            let start = offsets[vertex];
+            for &triangle in &adjacency[start..start + valence[vertex] as usize] {
+                let sum = triangles[triangle].iter().map(|&v| score[v]).sum::<f32>();
+                if sum != triangle_score[triangle] {
+                    triangle_score[triangle] = sum;
+                    queue.push(ScoredTriangle {
+                        score: sum,
+                        index: triangle,
+                    });
+                }
+                if sum > best_score {
+                    best_score = sum;
+                    best = Some(triangle);
+                }
+            }
+        }
+    }
+
+    let reordered = order
+        .iter()
+        .flat_map(|&triangle| indices[triangle * 3..triangle * 3 + 3].to_vec())
+        .collect::<Vec<_>>();
+    indices.copy_from_slice(&reordered);
+}
+
+/// Triangle with its score, ordered by score and then by lowest index.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ScoredTriangle {
+    score: f32,
+    index: usize,
+}
+
+impl Eq for ScoredTriangle {}
+
+impl PartialOrd for ScoredTriangle {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ScoredTriangle {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Scores are always finite.
+        self.score
+            .partial_cmp(&other.score)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vertex::Position;
+
+    /// Grid of `size` x `size` quads with triangles in row order.
+    fn grid(size: u32) -> (Vec<Position>, Vec<u32>) {
+        let positions = (0..=size)
+            .flat_map(|y| (0..=size).map(move |x| Position([x as f32, y as f32, 0.0])))
+            .collect();
+        let vertex = |x: u32, y: u32| y * (size + 1) + x;
+        let indices = (0..size)
+            .flat_map(|y| (0..size).map(move |x| (x, y)))
+            .flat_map(|(x, y)| {
+                vec![
+                    vertex(x, y),
+                    vertex(x + 1, y),
+                    vertex(x + 1, y + 1),
+                    vertex(x, y),
+                    vertex(x + 1, y + 1),
+                    vertex(x, y + 1),
+                ]
+            })
+            .collect();
+        (positions, indices)
+    }
+
+    /// Triangles rotated to start from the smallest index, preserving winding.
+    fn sorted_triangles(indices: &[u32]) -> Vec<[u32; 3]> {
+        let mut triangles = indices
+            .chunks_exact(3)
+            .map(|t| {
+                let first = (0..3).min_by_key(|&i| t[i]).unwrap();
+                [t[first], t[(first + 1) % 3], t[(first + 2) % 3]]
+            })
+            .collect::<Vec<_>>();
+        triangles.sort();
+        triangles
+    }
+
+    #[test]
+    fn vertex_cache_reduces_acmr() {
+        let (positions, indices) = grid(64);
+        let mut mesh = MeshBuilder::new()
+            .with_vertices(positions)
+            .with_indices(indices);
+        let stats = mesh
+            .optimize(&OptimizeConfig {
+                deduplicate: false,
+                vertex_fetch: false,
+                ..OptimizeConfig::default()
+            })
+            .unwrap();
+
+        let before = stats.acmr_before.unwrap();
+        let after = stats.acmr_after.unwrap();
+        assert!(before > 0.95, "ACMR before: {}", before);
+        assert!(after < 0.8, "ACMR after: {}", after);
+        assert_eq!(Some(after), mesh.acmr(32));
+    }
+
+    #[test]
+    fn vertex_cache_preserves_triangles() {
+        let (_, mut indices) = grid(16);
+        // Add disconnected triangles to exercise picking triangles outside of cache.
+        indices.extend((0..300).map(|index| 1000 + index));
+        let original = indices.clone();
+
+        optimize_vertex_cache(&mut indices, 16);
+        assert_ne!(indices, original);
+        assert_eq!(sorted_triangles(&indices), sorted_triangles(&original));
+    }
+
+    #[test]
+    fn optimize_preserves_geometry() {
+        let (positions, indices) = grid(8);
+        // Triangles as positions, rotated to start from the smallest one to preserve winding.
+        let triangles = |mesh: &MeshBuilder<'_>| {
+            let positions = mesh.attribute::<Position>().unwrap();
+            let mut triangles = mesh
+                .read_indices()
+                .unwrap()
+                .chunks_exact(3)
+                .map(|t| {
+                    let p = |i: usize| positions[t[i] as usize].0;
+                    let first = (0..3)
+                        .min_by(|&a, &b| p(a).partial_cmp(&p(b)).unwrap())
+                        .unwrap();
+                    [p(first), p((first + 1) % 3), p((first + 2) % 3)]
+                })
+                .collect::<Vec<_>>();
+            triangles.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            triangles
+        };
+
+        let mut mesh = MeshBuilder::new()
+            .with_vertices(positions)
+            .with_indices(indices);
+        let before = triangles(&mesh);
+        let stats = mesh.optimize(&OptimizeConfig::default()).unwrap();
+        assert_eq!(stats.vertices_after, 81);
+        assert_eq!(mesh.index_type(), Some(gfx_hal::IndexType::U16));
+        assert_eq!(triangles(&mesh), before);
+    }
+}
@@ -0,0 +1,244 @@
+//!
+//! Sub-allocate many meshes from few large buffers.
+//!
+
+use std::ops::Range;
+
+use crate::{
+    command::QueueId,
+    factory::Factory,
+    memory::Data,
+    mesh::{align_stream, Mesh, MeshBuilder, STREAM_ALIGN},
+    resource::{Buffer, BufferInfo, Handle},
+};
+
+/// Pool of large buffers that meshes are sub-allocated from.
+///
+/// Meshes built by the pool share buffers, which reduces number of allocations
+/// and lets consecutive meshes be bound without switching buffers.
+/// Meshes that don't fit into chunk of the pool get dedicated buffer.
+///
+/// Space of the mesh is reclaimed only by `MeshPool::free`.
+/// Dropping a mesh built by the pool leaks its space until the pool is dropped.
+#[derive(Debug)]
+pub struct MeshPool<B: gfx_hal::Backend> {
+    chunk_size: u64,
+    chunks: Vec<Chunk<B>>,
+}
+
+#[derive(Debug)]
+struct Chunk<B: gfx_hal::Backend> {
+    buffer: Handle<Buffer<B>>,
+    size: u64,
+    free: FreeRanges,
+}
+
+/// Sorted list of non-adjacent free ranges of a chunk.
+#[derive(Debug)]
+struct FreeRanges {
+    ranges: Vec<Range<u64>>,
+}
+
+impl FreeRanges {
+    /// Free ranges of chunk of `size` bytes with first `used` bytes allocated.
+    fn new(size: u64, used: u64) -> Self {
+        // Chunk allocated for a mesh larger than chunk size has no free space left.
+        // `ranges` is a list of ranges that happens to have single element here.
+        #[allow(clippy::single_range_in_vec_init)]
+        let ranges = if used < size {
+            vec![used..size]
+        } else {
+            Vec::new()
+        };
+        FreeRanges { ranges }
+    }
+
+    /// Find first free range large enough and allocate from its start.
+    fn allocate(&mut self, size: u64) -> Option<Range<u64>> {
+        let index = self
+            .ranges
+            .iter()
+            .position(|range| range.end - range.start >= size)?;
+        let start = self.ranges[index].start;
+        self.ranges[index].start += size;
+        if self.ranges[index].start == self.ranges[index].end {
+            self.ranges.remove(index);
+        }
+        Some(start..start + size)
+    }
+
+    /// Return range back, merging it with adjacent free ranges.
+    fn release(&mut self, range: Range<u64>) {
+        let index = self
+            .ranges
+            .iter()
+            .position(|free| free.start > range.start)
+            .unwrap_or(self.ranges.len());
+        let merge_prev = index > 0 && self.ranges[index - 1].end == range.start;
+        let merge_next = index < self.ranges.len() && self.ranges[index].start == range.end;
+        match (merge_prev, merge_next) {
+            (true, true) => {
+                self.ranges[index - 1].end = self.ranges[index].end;
+                self.ranges.remove(index);
+            }
+            (true, false) => self.ranges[index - 1].end = range.end,
+            (false, true) => self.ranges[index].start = range.start,
+            (false, false) => self.ranges.insert(index, range),
+        }
+    }
+
+    /// Check if whole chunk of `size` bytes is free.
+    fn is_unused(&self, size: u64) -> bool {
+        self.ranges.len() == 1 && self.ranges[0] == (0..size)
+    }
+}
+
+impl<B> MeshPool<B>
+where
+    B: gfx_hal::Backend,
+{
+    /// Create empty pool that allocates buffers of `chunk_size` bytes.
+    pub fn new(chunk_size: u64) -> Self {
+        MeshPool {
+            chunk_size: align_stream(chunk_size.max(STREAM_ALIGN)),
+            chunks: Vec::new(),
+        }
+    }
+
+    /// Size of buffers allocated by the pool.
+    pub fn chunk_size(&self) -> u64 {
+        self.chunk_size
+    }
+
+    /// Number of buffers allocated by the pool.
+    pub fn chunks(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Build mesh in buffer of this pool.
+    pub fn build(
+        &mut self,
+        builder: &MeshBuilder<'_>,
+        queue: QueueId,
+        factory: &Factory<B>,
+    ) -> Result<Mesh<B>, failure::Error> {
+        let size = builder.packed_size().max(STREAM_ALIGN);
+
+        let found = self
+            .chunks
+            .iter_mut()
+            .enumerate()
+            .find_map(|(index, chunk)| chunk.free.allocate(size).map(|region| (index, region)));
+
+        let (index, region) = match found {
+            Some(found) => found,
+            None => {
+                let chunk_size = self.chunk_size.max(size);
+                let buffer = factory.create_buffer(
+                    BufferInfo {
+                        size: chunk_size,
+                        usage: gfx_hal::buffer::Usage::VERTEX
+                            | gfx_hal::buffer::Usage::INDEX
+                            | gfx_hal::buffer::Usage::TRANSFER_DST,
+                    },
+                    Data,
+                )?;
+                self.chunks.push(Chunk {
+                    buffer: buffer.into(),
+                    size: chunk_size,
+                    free: FreeRanges::new(chunk_size, size),
+                });
+                (self.chunks.len() - 1, 0..size)
+            }
+        };
+
+        let chunk = &mut self.chunks[index];
+        // Region is allocated for this mesh only,
+        // and freed regions are not accessed by device anymore.
+        let result =
+            unsafe { builder.build_in(chunk.buffer.clone(), region.clone(), queue, factory) };
+        if result.is_err() {
+            chunk.free.release(region);
+        }
+        result
+    }
+
+    /// Free mesh built by this pool, making its space available for new meshes.
+    /// Dedicated buffers of meshes larger than chunk size are released.
+    ///
+    /// # Safety
+    ///
+    /// Mesh must not be used by device anymore,
+    /// e.g. all command buffers that draw it must be complete.
+    ///
+    /// # Panics
+    ///
+    /// Panics if mesh wasn't built by this pool.
+    pub unsafe fn free(&mut self, mesh: Mesh<B>) {
+        let (buffer, region) = mesh.region();
+        let index = self
+            .chunks
+            .iter()
+            .position(|chunk| std::ptr::eq::<Buffer<B>>(&*chunk.buffer, &**buffer))
+            .expect("Mesh wasn't built by this pool");
+
+        let chunk = &mut self.chunks[index];
+        chunk.free.release(region);
+        if chunk.size > self.chunk_size && chunk.free.is_unused(chunk.size) {
+            self.chunks.swap_remove(index);
+        }
+    }
+}
+
+#[cfg(test)]
+// Expected free lists often consist of single range.
+#[allow(clippy::single_range_in_vec_init)]
+mod tests {
+    use super::FreeRanges;
+
+    #[test]
+    fn allocates_first_fit() {
+        let mut free = FreeRanges::new(1024, 256);
+        assert_eq!(free.allocate(256), Some(256..512));
+        assert_eq!(free.allocate(1024), None);
+        assert_eq!(free.ranges, [512..1024]);
+    }
+
+    #[test]
+    fn removes_exactly_filled_range() {
+        let mut free = FreeRanges::new(1024, 512);
+        assert_eq!(free.allocate(512), Some(512..1024));
+        assert!(free.ranges.is_empty());
+        assert_eq!(free.allocate(1), None);
+
+        assert!(FreeRanges::new(1024, 1024).ranges.is_empty());
+    }
+
+    #[test]
+    fn merges_with_neighbours() {
+        let mut free = FreeRanges::new(1024, 0);
+        let a = free.allocate(256).unwrap();
+        let b = free.allocate(256).unwrap();
+        let c = free.allocate(256).unwrap();
+        assert_eq!(free.ranges, [768..1024]);
+
+        free.release(a);
+        assert_eq!(free.ranges, [0..256, 768..1024]);
+
+        // Merge with previous.
+        free.release(b);
+        assert_eq!(free.ranges, [0..512, 768..1024]);
+
+        // Merge with both.
+        free.release(c);
+        assert!(free.is_unused(1024));
+
+        // Merge with next.
+        let a = free.allocate(256).unwrap();
+        let b = free.allocate(256).unwrap();
+        free.release(b);
+        assert_eq!(free.ranges, [256..1024]);
+        free.release(a);
+        assert!(free.is_unused(1024));
+    }
+}
@@ -115,6 +115,48 @@ impl AsAttribute for TexCoord {
     const FORMAT: gfx_hal::format::Format = gfx_hal::format::Format::Rg32Float;
 }
 
+/// Type for joint indices attribute of vertex.
+/// Indices refer to joints of the skin the mesh is bound to.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Joints(pub [u16; 4]);
+impl<T> From<T> for Joints
+where
+    T: Into<[u16; 4]>,
+{
+    fn from(from: T) -> Self {
+        Joints(from.into())
+    }
+}
+
+impl AsAttribute for Joints {
+    const NAME: &'static str = "joints";
+    const SIZE: u32 = 8;
+    const FORMAT: gfx_hal::format::Format = gfx_hal::format::Format::Rgba16Uint;
+}
+
+/// Type for joint weights attribute of vertex.
+/// Weights correspond to joints from `Joints` attribute.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Weights(pub [f32; 4]);
+impl<T> From<T> for Weights
+where
+    T: Into<[f32; 4]>,
+{
+    fn from(from: T) -> Self {
+        Weights(from.into())
+    }
+}
+
+impl AsAttribute for Weights {
+    const NAME: &'static str = "weights";
+    const SIZE: u32 = 16;
+    const FORMAT: gfx_hal::format::Format = gfx_hal::format::Format::Rgba32Float;
+}
+
 /// Vertex format contains information to initialize graphics pipeline
 /// Attributes must be sorted by offset.
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -146,6 +188,14 @@ pub trait AsVertex: Debug + PartialEq + PartialOrd + Copy + Sized + Send + Sync
     /// List of all attributes formats with name and offset.
     const VERTEX: VertexFormat<'static>;
 
+    /// Names of attributes in the same order as in `VERTEX`.
+    /// Names are used to match attributes between vertex formats,
+    /// see `MeshBuilder::attribute` and `MeshBuilder::add_layout`.
+    /// For attributes implementing `AsAttribute` it should be `AsAttribute::NAME`.
+    /// Defaults to no names, in which case attributes of the format are never matched
+    /// and `MeshBuilder::add_layout` fails for it.
+    const NAMES: &'static [&'static str] = &[];
+
     /// Returns attribute of vertex by type
     #[inline]
     fn attribute<F>() -> Attribute
@@ -168,6 +218,8 @@ where
         }]),
         stride: T::SIZE,
     };
+
+    const NAMES: &'static [&'static str] = &[T::NAME];
 }
 
 /// Trait implemented by all valid vertex formats for each field
@@ -205,6 +257,8 @@ impl AsVertex for PosColor {
         ]),
         stride: Position::SIZE + Color::SIZE,
     };
+
+    const NAMES: &'static [&'static str] = &[Position::NAME, Color::NAME];
 }
 
 impl WithAttribute<Position> for PosColor {
@@ -240,6 +294,8 @@ impl AsVertex for PosNorm {
         ]),
         stride: Position::SIZE + Normal::SIZE,
     };
+
+    const NAMES: &'static [&'static str] = &[Position::NAME, Normal::NAME];
 }
 
 impl WithAttribute<Position> for PosNorm {
@@ -278,6 +334,8 @@ impl AsVertex for PosColorNorm {
         ]),
         stride: Position::SIZE + Color::SIZE + Normal::SIZE,
     };
+
+    const NAMES: &'static [&'static str] = &[Position::NAME, Color::NAME, Normal::NAME];
 }
 
 impl WithAttribute<Position> for PosColorNorm {
@@ -320,6 +378,8 @@ impl AsVertex for PosTex {
         ]),
         stride: Position::SIZE + TexCoord::SIZE,
     };
+
+    const NAMES: &'static [&'static str] = &[Position::NAME, TexCoord::NAME];
 }
 
 impl WithAttribute<Position> for PosTex {
@@ -358,6 +418,8 @@ impl AsVertex for PosNormTex {
         ]),
         stride: Position::SIZE + Normal::SIZE + TexCoord::SIZE,
     };
+
+    const NAMES: &'static [&'static str] = &[Position::NAME, Normal::NAME, TexCoord::NAME];
 }
 
 impl WithAttribute<Position> for PosNormTex {
@@ -406,6 +468,9 @@ impl AsVertex for PosNormTangTex {
         ]),
         stride: Position::SIZE + Normal::SIZE + Tangent::SIZE + TexCoord::SIZE,
     };
+
+    const NAMES: &'static [&'static str] =
+        &[Position::NAME, Normal::NAME, Tangent::NAME, TexCoord::NAME];
 }
 
 impl WithAttribute<Position> for PosNormTangTex {
@@ -475,6 +540,9 @@ impl AsVertex for Transform {
         ]),
         stride: 64,
     };
+
+    const NAMES: &'static [&'static str] =
+        &["transform_0", "transform_1", "transform_2", "transform_3"];
 }
 
 /// Allows to query specific `Attribute`s of `AsVertex`
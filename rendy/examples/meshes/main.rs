@@ -384,10 +384,14 @@ fn main() {
         )),
     );
 
-    let depth = graph_builder.create_image(
+    let depth = graph_builder.create_image_with_formats(
         surface.kind(),
         1,
-        gfx_hal::format::Format::D16Unorm,
+        vec![
+            gfx_hal::format::Format::D16Unorm,
+            gfx_hal::format::Format::X8D24Unorm,
+            gfx_hal::format::Format::D32Float,
+        ],
         Some(gfx_hal::command::ClearValue::DepthStencil(
             gfx_hal::command::ClearDepthStencil(1.0, 0),
         )),
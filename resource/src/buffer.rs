@@ -9,6 +9,10 @@ use {
     },
     gfx_hal::{Backend, Device as _},
     relevant::Relevant,
+    std::{
+        mem::{align_of, size_of},
+        ops::Range,
+    },
 };
 
 /// Buffer info.
@@ -129,7 +133,7 @@ where
     pub fn map<'a>(
         &'a mut self,
         device: &Device<B>,
-        range: std::ops::Range<u64>,
+        range: Range<u64>,
     ) -> Result<MappedRange<'a, B>, gfx_hal::mapping::Error> {
         self.block.map(device, range)
     }
@@ -138,4 +142,158 @@ where
     pub fn size(&self) -> u64 {
         self.info().size
     }
+
+    /// Get typed writer for `len` elements of the buffer starting from `offset` in bytes.
+    ///
+    /// Whole buffer gets mapped and the mapping is kept alive until buffer is destroyed,
+    /// so subsequent writes won't remap memory.
+    /// `non_coherent_atom_size` must be taken from device limits.
+    ///
+    /// # Safety
+    ///
+    /// Caller must ensure that device doesn't use memory region that being updated.
+    pub unsafe fn writer<'a, T>(
+        &'a mut self,
+        device: &'a Device<B>,
+        offset: u64,
+        len: usize,
+        non_coherent_atom_size: u64,
+    ) -> Result<BufferWriter<'a, B, T>, gfx_hal::mapping::Error>
+    where
+        T: Copy,
+    {
+        let size = len as u64 * size_of::<T>() as u64;
+        assert!(size > 0, "Writer must cover non-empty range");
+        assert!(
+            offset + size <= self.size(),
+            "Writer range {:?} is out of buffer bounds {}",
+            offset..offset + size,
+            self.size()
+        );
+
+        let buffer_size = self.size();
+        let mapping = self.block.map(device, 0..buffer_size)?;
+        let ptr = mapping.ptr().as_ptr().add(offset as usize);
+        assert_eq!(
+            ptr as usize % align_of::<T>(),
+            0,
+            "Writer offset must be multiple of element alignment"
+        );
+
+        Ok(BufferWriter {
+            slice: std::slice::from_raw_parts_mut(ptr as *mut T, len),
+            mapping,
+            device,
+            offset,
+            non_coherent_atom_size,
+            written: None,
+        })
+    }
+}
+
+/// Typed writer into mapped buffer range.
+/// Provides slice-like access to the buffer content.
+///
+/// Written ranges are flushed on drop or explicit [`flush`] call
+/// if buffer is bound to non-coherent memory.
+/// Errors of flush on drop can't be handled and are only logged,
+/// so call [`flush`] before dropping the writer to check for them.
+///
+/// [`flush`]: #method.flush
+#[derive(derivative::Derivative)]
+#[derivative(Debug)]
+pub struct BufferWriter<'a, B: Backend, T> {
+    #[derivative(Debug = "ignore")]
+    slice: &'a mut [T],
+    mapping: MappedRange<'a, B>,
+    #[derivative(Debug = "ignore")]
+    device: &'a Device<B>,
+    offset: u64,
+    non_coherent_atom_size: u64,
+    written: Option<Range<usize>>,
+}
+
+impl<'a, B, T> BufferWriter<'a, B, T>
+where
+    B: Backend,
+    T: Copy,
+{
+    /// Write `data` starting from element at `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index + data.len()` is greater than writer length.
+    pub fn write(&mut self, index: usize, data: &[T]) {
+        self.slice_mut(index..index + data.len())
+            .copy_from_slice(data);
+    }
+
+    /// Set element at `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn set(&mut self, index: usize, value: T) {
+        self.slice_mut(index..index + 1)[0] = value;
+    }
+}
+
+impl<'a, B, T> BufferWriter<'a, B, T>
+where
+    B: Backend,
+{
+    /// Get number of elements accessible through this writer.
+    pub fn len(&self) -> usize {
+        self.slice.len()
+    }
+
+    /// Check if writer has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.slice.is_empty()
+    }
+
+    /// Get mutable slice of the elements in `range`.
+    /// Whole range is considered written.
+    ///
+    /// Note that reading from returned slice may be slow
+    /// as mapped memory is usually not cached by the host.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is out of bounds.
+    pub fn slice_mut(&mut self, range: Range<usize>) -> &mut [T] {
+        assert!(range.end <= self.slice.len());
+        if range.start < range.end {
+            self.written = Some(match self.written.take() {
+                Some(written) => written.start.min(range.start)..written.end.max(range.end),
+                None => range.clone(),
+            });
+        }
+        &mut self.slice[range]
+    }
+
+    /// Flush written elements.
+    /// Makes them available to device operations that will be submitted after this call.
+    /// No-op if memory is coherent or nothing was written since last flush.
+    pub fn flush(&mut self) -> Result<(), gfx_hal::device::OutOfMemory> {
+        if let Some(written) = self.written.take() {
+            let element = size_of::<T>() as u64;
+            let start = self.offset + written.start as u64 * element;
+            let end = self.offset + written.end as u64 * element;
+            self.mapping
+                .flush(self.device, start..end, self.non_coherent_atom_size)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a, B, T> Drop for BufferWriter<'a, B, T>
+where
+    B: Backend,
+{
+    fn drop(&mut self) {
+        if let Err(err) = self.flush() {
+            log::error!("Failed to flush buffer writer on drop: {}", err);
+        }
+    }
 }
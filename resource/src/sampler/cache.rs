@@ -1,20 +1,45 @@
 //! A cache to store and retrieve samplers
 
 use {
-    super::Sampler,
+    super::{Sampler, SamplerError},
     crate::escape::Handle,
     gfx_hal::{image::SamplerInfo, Backend},
     std::{
         collections::hash_map::{Entry, HashMap},
         ops::{Deref, DerefMut},
+        sync::atomic::{AtomicU64, Ordering},
     },
 };
 
+/// Number of cached samplers after which cache starts to warn about sampler count.
+const WARN_THRESHOLD: usize = 256;
+
+/// Statistics of the sampler cache.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SamplerCacheStats {
+    /// Number of samplers in the cache.
+    pub samplers: usize,
+
+    /// Number of cached samplers which have the same filters and wrap modes
+    /// as some other cached sampler, differing only in LOD, anisotropy, comparison or border.
+    /// Large number here usually means that sampler parameters are computed
+    /// where fixed configuration would suffice.
+    pub similar: usize,
+
+    /// Number of requests served from the cache.
+    pub hits: u64,
+
+    /// Number of requests that created new sampler.
+    pub misses: u64,
+}
+
 /// Sampler cache holds handlers to created samplers.
 #[derive(Debug, derivative::Derivative)]
 #[derivative(Default(bound = ""))]
 pub struct SamplerCache<B: Backend> {
     samplers: HashMap<SamplerInfo, Handle<Sampler<B>>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
 }
 
 impl<B> SamplerCache<B>
@@ -26,15 +51,20 @@ where
     pub fn get(
         &mut self,
         info: SamplerInfo,
-        create: impl FnOnce() -> Result<Handle<Sampler<B>>, gfx_hal::device::AllocationError>,
-    ) -> Result<Handle<Sampler<B>>, gfx_hal::device::AllocationError> {
-        Ok(match self.samplers.entry(info) {
-            Entry::Occupied(occupied) => occupied.get().clone(),
+        create: impl FnOnce() -> Result<Handle<Sampler<B>>, SamplerError>,
+    ) -> Result<Handle<Sampler<B>>, SamplerError> {
+        match self.samplers.entry(info) {
+            Entry::Occupied(occupied) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Ok(occupied.get().clone())
+            }
             Entry::Vacant(vacant) => {
                 let sampler = create()?;
-                vacant.insert(sampler).clone()
+                vacant.insert(sampler.clone());
+                self.inserted();
+                Ok(sampler)
             }
-        })
+        }
     }
 
     /// Get sampler with specified paramters.
@@ -44,20 +74,56 @@ where
         read: R,
         upgrade: U,
         info: SamplerInfo,
-        create: impl FnOnce() -> Result<Handle<Sampler<B>>, gfx_hal::device::AllocationError>,
-    ) -> Result<Handle<Sampler<B>>, gfx_hal::device::AllocationError>
+        create: impl FnOnce() -> Result<Handle<Sampler<B>>, SamplerError>,
+    ) -> Result<Handle<Sampler<B>>, SamplerError>
     where
         R: Deref<Target = Self>,
         W: DerefMut<Target = Self>,
         U: FnOnce(R) -> W,
     {
         if let Some(sampler) = read.samplers.get(&info) {
+            read.hits.fetch_add(1, Ordering::Relaxed);
             return Ok(sampler.clone());
         }
         let sampler = create()?;
         {
-            upgrade(read).samplers.insert(info, sampler.clone());
+            let mut write = upgrade(read);
+            write.samplers.insert(info, sampler.clone());
+            write.inserted();
         }
         Ok(sampler)
     }
+
+    /// Get statistics of the cache.
+    pub fn stats(&self) -> SamplerCacheStats {
+        let mut groups = HashMap::new();
+        for info in self.samplers.keys() {
+            *groups
+                .entry((
+                    info.min_filter,
+                    info.mag_filter,
+                    info.mip_filter,
+                    info.wrap_mode,
+                ))
+                .or_insert(0) += 1;
+        }
+
+        SamplerCacheStats {
+            samplers: self.samplers.len(),
+            similar: groups.values().filter(|&&count| count > 1).sum(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    fn inserted(&mut self) {
+        *self.misses.get_mut() += 1;
+        let count = self.samplers.len();
+        if count >= WARN_THRESHOLD && count.is_power_of_two() {
+            log::warn!(
+                "Sampler cache holds {} samplers. Consider using fewer sampler configurations",
+                count
+            );
+        }
+    }
 }
@@ -1,14 +1,101 @@
 //! Sampler creation-info and wrappers.
 
 mod cache;
+mod preset;
 
 use {
     crate::util::{device_owned, Device, DeviceId},
-    gfx_hal::{image::SamplerInfo, Backend, Device as _},
+    gfx_hal::{
+        image::{Anisotropic, SamplerInfo},
+        Backend, Device as _,
+    },
     relevant::Relevant,
 };
 
-pub use crate::sampler::cache::SamplerCache;
+pub use crate::sampler::{
+    cache::{SamplerCache, SamplerCacheStats},
+    preset::SamplerPreset,
+};
+
+/// Device capabilities relevant for sampler creation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SamplerLimits {
+    /// Anisotropic filtering is supported.
+    pub anisotropy: bool,
+
+    /// Maximum degree of anisotropy.
+    pub max_anisotropy: f32,
+
+    /// Depth comparison samplers are supported.
+    pub comparison: bool,
+}
+
+/// Possible errors returned by sampler creation.
+#[allow(missing_copy_implementations)]
+#[derive(Debug, failure::Fail)]
+pub enum SamplerError {
+    /// Sampler allocation failure.
+    #[fail(display = "{}", _0)]
+    AllocationError(gfx_hal::device::AllocationError),
+
+    /// Anisotropic filtering requested but not supported by device.
+    #[fail(display = "Anisotropic filtering is not supported")]
+    AnisotropyNotSupported,
+
+    /// Requested degree of anisotropy is out of supported range.
+    #[fail(display = "Anisotropy {} is out of supported range 1..={}", _0, _1)]
+    InvalidAnisotropy(u8, f32),
+
+    /// LOD range is not valid.
+    #[fail(display = "Invalid LOD range {}..{}", _0, _1)]
+    InvalidLodRange(f32, f32),
+
+    /// Depth comparison requested but not supported by device.
+    #[fail(display = "Depth comparison samplers are not supported")]
+    ComparisonNotSupported,
+}
+
+impl From<gfx_hal::device::AllocationError> for SamplerError {
+    fn from(error: gfx_hal::device::AllocationError) -> Self {
+        SamplerError::AllocationError(error)
+    }
+}
+
+impl From<gfx_hal::device::OutOfMemory> for SamplerError {
+    fn from(error: gfx_hal::device::OutOfMemory) -> Self {
+        SamplerError::AllocationError(error.into())
+    }
+}
+
+/// Check that sampler can be created with `info` on device with `limits`.
+pub fn validate_sampler_info(
+    info: &SamplerInfo,
+    limits: &SamplerLimits,
+) -> Result<(), SamplerError> {
+    if let Anisotropic::On(anisotropy) = info.anisotropic {
+        if !limits.anisotropy {
+            return Err(SamplerError::AnisotropyNotSupported);
+        }
+        if anisotropy < 1 || f32::from(anisotropy) > limits.max_anisotropy {
+            return Err(SamplerError::InvalidAnisotropy(
+                anisotropy,
+                limits.max_anisotropy,
+            ));
+        }
+    }
+
+    let min_lod: f32 = info.lod_range.start.into();
+    let max_lod: f32 = info.lod_range.end.into();
+    if min_lod > max_lod {
+        return Err(SamplerError::InvalidLodRange(min_lod, max_lod));
+    }
+
+    if info.comparison.is_some() && !limits.comparison {
+        return Err(SamplerError::ComparisonNotSupported);
+    }
+
+    Ok(())
+}
 
 /// Generic sampler resource wrapper.
 #[derive(Debug)]
@@ -26,11 +113,13 @@ where
     B: Backend,
 {
     /// Create new sampler.
+    /// `info` is not validated, see [`create_validated`].
+    ///
+    /// [`create_validated`]: #method.create_validated
     pub fn create(
         device: &Device<B>,
         info: SamplerInfo,
     ) -> Result<Self, gfx_hal::device::AllocationError> {
-        // TODO: Check info is valid.
         let raw = unsafe { device.create_sampler(info.clone()) }?;
         Ok(Sampler {
             device: device.id(),
@@ -40,6 +129,17 @@ where
         })
     }
 
+    /// Create new sampler.
+    /// `info` is validated against `limits` first.
+    pub fn create_validated(
+        device: &Device<B>,
+        info: SamplerInfo,
+        limits: &SamplerLimits,
+    ) -> Result<Self, SamplerError> {
+        validate_sampler_info(&info, limits)?;
+        Ok(Self::create(device, info)?)
+    }
+
     /// Destroy sampler resource.
     pub unsafe fn dispose(self, device: &Device<B>) {
         self.assert_device_owner(device);
@@ -0,0 +1,55 @@
+//! Commonly used sampler configurations
+
+use gfx_hal::{
+    image::{Anisotropic, Filter, SamplerInfo, WrapMode},
+    pso::Comparison,
+};
+
+/// Named sampler configuration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SamplerPreset {
+    /// Linear filtering with repeating wrap mode.
+    LinearRepeat,
+
+    /// Linear filtering with clamping wrap mode.
+    LinearClamp,
+
+    /// Nearest filtering with repeating wrap mode.
+    NearestRepeat,
+
+    /// Nearest filtering with clamping wrap mode.
+    NearestClamp,
+
+    /// Linear filtering with clamping wrap mode and `LessEqual` depth comparison.
+    /// Suitable for sampling shadow maps.
+    ShadowCompare,
+
+    /// Linear filtering with repeating wrap mode and anisotropic filtering of specified degree.
+    Anisotropic(u8),
+}
+
+impl SamplerPreset {
+    /// Get sampler info for the preset.
+    pub fn info(&self) -> SamplerInfo {
+        match *self {
+            SamplerPreset::LinearRepeat => SamplerInfo::new(Filter::Linear, WrapMode::Tile),
+            SamplerPreset::LinearClamp => SamplerInfo::new(Filter::Linear, WrapMode::Clamp),
+            SamplerPreset::NearestRepeat => SamplerInfo::new(Filter::Nearest, WrapMode::Tile),
+            SamplerPreset::NearestClamp => SamplerInfo::new(Filter::Nearest, WrapMode::Clamp),
+            SamplerPreset::ShadowCompare => SamplerInfo {
+                comparison: Some(Comparison::LessEqual),
+                ..SamplerInfo::new(Filter::Linear, WrapMode::Clamp)
+            },
+            SamplerPreset::Anisotropic(anisotropy) => SamplerInfo {
+                anisotropic: Anisotropic::On(anisotropy),
+                ..SamplerInfo::new(Filter::Linear, WrapMode::Tile)
+            },
+        }
+    }
+}
+
+impl From<SamplerPreset> for SamplerInfo {
+    fn from(preset: SamplerPreset) -> Self {
+        preset.info()
+    }
+}
@@ -0,0 +1,627 @@
+//! Module for packing many images into a single array `Texture`
+use {
+    crate::{
+        convert::Converter,
+        factory::{Factory, ImageState, ImageStateOrLayout},
+        pixel::AsPixel,
+        texture::{Texture, TextureBuilder},
+        util::cast_slice,
+    },
+    gfx_hal::{
+        format::{Format, Swizzle},
+        image, Backend,
+    },
+};
+
+/// Placement of a single image in the atlas.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AtlasEntry {
+    /// Layer of the atlas texture the image is placed to.
+    pub layer: image::Layer,
+    /// Horizontal offset of the image in texels.
+    pub x: u32,
+    /// Vertical offset of the image in texels.
+    pub y: u32,
+    /// Width of the image in texels.
+    pub width: u32,
+    /// Height of the image in texels.
+    pub height: u32,
+    /// Texture coordinates of the top-left corner of the image.
+    pub uv_min: [f32; 2],
+    /// Texture coordinates of the bottom-right corner of the image.
+    pub uv_max: [f32; 2],
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Segment {
+    x: u32,
+    y: u32,
+    width: u32,
+}
+
+/// Skyline bottom-left rectangle packer for a single layer.
+#[derive(Clone, Debug)]
+struct Skyline {
+    width: u32,
+    height: u32,
+    segments: Vec<Segment>,
+}
+
+impl Skyline {
+    fn new(width: u32, height: u32) -> Self {
+        Skyline {
+            width,
+            height,
+            segments: vec![Segment { x: 0, y: 0, width }],
+        }
+    }
+
+    /// Find lowest position of rectangle starting at segment `index`.
+    fn fit(&self, index: usize, width: u32, height: u32) -> Option<u32> {
+        let x = self.segments[index].x;
+        if x + width > self.width {
+            return None;
+        }
+
+        let mut y = 0;
+        let mut covered = 0;
+        for segment in &self.segments[index..] {
+            if covered >= width {
+                break;
+            }
+            y = y.max(segment.y);
+            if y + height > self.height {
+                return None;
+            }
+            covered += segment.width;
+        }
+        Some(y)
+    }
+
+    /// Allocate rectangle, returning its offset.
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let (index, y) = (0..self.segments.len())
+            .filter_map(|index| self.fit(index, width, height).map(|y| (index, y)))
+            .min_by_key(|&(index, y)| (y + height, self.segments[index].x))?;
+
+        let x = self.segments[index].x;
+        self.segments.insert(
+            index,
+            Segment {
+                x,
+                y: y + height,
+                width,
+            },
+        );
+
+        // Cut segments covered by the new one.
+        let end = x + width;
+        let next = index + 1;
+        while next < self.segments.len() {
+            let segment = &mut self.segments[next];
+            if segment.x >= end {
+                break;
+            }
+            let overlap = end - segment.x;
+            if overlap >= segment.width {
+                self.segments.remove(next);
+            } else {
+                segment.x += overlap;
+                segment.width -= overlap;
+                break;
+            }
+        }
+
+        // Merge neighbouring segments of the same height.
+        let mut i = 1;
+        while i < self.segments.len() {
+            if self.segments[i - 1].y == self.segments[i].y {
+                self.segments[i - 1].width += self.segments[i].width;
+                self.segments.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+
+        Some((x, y))
+    }
+}
+
+/// Shared state of atlas builder and built atlas.
+#[derive(Clone, Debug)]
+struct Packer {
+    width: u32,
+    height: u32,
+    format: Format,
+    padding: u32,
+    layers: Vec<Skyline>,
+    entries: Vec<AtlasEntry>,
+}
+
+impl Packer {
+    /// Allocate space for the image in existing layers.
+    /// Entry is not recorded, see `Packer::push`.
+    fn allocate(&mut self, width: u32, height: u32) -> Result<Option<AtlasEntry>, failure::Error> {
+        if width > self.width || height > self.height {
+            failure::bail!(
+                "Image {}x{} doesn't fit into atlas layer {}x{}",
+                width,
+                height,
+                self.width,
+                self.height
+            );
+        }
+
+        // Padding is not required on the edges of the layer.
+        let padded_width = (width + self.padding).min(self.width);
+        let padded_height = (height + self.padding).min(self.height);
+
+        for (layer, skyline) in self.layers.iter_mut().enumerate() {
+            if let Some((x, y)) = skyline.allocate(padded_width, padded_height) {
+                let entry = AtlasEntry {
+                    layer: layer as image::Layer,
+                    x,
+                    y,
+                    width,
+                    height,
+                    uv_min: [x as f32 / self.width as f32, y as f32 / self.height as f32],
+                    uv_max: [
+                        (x + width) as f32 / self.width as f32,
+                        (y + height) as f32 / self.height as f32,
+                    ],
+                };
+                return Ok(Some(entry));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Record allocated entry.
+    fn push(&mut self, entry: AtlasEntry) {
+        self.entries.push(entry);
+    }
+
+    /// Convert pixel data into tightly packed rows of atlas format.
+    /// `swizzle` is baked into the pixels as atlas is sampled without it.
+    fn prepare(
+        &self,
+        width: u32,
+        height: u32,
+        data_width: u32,
+        data: &[u8],
+        format: Format,
+        swizzle: Swizzle,
+    ) -> Result<Vec<u8>, failure::Error> {
+        if width == 0 || height == 0 {
+            failure::bail!("Atlas entries must not be empty");
+        }
+
+        let converter = if format == self.format && swizzle == Swizzle::NO {
+            None
+        } else {
+            Some(Converter::new(format, self.format).ok_or_else(|| {
+                failure::format_err!(
+                    "Can't convert image from {:?} to atlas format {:?}",
+                    format,
+                    self.format
+                )
+            })?)
+        };
+
+        let texel_size = format.surface_desc().bits as usize / 8;
+        let data_width = if data_width == 0 { width } else { data_width };
+        if data_width < width {
+            failure::bail!(
+                "Data width {} is less than image width {}",
+                data_width,
+                width
+            );
+        }
+
+        let row_size = width as usize * texel_size;
+        let stride = data_width as usize * texel_size;
+        let expected = stride * (height as usize - 1) + row_size;
+        if data.len() < expected {
+            failure::bail!(
+                "Image data has size {} while at least {} expected",
+                data.len(),
+                expected
+            );
+        }
+
+        let mut pixels = Vec::with_capacity(row_size * height as usize);
+        for row in 0..height as usize {
+            pixels.extend_from_slice(&data[row * stride..row * stride + row_size]);
+        }
+
+        Ok(match converter {
+            None => pixels,
+            Some(converter) => converter.convert_swizzled(&pixels, swizzle),
+        })
+    }
+}
+
+/// Get extent and pixel data of single layer, single level image.
+fn builder_image<'b>(
+    builder: &'b TextureBuilder<'_>,
+) -> Result<(u32, u32, &'b [u8]), failure::Error> {
+    match builder.kind() {
+        image::Kind::D2(width, height, 1, 1) if builder.levels() == 1 => {
+            Ok((width, height, builder.data()))
+        }
+        kind => failure::bail!(
+            "Only single layer 2D images without mip levels can be added to atlas. Got {:?}",
+            kind
+        ),
+    }
+}
+
+/// Builder for texture atlas.
+/// Packs images into layers of `D2Array` texture using skyline packer.
+#[derive(Clone, Debug)]
+pub struct TextureAtlasBuilder {
+    packer: Packer,
+    max_layers: image::Layer,
+    data: Vec<u8>,
+    sampler_info: image::SamplerInfo,
+}
+
+impl TextureAtlasBuilder {
+    /// New empty atlas with layers of `width` by `height` texels in `format`.
+    ///
+    /// `format` must be an uncompressed format.
+    pub fn new(width: u32, height: u32, format: Format) -> Self {
+        assert_eq!(
+            format.surface_desc().dim,
+            (1, 1),
+            "Block-compressed formats are not supported by atlas"
+        );
+        TextureAtlasBuilder {
+            packer: Packer {
+                width,
+                height,
+                format,
+                padding: 1,
+                layers: Vec::new(),
+                entries: Vec::new(),
+            },
+            max_layers: 1,
+            data: Vec::new(),
+            sampler_info: image::SamplerInfo::new(image::Filter::Linear, image::WrapMode::Clamp),
+        }
+    }
+
+    /// Set maximum number of layers.
+    pub fn with_max_layers(mut self, max_layers: image::Layer) -> Self {
+        self.set_max_layers(max_layers);
+        self
+    }
+
+    /// Set maximum number of layers.
+    pub fn set_max_layers(&mut self, max_layers: image::Layer) -> &mut Self {
+        assert!(max_layers > 0, "Atlas must have at least one layer");
+        self.max_layers = max_layers;
+        self
+    }
+
+    /// Set number of empty texels between images.
+    /// Prevents bleeding of neighbouring images when sampled with linear filtering.
+    pub fn with_padding(mut self, padding: u32) -> Self {
+        self.set_padding(padding);
+        self
+    }
+
+    /// Set number of empty texels between images.
+    /// Prevents bleeding of neighbouring images when sampled with linear filtering.
+    pub fn set_padding(&mut self, padding: u32) -> &mut Self {
+        self.packer.padding = padding;
+        self
+    }
+
+    /// With atlas sampler info.
+    pub fn with_sampler_info(mut self, sampler_info: image::SamplerInfo) -> Self {
+        self.set_sampler_info(sampler_info);
+        self
+    }
+
+    /// Set atlas sampler info.
+    pub fn set_sampler_info(&mut self, sampler_info: image::SamplerInfo) -> &mut Self {
+        self.sampler_info = sampler_info;
+        self
+    }
+
+    /// Get entries added so far in order of insertion.
+    pub fn entries(&self) -> &[AtlasEntry] {
+        &self.packer.entries
+    }
+
+    /// Add image loaded into `TextureBuilder`, for example with `load_from_image`.
+    /// Image must be single layer 2D image without mip levels.
+    /// Swizzle of the builder is applied to the pixels.
+    pub fn add_image(
+        &mut self,
+        builder: &TextureBuilder<'_>,
+    ) -> Result<AtlasEntry, failure::Error> {
+        let (width, height, data) = builder_image(builder)?;
+        let pixels = self.packer.prepare(
+            width,
+            height,
+            builder.data_width(),
+            data,
+            builder.format(),
+            builder.swizzle(),
+        )?;
+        self.add_pixels(width, height, &pixels)
+    }
+
+    /// Add image from pixel data.
+    pub fn add_data<P: AsPixel>(
+        &mut self,
+        width: u32,
+        height: u32,
+        data: &[P],
+    ) -> Result<AtlasEntry, failure::Error> {
+        self.add_raw_data(width, height, width, cast_slice(data), P::FORMAT)
+    }
+
+    /// Add image from pixel data with manual format definition.
+    /// Rows of the image are `data_width` texels apart.
+    pub fn add_raw_data(
+        &mut self,
+        width: u32,
+        height: u32,
+        data_width: u32,
+        data: &[u8],
+        format: Format,
+    ) -> Result<AtlasEntry, failure::Error> {
+        let pixels = self
+            .packer
+            .prepare(width, height, data_width, data, format, Swizzle::NO)?;
+        self.add_pixels(width, height, &pixels)
+    }
+
+    /// Add tightly packed pixels of atlas format.
+    fn add_pixels(
+        &mut self,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+    ) -> Result<AtlasEntry, failure::Error> {
+        let entry = match self.packer.allocate(width, height)? {
+            Some(entry) => entry,
+            None => {
+                if self.packer.layers.len() >= self.max_layers as usize {
+                    failure::bail!(
+                        "Image {}x{} doesn't fit into atlas of {} layers",
+                        width,
+                        height,
+                        self.max_layers
+                    );
+                }
+                self.packer
+                    .layers
+                    .push(Skyline::new(self.packer.width, self.packer.height));
+                let layer_size = self.layer_size();
+                self.data.resize(self.data.len() + layer_size, 0);
+                self.packer
+                    .allocate(width, height)?
+                    .expect("Image must fit into empty layer")
+            }
+        };
+
+        let texel_size = self.packer.format.surface_desc().bits as usize / 8;
+        let row_size = width as usize * texel_size;
+        let stride = self.packer.width as usize * texel_size;
+        let origin = entry.layer as usize * self.layer_size()
+            + entry.y as usize * stride
+            + entry.x as usize * texel_size;
+        for (row, pixels) in pixels.chunks(row_size).enumerate() {
+            let offset = origin + row * stride;
+            self.data[offset..offset + row_size].copy_from_slice(pixels);
+        }
+
+        self.packer.push(entry);
+        Ok(entry)
+    }
+
+    fn layer_size(&self) -> usize {
+        self.packer.width as usize
+            * self.packer.height as usize
+            * (self.packer.format.surface_desc().bits as usize / 8)
+    }
+
+    /// Build atlas texture.
+    ///
+    /// Texture has as many layers as were required to fit all images added so far.
+    /// More images can be inserted later into the free space of those layers.
+    pub fn build<B>(
+        self,
+        next_state: ImageState,
+        factory: &mut Factory<B>,
+    ) -> Result<TextureAtlas<B>, failure::Error>
+    where
+        B: Backend,
+    {
+        let TextureAtlasBuilder {
+            mut packer,
+            mut data,
+            sampler_info,
+            ..
+        } = self;
+
+        if packer.layers.is_empty() {
+            packer
+                .layers
+                .push(Skyline::new(packer.width, packer.height));
+            data.resize(
+                packer.width as usize
+                    * packer.height as usize
+                    * (packer.format.surface_desc().bits as usize / 8),
+                0,
+            );
+        }
+
+        let texture = TextureBuilder::new()
+            .with_raw_data(data, packer.format)
+            .with_data_width(packer.width)
+            .with_data_height(packer.height)
+            .with_kind(image::Kind::D2(
+                packer.width,
+                packer.height,
+                packer.layers.len() as image::Layer,
+                1,
+            ))
+            .with_view_kind(image::ViewKind::D2Array)
+            .with_sampler_info(sampler_info)
+            .build(next_state, factory)?;
+
+        Ok(TextureAtlas { texture, packer })
+    }
+}
+
+/// Texture atlas.
+/// Tracks free space of its layers to allow inserting more images.
+#[derive(Debug)]
+pub struct TextureAtlas<B: Backend> {
+    texture: Texture<B>,
+    packer: Packer,
+}
+
+impl<B> TextureAtlas<B>
+where
+    B: Backend,
+{
+    /// Get atlas texture.
+    pub fn texture(&self) -> &Texture<B> {
+        &self.texture
+    }
+
+    /// Get all entries in order of insertion.
+    pub fn entries(&self) -> &[AtlasEntry] {
+        &self.packer.entries
+    }
+
+    /// Insert image loaded into `TextureBuilder` into free space of the atlas.
+    /// Image must be single layer 2D image without mip levels.
+    /// Swizzle of the builder is applied to the pixels.
+    ///
+    /// # Safety
+    ///
+    /// See `insert_raw_data`.
+    pub unsafe fn insert_image(
+        &mut self,
+        factory: &Factory<B>,
+        builder: &TextureBuilder<'_>,
+        last_state: impl Into<ImageStateOrLayout>,
+        next_state: ImageState,
+    ) -> Result<AtlasEntry, failure::Error> {
+        let (width, height, data) = builder_image(builder)?;
+        let pixels = self.packer.prepare(
+            width,
+            height,
+            builder.data_width(),
+            data,
+            builder.format(),
+            builder.swizzle(),
+        )?;
+        self.insert_pixels(factory, width, height, &pixels, last_state, next_state)
+    }
+
+    /// Insert image from pixel data into free space of the atlas.
+    ///
+    /// # Safety
+    ///
+    /// See `insert_raw_data`.
+    pub unsafe fn insert_data<P: AsPixel>(
+        &mut self,
+        factory: &Factory<B>,
+        width: u32,
+        height: u32,
+        data: &[P],
+        last_state: impl Into<ImageStateOrLayout>,
+        next_state: ImageState,
+    ) -> Result<AtlasEntry, failure::Error> {
+        self.insert_raw_data(
+            factory,
+            width,
+            height,
+            width,
+            cast_slice(data),
+            P::FORMAT,
+            last_state,
+            next_state,
+        )
+    }
+
+    /// Insert image from pixel data with manual format definition into free space of the atlas.
+    /// Only the region occupied by the image is uploaded.
+    ///
+    /// Fails if the image doesn't fit into any layer.
+    ///
+    /// # Safety
+    ///
+    /// Atlas must be created by this `Factory`.
+    /// `last_state` and `next_state` must satisfy the requirements of `Factory::upload_image`.
+    // Image size and data layout come in addition to `Factory::upload_image` states.
+    #[allow(clippy::too_many_arguments)]
+    pub unsafe fn insert_raw_data(
+        &mut self,
+        factory: &Factory<B>,
+        width: u32,
+        height: u32,
+        data_width: u32,
+        data: &[u8],
+        format: Format,
+        last_state: impl Into<ImageStateOrLayout>,
+        next_state: ImageState,
+    ) -> Result<AtlasEntry, failure::Error> {
+        let pixels = self
+            .packer
+            .prepare(width, height, data_width, data, format, Swizzle::NO)?;
+        self.insert_pixels(factory, width, height, &pixels, last_state, next_state)
+    }
+
+    /// Insert tightly packed pixels of atlas format.
+    /// Space is reserved for the entry only after successful upload.
+    unsafe fn insert_pixels(
+        &mut self,
+        factory: &Factory<B>,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+        last_state: impl Into<ImageStateOrLayout>,
+        next_state: ImageState,
+    ) -> Result<AtlasEntry, failure::Error> {
+        let layers = self.packer.layers.clone();
+        let entry = self.packer.allocate(width, height)?.ok_or_else(|| {
+            failure::format_err!("Image {}x{} doesn't fit into atlas", width, height)
+        })?;
+
+        let result = self.texture.update_region(
+            factory,
+            entry.layer,
+            0,
+            gfx_hal::pso::Rect {
+                x: entry.x as i16,
+                y: entry.y as i16,
+                w: entry.width as i16,
+                h: entry.height as i16,
+            },
+            pixels,
+            last_state,
+            next_state,
+        );
+
+        match result {
+            Ok(()) => {
+                self.packer.push(entry);
+                Ok(entry)
+            }
+            Err(err) => {
+                self.packer.layers = layers;
+                Err(err)
+            }
+        }
+    }
+}
@@ -0,0 +1,806 @@
+//! CPU-side conversion of texel data between formats.
+//! Used when device doesn't support format of the texture data.
+
+use {
+    crate::pixel::Half,
+    gfx_hal::format::{ChannelType, Component, Format, SurfaceType, Swizzle, NUM_FORMATS},
+};
+
+const R: usize = 0;
+const G: usize = 1;
+const B: usize = 2;
+const A: usize = 3;
+
+/// Single channel of the texel.
+/// `component` is index in RGBA order.
+/// `offset` is in bits from the start of the texel for plain formats
+/// and from the least significant bit for packed formats.
+#[derive(Clone, Copy, Debug)]
+struct Channel {
+    component: usize,
+    offset: u32,
+    bits: u32,
+}
+
+macro_rules! layout {
+    ($kind:ident $size:literal: $(($component:ident, $offset:literal, $bits:literal)),*) => {{
+        const CHANNELS: &[Channel] = &[$(Channel {
+            component: $component,
+            offset: $offset,
+            bits: $bits,
+        }),*];
+        Some(TexelLayout {
+            size: $size,
+            packed: layout!(@packed $kind),
+            channels: CHANNELS,
+        })
+    }};
+    (@packed packed) => { true };
+    (@packed plain) => { false };
+}
+
+/// Layout of the texel in memory.
+#[derive(Clone, Copy, Debug)]
+struct TexelLayout {
+    /// Size of the texel in bytes.
+    size: usize,
+
+    /// Packed texels are read as single native-endian integer.
+    /// Plain texels have each channel stored as separate native-endian value.
+    packed: bool,
+
+    channels: &'static [Channel],
+}
+
+fn texel_layout(surface: SurfaceType) -> Option<TexelLayout> {
+    use SurfaceType::*;
+    match surface {
+        R4_G4 => layout!(packed 1: (R, 4, 4), (G, 0, 4)),
+        R4_G4_B4_A4 => layout!(packed 2: (R, 12, 4), (G, 8, 4), (B, 4, 4), (A, 0, 4)),
+        B4_G4_R4_A4 => layout!(packed 2: (B, 12, 4), (G, 8, 4), (R, 4, 4), (A, 0, 4)),
+        R5_G6_B5 => layout!(packed 2: (R, 11, 5), (G, 5, 6), (B, 0, 5)),
+        B5_G6_R5 => layout!(packed 2: (B, 11, 5), (G, 5, 6), (R, 0, 5)),
+        R5_G5_B5_A1 => layout!(packed 2: (R, 11, 5), (G, 6, 5), (B, 1, 5), (A, 0, 1)),
+        B5_G5_R5_A1 => layout!(packed 2: (B, 11, 5), (G, 6, 5), (R, 1, 5), (A, 0, 1)),
+        A1_R5_G5_B5 => layout!(packed 2: (A, 15, 1), (R, 10, 5), (G, 5, 5), (B, 0, 5)),
+        R8 => layout!(plain 1: (R, 0, 8)),
+        R8_G8 => layout!(plain 2: (R, 0, 8), (G, 8, 8)),
+        R8_G8_B8 => layout!(plain 3: (R, 0, 8), (G, 8, 8), (B, 16, 8)),
+        B8_G8_R8 => layout!(plain 3: (B, 0, 8), (G, 8, 8), (R, 16, 8)),
+        R8_G8_B8_A8 => layout!(plain 4: (R, 0, 8), (G, 8, 8), (B, 16, 8), (A, 24, 8)),
+        B8_G8_R8_A8 => layout!(plain 4: (B, 0, 8), (G, 8, 8), (R, 16, 8), (A, 24, 8)),
+        A8_B8_G8_R8 => layout!(packed 4: (A, 24, 8), (B, 16, 8), (G, 8, 8), (R, 0, 8)),
+        A2_R10_G10_B10 => layout!(packed 4: (A, 30, 2), (R, 20, 10), (G, 10, 10), (B, 0, 10)),
+        A2_B10_G10_R10 => layout!(packed 4: (A, 30, 2), (B, 20, 10), (G, 10, 10), (R, 0, 10)),
+        R16 => layout!(plain 2: (R, 0, 16)),
+        R16_G16 => layout!(plain 4: (R, 0, 16), (G, 16, 16)),
+        R16_G16_B16 => layout!(plain 6: (R, 0, 16), (G, 16, 16), (B, 32, 16)),
+        R16_G16_B16_A16 => layout!(plain 8: (R, 0, 16), (G, 16, 16), (B, 32, 16), (A, 48, 16)),
+        R32 => layout!(plain 4: (R, 0, 32)),
+        R32_G32 => layout!(plain 8: (R, 0, 32), (G, 32, 32)),
+        R32_G32_B32 => layout!(plain 12: (R, 0, 32), (G, 32, 32), (B, 64, 32)),
+        R32_G32_B32_A32 => layout!(plain 16: (R, 0, 32), (G, 32, 32), (B, 64, 32), (A, 96, 32)),
+        R64 => layout!(plain 8: (R, 0, 64)),
+        R64_G64 => layout!(plain 16: (R, 0, 64), (G, 64, 64)),
+        R64_G64_B64 => layout!(plain 24: (R, 0, 64), (G, 64, 64), (B, 128, 64)),
+        R64_G64_B64_A64 => layout!(plain 32: (R, 0, 64), (G, 64, 64), (B, 128, 64), (A, 192, 64)),
+        _ => None,
+    }
+}
+
+/// Numeric class of the channel type.
+/// Shaders read formats of different classes with different sampler types,
+/// so conversion never crosses classes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Class {
+    Float,
+    Uint,
+    Int,
+}
+
+fn class(channel_type: ChannelType) -> Class {
+    match channel_type {
+        ChannelType::Uint => Class::Uint,
+        ChannelType::Int => Class::Int,
+        _ => Class::Float,
+    }
+}
+
+/// Number of significant bits of the channel.
+fn precision(channel_type: ChannelType, bits: u32) -> u32 {
+    match (channel_type, bits) {
+        (ChannelType::Float, 16) => 11,
+        (ChannelType::Float, 32) => 24,
+        (ChannelType::Float, 64) => 53,
+        (_, bits) => bits,
+    }
+}
+
+/// Check if channel of `src` type can be converted to `dst` type without loss.
+/// Alpha channel of sRGB formats is linear.
+fn lossless(src: ChannelType, src_bits: u32, dst: ChannelType, dst_bits: u32, alpha: bool) -> bool {
+    let src_precision = precision(src, src_bits);
+    let dst_precision = precision(dst, dst_bits);
+    match (src, dst) {
+        _ if src == dst || alpha => dst_precision >= src_precision,
+        // Floating point values keep relative precision for dark colors.
+        (ChannelType::Srgb, ChannelType::Float) => dst_precision >= src_precision,
+        // Linear representation of sRGB values requires few more bits.
+        (ChannelType::Srgb, _) => dst_precision >= src_precision + 4,
+        // Bright linear values get closer than sRGB steps.
+        (_, ChannelType::Srgb) => false,
+        _ => dst_precision >= src_precision,
+    }
+}
+
+/// Check if values of `src` channel type can be represented in `dst` channel type.
+fn representable(src: ChannelType, dst: ChannelType) -> bool {
+    use ChannelType::*;
+    match (src, dst) {
+        (src, dst) if src == dst => true,
+        (Unorm, Srgb) | (Srgb, Unorm) => true,
+        (Unorm, Float) | (Inorm, Float) | (Srgb, Float) => true,
+        (Uscaled, Float) | (Iscaled, Float) => true,
+        _ => false,
+    }
+}
+
+pub(crate) fn srgb_to_linear(value: f64) -> f64 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f64) -> f64 {
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn mask(bits: u32) -> u64 {
+    if bits == 64 {
+        !0
+    } else {
+        (1 << bits) - 1
+    }
+}
+
+fn sign_extend(raw: u64, bits: u32) -> i64 {
+    let shift = 64 - bits;
+    ((raw << shift) as i64) >> shift
+}
+
+/// Decode raw channel bits into value.
+fn decode_channel(channel_type: ChannelType, component: usize, bits: u32, raw: u64) -> f64 {
+    let max = mask(bits) as f64;
+    let signed_max = (mask(bits) >> 1) as f64;
+    match channel_type {
+        ChannelType::Unorm => raw as f64 / max,
+        ChannelType::Inorm => (sign_extend(raw, bits) as f64 / signed_max).max(-1.0),
+        ChannelType::Srgb if component == A => raw as f64 / max,
+        ChannelType::Srgb => srgb_to_linear(raw as f64 / max),
+        ChannelType::Uint | ChannelType::Uscaled => raw as f64,
+        ChannelType::Int | ChannelType::Iscaled => sign_extend(raw, bits) as f64,
+        ChannelType::Float => match bits {
+            16 => f64::from(Half(raw as u16).to_f32()),
+            32 => f64::from(f32::from_bits(raw as u32)),
+            _ => f64::from_bits(raw),
+        },
+        ChannelType::Ufloat => unreachable!("Ufloat formats have no layout"),
+    }
+}
+
+/// Encode value into raw channel bits.
+fn encode_channel(channel_type: ChannelType, component: usize, bits: u32, value: f64) -> u64 {
+    let max = mask(bits) as f64;
+    let signed_max = (mask(bits) >> 1) as f64;
+    match channel_type {
+        ChannelType::Unorm => (value.clamp(0.0, 1.0) * max).round() as u64,
+        ChannelType::Inorm => {
+            ((value.clamp(-1.0, 1.0) * signed_max).round() as i64) as u64 & mask(bits)
+        }
+        ChannelType::Srgb if component == A => (value.clamp(0.0, 1.0) * max).round() as u64,
+        ChannelType::Srgb => (linear_to_srgb(value.clamp(0.0, 1.0)) * max).round() as u64,
+        ChannelType::Uint | ChannelType::Uscaled => value.round().max(0.0).min(max) as u64,
+        ChannelType::Int | ChannelType::Iscaled => {
+            (value.round().max(-signed_max - 1.0).min(signed_max) as i64) as u64 & mask(bits)
+        }
+        ChannelType::Float => match bits {
+            16 => u64::from(Half::from_f32(value as f32).0),
+            32 => u64::from((value as f32).to_bits()),
+            _ => value.to_bits(),
+        },
+        ChannelType::Ufloat => unreachable!("Ufloat formats have no layout"),
+    }
+}
+
+fn read_bits(bytes: &[u8]) -> u64 {
+    match bytes.len() {
+        1 => u64::from(bytes[0]),
+        2 => u64::from(u16::from_ne_bytes([bytes[0], bytes[1]])),
+        4 => {
+            let mut value = [0; 4];
+            value.copy_from_slice(bytes);
+            u64::from(u32::from_ne_bytes(value))
+        }
+        8 => {
+            let mut value = [0; 8];
+            value.copy_from_slice(bytes);
+            u64::from_ne_bytes(value)
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn write_bits(bytes: &mut [u8], value: u64) {
+    match bytes.len() {
+        1 => bytes[0] = value as u8,
+        2 => bytes.copy_from_slice(&(value as u16).to_ne_bytes()),
+        4 => bytes.copy_from_slice(&(value as u32).to_ne_bytes()),
+        8 => bytes.copy_from_slice(&value.to_ne_bytes()),
+        _ => unreachable!(),
+    }
+}
+
+/// Format with known texel layout.
+#[derive(Clone, Copy, Debug)]
+struct Texel {
+    format: Format,
+    channel_type: ChannelType,
+    layout: TexelLayout,
+}
+
+impl Texel {
+    fn new(format: Format) -> Option<Self> {
+        if format.surface_desc().dim != (1, 1) {
+            return None;
+        }
+        let base = format.base_format();
+        if base.1 == ChannelType::Ufloat {
+            return None;
+        }
+        Some(Texel {
+            format,
+            channel_type: base.1,
+            layout: texel_layout(base.0)?,
+        })
+    }
+
+    fn has(&self, component: usize) -> bool {
+        self.layout
+            .channels
+            .iter()
+            .any(|channel| channel.component == component)
+    }
+
+    fn bits(&self, component: usize) -> Option<u32> {
+        self.layout
+            .channels
+            .iter()
+            .find(|channel| channel.component == component)
+            .map(|channel| channel.bits)
+    }
+
+    /// Read texel into RGBA values.
+    /// Missing components are filled as device does: `(0, 0, 0, 1)`.
+    fn decode(&self, texel: &[u8]) -> [f64; 4] {
+        let mut rgba = [0.0, 0.0, 0.0, 1.0];
+        if self.layout.packed {
+            let raw = read_bits(texel);
+            for channel in self.layout.channels {
+                let bits = (raw >> channel.offset) & mask(channel.bits);
+                rgba[channel.component] =
+                    decode_channel(self.channel_type, channel.component, channel.bits, bits);
+            }
+        } else {
+            for channel in self.layout.channels {
+                let start = channel.offset as usize / 8;
+                let raw = read_bits(&texel[start..start + channel.bits as usize / 8]);
+                rgba[channel.component] =
+                    decode_channel(self.channel_type, channel.component, channel.bits, raw);
+            }
+        }
+        rgba
+    }
+
+    /// Write RGBA values into texel.
+    fn encode(&self, rgba: [f64; 4], texel: &mut [u8]) {
+        if self.layout.packed {
+            let mut raw = 0;
+            for channel in self.layout.channels {
+                let bits = encode_channel(
+                    self.channel_type,
+                    channel.component,
+                    channel.bits,
+                    rgba[channel.component],
+                );
+                raw |= (bits & mask(channel.bits)) << channel.offset;
+            }
+            write_bits(texel, raw);
+        } else {
+            for channel in self.layout.channels {
+                let start = channel.offset as usize / 8;
+                let bits = encode_channel(
+                    self.channel_type,
+                    channel.component,
+                    channel.bits,
+                    rgba[channel.component],
+                );
+                write_bits(&mut texel[start..start + channel.bits as usize / 8], bits);
+            }
+        }
+    }
+}
+
+/// Converts texel data from one format to another.
+///
+/// Texels are decoded into RGBA values the way device would read them
+/// (normalized, sRGB-decoded, with missing components set to `(0, 0, 0, 1)`)
+/// and encoded into target format.
+/// So sampling converted data yields the same values up to precision of the target format.
+#[derive(Clone, Copy, Debug)]
+pub struct Converter {
+    src: Texel,
+    dst: Texel,
+}
+
+impl Converter {
+    /// Create converter from `src` format to `dst` format.
+    ///
+    /// Returns `None` if either format has no known texel layout
+    /// (block-compressed, depth-stencil, shared exponent and packed float formats),
+    /// if formats are read with different sampler types by shaders
+    /// (float, unsigned integer and signed integer)
+    /// or if range of source values can't be represented by the destination format.
+    pub fn new(src: Format, dst: Format) -> Option<Self> {
+        let src = Texel::new(src)?;
+        let dst = Texel::new(dst)?;
+        if class(src.channel_type) != class(dst.channel_type)
+            || !representable(src.channel_type, dst.channel_type)
+        {
+            return None;
+        }
+        Some(Converter { src, dst })
+    }
+
+    /// Source format.
+    pub fn src(&self) -> Format {
+        self.src.format
+    }
+
+    /// Destination format.
+    pub fn dst(&self) -> Format {
+        self.dst.format
+    }
+
+    /// Converter performing reverse conversion.
+    /// Values that can't be represented by the source format are clamped.
+    pub fn inverse(&self) -> Self {
+        Converter {
+            src: self.dst,
+            dst: self.src,
+        }
+    }
+
+    /// Check if conversion preserves all values of the source format.
+    pub fn is_lossless(&self) -> bool {
+        self.src.layout.channels.iter().all(|channel| {
+            self.dst.bits(channel.component).map_or(false, |bits| {
+                lossless(
+                    self.src.channel_type,
+                    channel.bits,
+                    self.dst.channel_type,
+                    bits,
+                    channel.component == A,
+                )
+            })
+        })
+    }
+
+    /// Get byte ranges to copy from source texel to destination texel
+    /// if conversion only reorders, drops or adds channels.
+    /// Returns `None` if values need to be converted.
+    fn copies(&self) -> Option<Vec<(usize, usize, usize)>> {
+        if self.src.layout.packed
+            || self.dst.layout.packed
+            || self.src.channel_type != self.dst.channel_type
+        {
+            return None;
+        }
+
+        self.dst
+            .layout
+            .channels
+            .iter()
+            .filter_map(|dst| {
+                let src = self
+                    .src
+                    .layout
+                    .channels
+                    .iter()
+                    .find(|src| src.component == dst.component)?;
+                Some(if src.bits == dst.bits {
+                    Some((
+                        src.offset as usize / 8,
+                        dst.offset as usize / 8,
+                        dst.bits as usize / 8,
+                    ))
+                } else {
+                    None
+                })
+            })
+            .collect()
+    }
+
+    /// Convert tightly packed texels.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `data` length is not multiple of source texel size.
+    pub fn convert(&self, data: &[u8]) -> Vec<u8> {
+        self.convert_swizzled(data, Swizzle::NO)
+    }
+
+    /// Convert tightly packed texels,
+    /// applying `swizzle` to decoded values the way device applies swizzle of image view.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `data` length is not multiple of source texel size.
+    pub fn convert_swizzled(&self, data: &[u8], swizzle: Swizzle) -> Vec<u8> {
+        assert_eq!(
+            data.len() % self.src.layout.size,
+            0,
+            "Data must consist of whole texels"
+        );
+        let count = data.len() / self.src.layout.size;
+        let mut result = vec![0; count * self.dst.layout.size];
+        let texels = data
+            .chunks_exact(self.src.layout.size)
+            .zip(result.chunks_exact_mut(self.dst.layout.size));
+
+        let copies = if swizzle == Swizzle::NO {
+            self.copies()
+        } else {
+            None
+        };
+
+        match copies {
+            Some(copies) => {
+                // Components missing in the source are filled with defaults.
+                let mut template = vec![0; self.dst.layout.size];
+                self.dst.encode([0.0, 0.0, 0.0, 1.0], &mut template);
+                for (src, dst) in texels {
+                    dst.copy_from_slice(&template);
+                    for &(src_offset, dst_offset, size) in &copies {
+                        dst[dst_offset..dst_offset + size]
+                            .copy_from_slice(&src[src_offset..src_offset + size]);
+                    }
+                }
+            }
+            None => {
+                for (src, dst) in texels {
+                    self.dst
+                        .encode(apply_swizzle(self.src.decode(src), swizzle), dst);
+                }
+            }
+        }
+        result
+    }
+
+    /// Ordering key of the conversion. Lower is better.
+    fn cost(&self) -> (bool, bool, usize, bool) {
+        (
+            !self.is_lossless(),
+            self.src.channel_type != self.dst.channel_type,
+            self.dst.layout.size,
+            // Prefer targets that don't add components.
+            (R..=A).any(|c| self.dst.has(c) && !self.src.has(c)),
+        )
+    }
+}
+
+fn apply_swizzle(rgba: [f64; 4], Swizzle(r, g, b, a): Swizzle) -> [f64; 4] {
+    let component = |component| match component {
+        Component::Zero => 0.0,
+        Component::One => 1.0,
+        Component::R => rgba[R],
+        Component::G => rgba[G],
+        Component::B => rgba[B],
+        Component::A => rgba[A],
+    };
+    [component(r), component(g), component(b), component(a)]
+}
+
+fn all_formats() -> impl Iterator<Item = Format> {
+    // `Format` variants are numbered from 1 to `NUM_FORMATS - 1` without gaps.
+    (1..NUM_FORMATS as u32).map(|value| unsafe { std::mem::transmute::<u32, Format>(value) })
+}
+
+/// Get list of formats into which texels of `format` can be converted
+/// without dropping components, ordered by preference.
+/// Lossless conversions with the same channel type and smallest texel size come first.
+pub fn conversion_targets(format: Format) -> Vec<Converter> {
+    let src = match Texel::new(format) {
+        Some(src) => src,
+        None => return Vec::new(),
+    };
+
+    let mut targets: Vec<Converter> = all_formats()
+        .filter(|&dst| dst != format)
+        .filter_map(|dst| Converter::new(format, dst))
+        .filter(|converter| (R..=A).all(|c| !src.has(c) || converter.dst.has(c)))
+        .collect();
+
+    targets.sort_by_key(Converter::cost);
+    targets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(src: Format, dst: Format, data: &[u8]) -> Vec<u8> {
+        Converter::new(src, dst).unwrap().convert(data)
+    }
+
+    /// Convert back with inverse of `src` to `dst` conversion.
+    fn convert_back(src: Format, dst: Format, data: &[u8]) -> Vec<u8> {
+        Converter::new(src, dst).unwrap().inverse().convert(data)
+    }
+
+    /// Convert with decoding and encoding of every channel, bypassing byte copies.
+    fn convert_slow(src: Format, dst: Format, data: &[u8]) -> Vec<u8> {
+        let converter = Converter::new(src, dst).unwrap();
+        let mut result = Vec::new();
+        for texel in data.chunks_exact(converter.src.layout.size) {
+            let mut out = vec![0; converter.dst.layout.size];
+            converter.dst.encode(converter.src.decode(texel), &mut out);
+            result.extend_from_slice(&out);
+        }
+        result
+    }
+
+    fn u16s(data: &[u8]) -> Vec<u16> {
+        data.chunks_exact(2)
+            .map(|c| u16::from_ne_bytes([c[0], c[1]]))
+            .collect()
+    }
+
+    fn f32s(data: &[u8]) -> Vec<f32> {
+        data.chunks_exact(4)
+            .map(|c| f32::from_bits(u32::from_ne_bytes([c[0], c[1], c[2], c[3]])))
+            .collect()
+    }
+
+    fn bytes_u16(values: &[u16]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|v| v.to_ne_bytes().to_vec())
+            .collect()
+    }
+
+    fn bytes_f32(values: &[f32]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|v| v.to_bits().to_ne_bytes().to_vec())
+            .collect()
+    }
+
+    fn all_bytes() -> Vec<u8> {
+        (0..=255).collect()
+    }
+
+    #[test]
+    fn unorm() {
+        let data = [0, 128, 255];
+        assert_eq!(
+            u16s(&convert(Format::R8Unorm, Format::R16Unorm, &data)),
+            [0, 32896, 65535]
+        );
+        assert_eq!(
+            convert(
+                Format::R16Unorm,
+                Format::R8Unorm,
+                &bytes_u16(&[0, 32896, 65535])
+            ),
+            data
+        );
+
+        let data = all_bytes();
+        let wide = convert(Format::R8Unorm, Format::R16Unorm, &data);
+        assert_eq!(convert(Format::R16Unorm, Format::R8Unorm, &wide), data);
+        assert!(Converter::new(Format::R8Unorm, Format::R16Unorm)
+            .unwrap()
+            .is_lossless());
+        assert!(!Converter::new(Format::R16Unorm, Format::R8Unorm)
+            .unwrap()
+            .is_lossless());
+    }
+
+    #[test]
+    fn snorm() {
+        let data = [0u8, 127, 0x81, 0x80];
+        assert_eq!(
+            u16s(&convert(Format::R8Inorm, Format::R16Inorm, &data)),
+            // Both -127 and -128 are -1.0.
+            [0, 32767, (-32767i16) as u16, (-32767i16) as u16]
+        );
+
+        let data: Vec<u8> = (0..=255u8).filter(|&v| v != 0x80).collect();
+        let wide = convert(Format::R8Inorm, Format::R16Inorm, &data);
+        assert_eq!(convert(Format::R16Inorm, Format::R8Inorm, &wide), data);
+    }
+
+    #[test]
+    fn srgb() {
+        // Alpha is not sRGB-encoded.
+        let data = [0, 188, 255, 128];
+        let linear = u16s(&convert(Format::Rgba8Srgb, Format::Rgba16Unorm, &data));
+        assert_eq!(linear[0], 0);
+        assert_eq!(
+            linear[1],
+            (srgb_to_linear(188.0 / 255.0) * 65535.0).round() as u16
+        );
+        assert_eq!(linear[2], 65535);
+        assert_eq!(linear[3], 32896);
+
+        let data: Vec<u8> = (0..=255).flat_map(|v| vec![v, v, v, v]).collect();
+        let linear = convert(Format::Rgba8Srgb, Format::Rgba16Unorm, &data);
+        assert_eq!(
+            convert(Format::Rgba16Unorm, Format::Rgba8Srgb, &linear),
+            data
+        );
+    }
+
+    #[test]
+    fn srgb_precision() {
+        let lossless = |src, dst| Converter::new(src, dst).unwrap().is_lossless();
+        assert!(lossless(Format::R8Srgb, Format::R16Unorm));
+        assert!(lossless(Format::R8Srgb, Format::R16Float));
+        assert!(!lossless(Format::R8Srgb, Format::R8Unorm));
+        assert!(!lossless(Format::R8Unorm, Format::R8Srgb));
+        assert!(!lossless(Format::R16Unorm, Format::R8Srgb));
+        assert!(lossless(Format::Rgba8Srgb, Format::Bgra8Srgb));
+    }
+
+    #[test]
+    fn float() {
+        let data = [0, 51, 255];
+        assert_eq!(
+            f32s(&convert(Format::R8Unorm, Format::R32Float, &data)),
+            [0.0, 0.2, 1.0]
+        );
+        let data = all_bytes();
+        let float = convert(Format::R8Unorm, Format::R32Float, &data);
+        assert_eq!(
+            convert_back(Format::R8Unorm, Format::R32Float, &float),
+            data
+        );
+
+        // Out of range values are clamped.
+        assert_eq!(
+            convert_back(
+                Format::R8Unorm,
+                Format::R32Float,
+                &bytes_f32(&[-1.0, 0.5, 2.0])
+            ),
+            [0, 128, 255]
+        );
+    }
+
+    #[test]
+    fn half() {
+        let values = [0.0, 0.5, 1.0, -2.0, 65504.0];
+        let half = u16s(&convert(
+            Format::R32Float,
+            Format::R16Float,
+            &bytes_f32(&values),
+        ));
+        assert_eq!(half, [0x0000, 0x3800, 0x3C00, 0xC000, 0x7BFF]);
+        assert_eq!(
+            f32s(&convert(
+                Format::R16Float,
+                Format::R32Float,
+                &bytes_u16(&half)
+            )),
+            values
+        );
+
+        let data = all_bytes();
+        let half = convert(Format::R8Unorm, Format::R16Float, &data);
+        assert_eq!(convert_back(Format::R8Unorm, Format::R16Float, &half), data);
+        assert!(Converter::new(Format::R8Unorm, Format::R16Float)
+            .unwrap()
+            .is_lossless());
+        assert!(!Converter::new(Format::R32Float, Format::R16Float)
+            .unwrap()
+            .is_lossless());
+    }
+
+    #[test]
+    fn reorder() {
+        let data = [1, 2, 3, 4, 5, 6, 7, 8];
+        assert_eq!(
+            convert(Format::Rgba8Unorm, Format::Bgra8Unorm, &data),
+            [3, 2, 1, 4, 7, 6, 5, 8]
+        );
+        assert_eq!(
+            convert(Format::Bgra8Unorm, Format::Rgba8Unorm, &data),
+            [3, 2, 1, 4, 7, 6, 5, 8]
+        );
+        assert_eq!(
+            convert(Format::Rgba8Srgb, Format::Bgra8Srgb, &data),
+            convert_slow(Format::Rgba8Srgb, Format::Bgra8Srgb, &data)
+        );
+    }
+
+    #[test]
+    fn padding() {
+        assert_eq!(
+            convert(Format::Rgb8Unorm, Format::Rgba8Unorm, &[1, 2, 3]),
+            [1, 2, 3, 255]
+        );
+        assert_eq!(
+            convert(Format::R8Unorm, Format::Rgba8Unorm, &[7]),
+            [7, 0, 0, 255]
+        );
+        assert_eq!(
+            u16s(&convert(
+                Format::Rg16Uint,
+                Format::Rgba16Uint,
+                &bytes_u16(&[10, 20])
+            )),
+            [10, 20, 0, 1]
+        );
+        assert_eq!(
+            f32s(&convert(
+                Format::Rgb32Float,
+                Format::Rgba32Float,
+                &bytes_f32(&[0.25, 0.5, 0.75])
+            )),
+            [0.25, 0.5, 0.75, 1.0]
+        );
+
+        let data = bytes_u16(&[1, 2, 3, 65535, 40000, 0]);
+        assert_eq!(
+            convert(Format::Rgb16Unorm, Format::Rgba16Unorm, &data),
+            convert_slow(Format::Rgb16Unorm, Format::Rgba16Unorm, &data)
+        );
+    }
+
+    #[test]
+    fn swizzle() {
+        let converter = Converter::new(Format::R8Unorm, Format::Rgba8Unorm).unwrap();
+        let luma = Swizzle(Component::R, Component::R, Component::R, Component::One);
+        assert_eq!(
+            converter.convert_swizzled(&[7, 200], luma),
+            [7, 7, 7, 255, 200, 200, 200, 255]
+        );
+
+        let converter = Converter::new(Format::Rgba8Unorm, Format::Rgba8Unorm).unwrap();
+        let bgra = Swizzle(Component::B, Component::G, Component::R, Component::A);
+        assert_eq!(
+            converter.convert_swizzled(&[1, 2, 3, 4], bgra),
+            [3, 2, 1, 4]
+        );
+    }
+
+    #[test]
+    fn packed() {
+        // R5G6B5 with all bits set in red only.
+        let data = 0xF800u16.to_ne_bytes();
+        assert_eq!(
+            convert(Format::R5g6b5Unorm, Format::Rgba8Unorm, &data),
+            [255, 0, 0, 255]
+        );
+        assert_eq!(
+            convert(Format::Rgba8Unorm, Format::R5g6b5Unorm, &[255, 0, 0, 255]),
+            data
+        );
+    }
+}
@@ -1,7 +1,13 @@
-//! Exports the image and palette modules if the features
+//! Exports the image, panorama, palette, ktx and dds modules if the features
 //! are enabled
 
+#[cfg(feature = "dds")]
+pub mod dds;
 #[cfg(feature = "image")]
 pub mod image;
+#[cfg(feature = "ktx")]
+pub mod ktx;
 #[cfg(feature = "palette")]
 pub mod palette;
+#[cfg(feature = "image")]
+pub mod panorama;
@@ -0,0 +1,350 @@
+//! Module that turns a DDS container into a `Texture`
+
+use {
+    crate::{
+        texture::{align_up, level_size},
+        TextureBuilder,
+    },
+    gfx_hal::{
+        format::{Component, Format, Swizzle},
+        image::{Kind, ViewKind},
+    },
+};
+
+const MAGIC: [u8; 4] = *b"DDS ";
+
+/// Size of the magic and `DDS_HEADER`.
+const HEADER_SIZE: usize = 128;
+
+/// Size of the `DDS_HEADER_DXT10`.
+const HEADER_DXT10_SIZE: usize = 20;
+
+const DDSD_DEPTH: u32 = 0x80_0000;
+const DDSD_MIPMAPCOUNT: u32 = 0x2_0000;
+
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_ALPHA: u32 = 0x2;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDPF_LUMINANCE: u32 = 0x2_0000;
+
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_CUBEMAP_ALLFACES: u32 = 0xFC00;
+const DDSCAPS2_VOLUME: u32 = 0x20_0000;
+
+const DDS_DIMENSION_TEXTURE1D: u32 = 2;
+const DDS_DIMENSION_TEXTURE2D: u32 = 3;
+const DDS_DIMENSION_TEXTURE3D: u32 = 4;
+const DDS_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut value = [0; 4];
+    value.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(value)
+}
+
+const fn four_cc(code: &[u8; 4]) -> u32 {
+    code[0] as u32 | (code[1] as u32) << 8 | (code[2] as u32) << 16 | (code[3] as u32) << 24
+}
+
+/// Map `DXGI_FORMAT` value to `Format`.
+fn dxgi_format(value: u32) -> Option<Format> {
+    Some(match value {
+        2 => Format::Rgba32Float,
+        3 => Format::Rgba32Uint,
+        4 => Format::Rgba32Int,
+        6 => Format::Rgb32Float,
+        7 => Format::Rgb32Uint,
+        8 => Format::Rgb32Int,
+        10 => Format::Rgba16Float,
+        11 => Format::Rgba16Unorm,
+        12 => Format::Rgba16Uint,
+        13 => Format::Rgba16Inorm,
+        14 => Format::Rgba16Int,
+        16 => Format::Rg32Float,
+        17 => Format::Rg32Uint,
+        18 => Format::Rg32Int,
+        24 => Format::A2b10g10r10Unorm,
+        25 => Format::A2b10g10r10Uint,
+        26 => Format::B10g11r11Ufloat,
+        28 => Format::Rgba8Unorm,
+        29 => Format::Rgba8Srgb,
+        30 => Format::Rgba8Uint,
+        31 => Format::Rgba8Inorm,
+        32 => Format::Rgba8Int,
+        34 => Format::Rg16Float,
+        35 => Format::Rg16Unorm,
+        36 => Format::Rg16Uint,
+        37 => Format::Rg16Inorm,
+        38 => Format::Rg16Int,
+        40 => Format::D32Float,
+        41 => Format::R32Float,
+        42 => Format::R32Uint,
+        43 => Format::R32Int,
+        49 => Format::Rg8Unorm,
+        50 => Format::Rg8Uint,
+        51 => Format::Rg8Inorm,
+        52 => Format::Rg8Int,
+        54 => Format::R16Float,
+        55 => Format::D16Unorm,
+        56 => Format::R16Unorm,
+        57 => Format::R16Uint,
+        58 => Format::R16Inorm,
+        59 => Format::R16Int,
+        61 => Format::R8Unorm,
+        62 => Format::R8Uint,
+        63 => Format::R8Inorm,
+        64 => Format::R8Int,
+        67 => Format::E5b9g9r9Ufloat,
+        71 => Format::Bc1RgbaUnorm,
+        72 => Format::Bc1RgbaSrgb,
+        74 => Format::Bc2Unorm,
+        75 => Format::Bc2Srgb,
+        77 => Format::Bc3Unorm,
+        78 => Format::Bc3Srgb,
+        80 => Format::Bc4Unorm,
+        81 => Format::Bc4Inorm,
+        83 => Format::Bc5Unorm,
+        84 => Format::Bc5Inorm,
+        85 => Format::R5g6b5Unorm,
+        86 => Format::A1r5g5b5Unorm,
+        87 => Format::Bgra8Unorm,
+        91 => Format::Bgra8Srgb,
+        95 => Format::Bc6hUfloat,
+        96 => Format::Bc6hFloat,
+        98 => Format::Bc7Unorm,
+        99 => Format::Bc7Srgb,
+        _ => return None,
+    })
+}
+
+/// Map legacy `DDS_PIXELFORMAT` to `Format` and swizzle.
+fn legacy_format(bytes: &[u8]) -> Option<(Format, Swizzle)> {
+    let flags = read_u32(bytes, 80);
+    let code = read_u32(bytes, 84);
+    let bits = read_u32(bytes, 88);
+    let masks = (
+        read_u32(bytes, 92),
+        read_u32(bytes, 96),
+        read_u32(bytes, 100),
+        read_u32(bytes, 104),
+    );
+
+    if flags & DDPF_FOURCC != 0 {
+        let format = match code {
+            c if c == four_cc(b"DXT1") => Format::Bc1RgbaUnorm,
+            c if c == four_cc(b"DXT2") || c == four_cc(b"DXT3") => Format::Bc2Unorm,
+            c if c == four_cc(b"DXT4") || c == four_cc(b"DXT5") => Format::Bc3Unorm,
+            c if c == four_cc(b"ATI1") || c == four_cc(b"BC4U") => Format::Bc4Unorm,
+            c if c == four_cc(b"BC4S") => Format::Bc4Inorm,
+            c if c == four_cc(b"ATI2") || c == four_cc(b"BC5U") => Format::Bc5Unorm,
+            c if c == four_cc(b"BC5S") => Format::Bc5Inorm,
+            // `D3DFORMAT` values.
+            36 => Format::Rgba16Unorm,
+            110 => Format::Rgba16Inorm,
+            111 => Format::R16Float,
+            112 => Format::Rg16Float,
+            113 => Format::Rgba16Float,
+            114 => Format::R32Float,
+            115 => Format::Rg32Float,
+            116 => Format::Rgba32Float,
+            _ => return None,
+        };
+        return Some((format, Swizzle::NO));
+    }
+
+    let no_alpha = Swizzle(Component::R, Component::G, Component::B, Component::One);
+    let alpha = flags & DDPF_ALPHAPIXELS != 0;
+
+    if flags & DDPF_RGB != 0 {
+        Some(match (bits, masks) {
+            (32, (0xFF, 0xFF00, 0xFF_0000, a)) => (
+                Format::Rgba8Unorm,
+                if alpha && a != 0 {
+                    Swizzle::NO
+                } else {
+                    no_alpha
+                },
+            ),
+            (32, (0xFF_0000, 0xFF00, 0xFF, a)) => (
+                Format::Bgra8Unorm,
+                if alpha && a != 0 {
+                    Swizzle::NO
+                } else {
+                    no_alpha
+                },
+            ),
+            (32, (0x3FF, 0xF_FC00, 0x3FF0_0000, _)) => (Format::A2b10g10r10Unorm, Swizzle::NO),
+            (32, (0xFFFF, 0xFFFF_0000, 0, 0)) => (Format::Rg16Unorm, Swizzle::NO),
+            (24, (0xFF, 0xFF00, 0xFF_0000, 0)) => (Format::Rgb8Unorm, Swizzle::NO),
+            (24, (0xFF_0000, 0xFF00, 0xFF, 0)) => (Format::Bgr8Unorm, Swizzle::NO),
+            (16, (0xF800, 0x7E0, 0x1F, 0)) => (Format::R5g6b5Unorm, Swizzle::NO),
+            (16, (0x7C00, 0x3E0, 0x1F, a)) => (
+                Format::A1r5g5b5Unorm,
+                if alpha && a != 0 {
+                    Swizzle::NO
+                } else {
+                    no_alpha
+                },
+            ),
+            _ => return None,
+        })
+    } else if flags & DDPF_LUMINANCE != 0 {
+        let luma = Swizzle(Component::R, Component::R, Component::R, Component::One);
+        let luma_alpha = Swizzle(Component::R, Component::R, Component::R, Component::G);
+        Some(match (bits, alpha) {
+            (8, false) => (Format::R8Unorm, luma),
+            (16, false) => (Format::R16Unorm, luma),
+            (16, true) => (Format::Rg8Unorm, luma_alpha),
+            _ => return None,
+        })
+    } else if flags & DDPF_ALPHA != 0 && bits == 8 {
+        Some((
+            Format::R8Unorm,
+            Swizzle(
+                Component::Zero,
+                Component::Zero,
+                Component::Zero,
+                Component::R,
+            ),
+        ))
+    } else {
+        None
+    }
+}
+
+/// Attempts to load a Texture from a DDS container.
+///
+/// All mip levels, array layers and cube faces stored in the container are preserved.
+/// Both legacy and `DX10` headers are supported.
+/// Partial cube maps are not supported.
+pub fn load_from_dds(bytes: &[u8]) -> Result<TextureBuilder<'static>, failure::Error> {
+    if bytes.len() < HEADER_SIZE || bytes[..4] != MAGIC || read_u32(bytes, 4) != 124 {
+        failure::bail!("Not a DDS container");
+    }
+
+    let flags = read_u32(bytes, 8);
+    let height = read_u32(bytes, 12);
+    let width = read_u32(bytes, 16);
+    let depth = if flags & DDSD_DEPTH != 0 {
+        read_u32(bytes, 24).max(1)
+    } else {
+        1
+    };
+    let levels = if flags & DDSD_MIPMAPCOUNT != 0 {
+        read_u32(bytes, 28).max(1)
+    } else {
+        1
+    };
+    let caps2 = read_u32(bytes, 112);
+
+    if levels > 32 {
+        failure::bail!("Invalid DDS level count {}", levels);
+    }
+    if width == 0 || height == 0 {
+        failure::bail!("DDS image size {}x{} must not be zero", width, height);
+    }
+
+    let is_dx10 = read_u32(bytes, 80) & DDPF_FOURCC != 0 && read_u32(bytes, 84) == four_cc(b"DX10");
+
+    let (format, swizzle, kind, view_kind, data_offset) = if is_dx10 {
+        if bytes.len() < HEADER_SIZE + HEADER_DXT10_SIZE {
+            failure::bail!("DDS DX10 header is truncated");
+        }
+
+        let dxgi_format_value = read_u32(bytes, HEADER_SIZE);
+        let dimension = read_u32(bytes, HEADER_SIZE + 4);
+        let misc = read_u32(bytes, HEADER_SIZE + 8);
+        let array_size = read_u32(bytes, HEADER_SIZE + 12).max(1);
+
+        let format = dxgi_format(dxgi_format_value)
+            .ok_or_else(|| failure::format_err!("Unsupported DXGI format {}", dxgi_format_value))?;
+
+        let cube = misc & DDS_RESOURCE_MISC_TEXTURECUBE != 0;
+        let layers = if cube { array_size * 6 } else { array_size };
+        if layers > u16::MAX as u32 {
+            failure::bail!("Too many DDS layers: {}", layers);
+        }
+        let layers = layers as u16;
+
+        let (kind, view_kind) = match dimension {
+            DDS_DIMENSION_TEXTURE1D if array_size == 1 => (Kind::D1(width, 1), ViewKind::D1),
+            DDS_DIMENSION_TEXTURE1D => (Kind::D1(width, layers), ViewKind::D1Array),
+            DDS_DIMENSION_TEXTURE2D if cube && array_size == 1 => {
+                (Kind::D2(width, height, layers, 1), ViewKind::Cube)
+            }
+            DDS_DIMENSION_TEXTURE2D if cube => {
+                (Kind::D2(width, height, layers, 1), ViewKind::CubeArray)
+            }
+            DDS_DIMENSION_TEXTURE2D if array_size == 1 => {
+                (Kind::D2(width, height, 1, 1), ViewKind::D2)
+            }
+            DDS_DIMENSION_TEXTURE2D => (Kind::D2(width, height, layers, 1), ViewKind::D2Array),
+            DDS_DIMENSION_TEXTURE3D => (Kind::D3(width, height, depth), ViewKind::D3),
+            _ => failure::bail!("Unsupported DDS resource dimension {}", dimension),
+        };
+
+        (
+            format,
+            Swizzle::NO,
+            kind,
+            view_kind,
+            HEADER_SIZE + HEADER_DXT10_SIZE,
+        )
+    } else {
+        let (format, swizzle) = legacy_format(bytes)
+            .ok_or_else(|| failure::format_err!("Unsupported DDS pixel format"))?;
+
+        let (kind, view_kind) = if caps2 & DDSCAPS2_CUBEMAP != 0 {
+            if caps2 & DDSCAPS2_CUBEMAP_ALLFACES != DDSCAPS2_CUBEMAP_ALLFACES {
+                failure::bail!("Partial DDS cube maps are not supported");
+            }
+            (Kind::D2(width, height, 6, 1), ViewKind::Cube)
+        } else if caps2 & DDSCAPS2_VOLUME != 0 {
+            (Kind::D3(width, height, depth), ViewKind::D3)
+        } else {
+            (Kind::D2(width, height, 1, 1), ViewKind::D2)
+        };
+
+        (format, swizzle, kind, view_kind, HEADER_SIZE)
+    };
+
+    let layers = kind.num_layers() as usize;
+    let level_sizes = (0..levels)
+        .map(|level| level_size(format, kind, level as u8).map(|size| size / layers))
+        .collect::<Option<Vec<usize>>>()
+        .ok_or_else(|| failure::format_err!("DDS image is too large"))?;
+    let layer_size = level_sizes
+        .iter()
+        .try_fold(0usize, |sum, &size| sum.checked_add(size))
+        .ok_or_else(|| failure::format_err!("DDS image is too large"))?;
+
+    let content = layer_size
+        .checked_mul(layers)
+        .and_then(|size| bytes.get(data_offset..data_offset.checked_add(size)?))
+        .ok_or_else(|| failure::format_err!("DDS pixel data is truncated"))?;
+
+    // DDS stores all levels of the first layer, then all levels of the second layer and so on.
+    // `TextureBuilder` expects all layers of the first level, then all layers of the second one.
+    let mut data = Vec::with_capacity(content.len());
+    let mut level_offset = 0;
+    for &level_size in &level_sizes {
+        for layer in 0..layers {
+            let start = layer * layer_size + level_offset;
+            data.extend_from_slice(&content[start..start + level_size]);
+        }
+        level_offset += level_size;
+    }
+
+    let (block_width, block_height) = format.surface_desc().dim;
+    let extent = kind.extent();
+
+    Ok(TextureBuilder::new()
+        .with_raw_data(data, format)
+        .with_swizzle(swizzle)
+        .with_data_width(align_up(extent.width, block_width as u32))
+        .with_data_height(align_up(extent.height, block_height as u32))
+        .with_levels(levels as u8)
+        .with_kind(kind)
+        .with_view_kind(view_kind))
+}
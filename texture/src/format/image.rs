@@ -1,6 +1,6 @@
 //! Module that turns an image into a `Texture`
 
-use crate::{pixel, TextureBuilder};
+use crate::{convert::srgb_to_linear, pixel, TextureBuilder};
 use derivative::Derivative;
 
 // reexport for easy usage in ImageTextureConfig
@@ -20,6 +20,18 @@ pub enum Repr {
     Srgb,
 }
 
+/// Size of channels of textures loaded from floating point images.
+#[derive(Derivative, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derivative(Default)]
+pub enum FloatRepr {
+    /// 16 bit floating point channels.
+    Half,
+    /// 32 bit floating point channels.
+    #[derivative(Default)]
+    Single,
+}
+
 /// Determines the way layers are being stored in source image.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -177,6 +189,9 @@ pub struct ImageTextureConfig {
     #[cfg_attr(feature = "serde", serde(with = "serde_image_format"))]
     pub format: Option<ImageFormat>,
     pub repr: Repr,
+    /// Channel size for floating point images such as HDR.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub float_repr: FloatRepr,
     pub kind: TextureKind,
     #[derivative(Default(
         value = "gfx_hal::image::SamplerInfo::new(gfx_hal::image::Filter::Linear, gfx_hal::image::WrapMode::Clamp)"
@@ -185,10 +200,12 @@ pub struct ImageTextureConfig {
 }
 
 #[cfg(feature = "serde")]
-mod serde_image_format {
+pub(crate) mod serde_image_format {
     //! Module for enabline serde to serialize and deserialize image formats
     use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
+    // Variant names must match `image::ImageFormat`.
+    #[allow(clippy::upper_case_acronyms)]
     #[derive(Serialize, Deserialize)]
     #[serde(remote = "image::ImageFormat")]
     enum SerdeImageFormat {
@@ -225,6 +242,17 @@ macro_rules! dyn_format {
     ($channel:ident, $size:ident, $repr:ident) => {
         <pixel::Pixel<pixel::$channel, pixel::$size, pixel::$repr> as pixel::AsPixel>::FORMAT
     };
+    ($channel:ident, _16, $repr:expr) => {{
+        // There are no sRGB formats with 16 bit channels.
+        match $repr {
+            Repr::Unorm | Repr::Srgb => dyn_format!($channel, _16, Unorm),
+            Repr::Inorm => dyn_format!($channel, _16, Inorm),
+            Repr::Uscaled => dyn_format!($channel, _16, Uscaled),
+            Repr::Iscaled => dyn_format!($channel, _16, Iscaled),
+            Repr::Uint => dyn_format!($channel, _16, Uint),
+            Repr::Int => dyn_format!($channel, _16, Int),
+        }
+    }};
     ($channel:ident, $size:ident, $repr:expr) => {{
         match $repr {
             Repr::Unorm => dyn_format!($channel, $size, Unorm),
@@ -239,20 +267,57 @@ macro_rules! dyn_format {
 }
 
 /// Attempts to load a Texture from an image.
+///
+/// 8 bit images are loaded with channel representation from `config.repr`.
+/// 16 bit PNG images are loaded into 16 bit normalized or integer formats.
+/// There are no sRGB formats with 16 bit channels,
+/// so with `Repr::Srgb` color channels of 16 bit images are converted to linear `Unorm`.
+/// HDR images are loaded into floating point formats with channel size from `config.float_repr`.
+///
+/// OpenEXR images are recognized when `config.format` is not set
+/// and are loaded into the same floating point formats as HDR images.
+/// Only single-part scanline images without compression are supported,
+/// e.g. images written by `ImageData::write_exr`.
 pub fn load_from_image(
     bytes: &[u8],
     config: ImageTextureConfig,
 ) -> Result<TextureBuilder<'static>, failure::Error> {
+    let (w, h, builder) = if config.format.is_none() && bytes.starts_with(&EXR_MAGIC) {
+        let (w, h, pixels) = decode_exr(bytes)?;
+        (w, h, float_builder(pixels, config.float_repr))
+    } else {
+        let image_format = config
+            .format
+            .map_or_else(|| image::guess_format(bytes), Ok)?;
+
+        match image_format {
+            ImageFormat::HDR => load_hdr(bytes, config.float_repr)?,
+            ImageFormat::PNG if is_png16(bytes)? => load_png16(bytes, &config)?,
+            _ => load_ldr(bytes, image_format, &config)?,
+        }
+    };
+
+    let (kind, layout) = config.kind.layout_and_kind(w, h);
+
+    Ok(builder
+        .with_data_width(layout.line_stride)
+        .with_data_height(layout.layer_stride)
+        .with_kind(kind)
+        .with_view_kind(config.kind.view_kind())
+        .with_sampler_info(config.sampler_info))
+}
+
+fn load_ldr(
+    bytes: &[u8],
+    image_format: ImageFormat,
+    config: &ImageTextureConfig,
+) -> Result<(u32, u32, TextureBuilder<'static>), failure::Error> {
     use gfx_hal::format::{Component, Swizzle};
     use image::{DynamicImage, GenericImageView};
 
-    let image_format = config
-        .format
-        .map_or_else(|| image::guess_format(bytes), |f| Ok(f))?;
     let image = image::load_from_memory_with_format(bytes, image_format)?;
 
     let (w, h) = image.dimensions();
-    let (kind, layout) = config.kind.layout_and_kind(w, h);
 
     let (vec, format, swizzle) = match image {
         DynamicImage::ImageLuma8(img) => (
@@ -287,12 +352,427 @@ pub fn load_from_image(
         ),
     };
 
-    Ok(TextureBuilder::new()
-        .with_raw_data(vec, format)
-        .with_swizzle(swizzle)
-        .with_data_width(layout.line_stride)
-        .with_data_height(layout.layer_stride)
-        .with_kind(kind)
-        .with_view_kind(config.kind.view_kind())
-        .with_sampler_info(config.sampler_info))
+    Ok((
+        w,
+        h,
+        TextureBuilder::new()
+            .with_raw_data(vec, format)
+            .with_swizzle(swizzle),
+    ))
+}
+
+fn load_hdr(
+    bytes: &[u8],
+    float_repr: FloatRepr,
+) -> Result<(u32, u32, TextureBuilder<'static>), failure::Error> {
+    let decoder = image::hdr::HDRDecoder::new(bytes)?;
+    let metadata = decoder.metadata();
+    let pixels = decoder
+        .read_image_hdr()?
+        .iter()
+        .map(|rgb| {
+            let [r, g, b] = rgb.data;
+            [r, g, b, 1.0]
+        })
+        .collect();
+
+    Ok((
+        metadata.width,
+        metadata.height,
+        float_builder(pixels, float_repr),
+    ))
+}
+
+/// Create builder with RGBA floating point data of the channel size from `float_repr`.
+fn float_builder(pixels: Vec<[f32; 4]>, float_repr: FloatRepr) -> TextureBuilder<'static> {
+    use pixel::Half;
+
+    match float_repr {
+        FloatRepr::Half => TextureBuilder::new().with_data(
+            pixels
+                .iter()
+                .map(|&[r, g, b, a]| pixel::Rgba16Float {
+                    repr: [
+                        Half::from_f32(r),
+                        Half::from_f32(g),
+                        Half::from_f32(b),
+                        Half::from_f32(a),
+                    ],
+                })
+                .collect::<Vec<_>>(),
+        ),
+        FloatRepr::Single => TextureBuilder::new().with_data(
+            pixels
+                .into_iter()
+                .map(|repr| pixel::Rgba32Float { repr })
+                .collect::<Vec<_>>(),
+        ),
+    }
+}
+
+/// Magic number at the start of OpenEXR files.
+const EXR_MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+
+/// Cursor over OpenEXR file.
+struct ExrReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ExrReader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], failure::Error> {
+        let bytes = self
+            .bytes
+            .get(self.pos..)
+            .and_then(|bytes| bytes.get(..len))
+            .ok_or_else(|| failure::format_err!("Unexpected end of OpenEXR file"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, failure::Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn i32(&mut self) -> Result<i32, failure::Error> {
+        let bytes = self.bytes(4)?;
+        Ok(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, failure::Error> {
+        let mut value = [0; 8];
+        value.copy_from_slice(self.bytes(8)?);
+        Ok(u64::from_le_bytes(value))
+    }
+
+    /// Read null-terminated string.
+    fn string(&mut self) -> Result<&'a [u8], failure::Error> {
+        let len = self.bytes[self.pos..]
+            .iter()
+            .position(|&byte| byte == 0)
+            .ok_or_else(|| failure::format_err!("Unexpected end of OpenEXR file"))?;
+        let string = self.bytes(len)?;
+        self.pos += 1;
+        Ok(string)
+    }
+}
+
+/// Channel of OpenEXR image.
+struct ExrChannel {
+    /// Index of RGBA component the channel is loaded into.
+    component: Option<usize>,
+    /// 0 for `u32`, 1 for half and 2 for single precision samples.
+    pixel_type: i32,
+}
+
+impl ExrChannel {
+    fn sample_size(&self) -> usize {
+        match self.pixel_type {
+            1 => 2,
+            _ => 4,
+        }
+    }
+
+    fn sample(&self, bytes: &[u8]) -> f32 {
+        match self.pixel_type {
+            0 => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32,
+            1 => pixel::Half(u16::from_le_bytes([bytes[0], bytes[1]])).to_f32(),
+            _ => f32::from_bits(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+        }
+    }
+}
+
+/// Decode uncompressed scanline OpenEXR image into RGBA pixels.
+/// Channels other than `R`, `G`, `B` and `A` are ignored.
+/// Missing color channels are zero and missing alpha is one.
+fn decode_exr(bytes: &[u8]) -> Result<(u32, u32, Vec<[f32; 4]>), failure::Error> {
+    use std::convert::TryFrom;
+
+    let mut reader = ExrReader { bytes, pos: 0 };
+    if reader.bytes(4)? != EXR_MAGIC {
+        failure::bail!("Not an OpenEXR file");
+    }
+    let version = reader.i32()?;
+    if version & 0xFF != 2 {
+        failure::bail!("Unsupported OpenEXR version {}", version & 0xFF);
+    }
+    // Tiled, deep and multi-part flags.
+    if version & 0x1A00 != 0 {
+        failure::bail!("Only scanline OpenEXR images are supported");
+    }
+
+    let mut channels = Vec::new();
+    let mut compression = None;
+    let mut window = None;
+    loop {
+        let name = reader.string()?;
+        if name.is_empty() {
+            break;
+        }
+        let _kind = reader.string()?;
+        let size = reader.i32()?;
+        if size < 0 {
+            failure::bail!("Invalid OpenEXR attribute size {}", size);
+        }
+        let mut value = ExrReader {
+            bytes: reader.bytes(size as usize)?,
+            pos: 0,
+        };
+        match name {
+            b"channels" => loop {
+                let name = value.string()?;
+                if name.is_empty() {
+                    break;
+                }
+                let pixel_type = value.i32()?;
+                value.bytes(4)?;
+                let sampling = (value.i32()?, value.i32()?);
+                if !(0..=2).contains(&pixel_type) {
+                    failure::bail!("Invalid OpenEXR pixel type {}", pixel_type);
+                }
+                if sampling != (1, 1) {
+                    failure::bail!("Subsampled OpenEXR channels are not supported");
+                }
+                let component = match name {
+                    b"R" => Some(0),
+                    b"G" => Some(1),
+                    b"B" => Some(2),
+                    b"A" => Some(3),
+                    _ => None,
+                };
+                channels.push(ExrChannel {
+                    component,
+                    pixel_type,
+                });
+            },
+            b"compression" => compression = Some(value.u8()?),
+            b"dataWindow" => {
+                window = Some([value.i32()?, value.i32()?, value.i32()?, value.i32()?]);
+            }
+            _ => {}
+        }
+    }
+
+    if channels.is_empty() {
+        failure::bail!("OpenEXR image has no channels");
+    }
+    match compression {
+        Some(0) => {}
+        Some(compression) => failure::bail!(
+            "OpenEXR compression {} is not supported. Only uncompressed images can be loaded",
+            compression
+        ),
+        None => failure::bail!("OpenEXR header has no compression attribute"),
+    }
+    let [min_x, min_y, max_x, max_y] =
+        window.ok_or_else(|| failure::format_err!("OpenEXR header has no data window"))?;
+    if max_x < min_x || max_y < min_y {
+        failure::bail!("Empty OpenEXR data window");
+    }
+    let width = (i64::from(max_x) - i64::from(min_x) + 1) as usize;
+    let height = (i64::from(max_y) - i64::from(min_y) + 1) as usize;
+
+    let line_size = channels
+        .iter()
+        .map(|channel| channel.sample_size())
+        .sum::<usize>()
+        .checked_mul(width)
+        .ok_or_else(|| failure::format_err!("OpenEXR image is too large"))?;
+    // Every pixel takes at least two bytes, so larger windows can't fit the file.
+    let pixel_count = width
+        .checked_mul(height)
+        .filter(|&count| count <= bytes.len())
+        .ok_or_else(|| failure::format_err!("OpenEXR image is too large"))?;
+
+    let mut pixels = vec![[0.0, 0.0, 0.0, 1.0]; pixel_count];
+
+    // Uncompressed images have one scanline per chunk.
+    let offsets = (0..height)
+        .map(|_| reader.u64())
+        .collect::<Result<Vec<_>, _>>()?;
+    for offset in offsets {
+        let mut chunk = ExrReader {
+            bytes,
+            pos: usize::try_from(offset)?,
+        };
+        let y = i64::from(chunk.i32()?) - i64::from(min_y);
+        let size = chunk.i32()?;
+        if y < 0 || y >= height as i64 || size as usize != line_size {
+            failure::bail!("Invalid OpenEXR scanline at offset {}", offset);
+        }
+        let line = &mut pixels[y as usize * width..][..width];
+        let mut data = chunk.bytes(line_size)?;
+        for channel in &channels {
+            let (samples, rest) = data.split_at(channel.sample_size() * width);
+            data = rest;
+            if let Some(component) = channel.component {
+                for (pixel, sample) in line
+                    .iter_mut()
+                    .zip(samples.chunks_exact(channel.sample_size()))
+                {
+                    pixel[component] = channel.sample(sample);
+                }
+            }
+        }
+    }
+
+    Ok((width as u32, height as u32, pixels))
+}
+
+fn is_png16(bytes: &[u8]) -> Result<bool, failure::Error> {
+    use image::{ColorType, ImageDecoder};
+
+    let decoder = image::png::PNGDecoder::new(bytes)?;
+    let bits = match decoder.colortype() {
+        ColorType::Gray(bits)
+        | ColorType::GrayA(bits)
+        | ColorType::RGB(bits)
+        | ColorType::RGBA(bits) => bits,
+        _ => return Ok(false),
+    };
+    Ok(bits == 16)
+}
+
+fn load_png16(
+    bytes: &[u8],
+    config: &ImageTextureConfig,
+) -> Result<(u32, u32, TextureBuilder<'static>), failure::Error> {
+    use gfx_hal::format::{Component, Swizzle};
+    use image::{ColorType, ImageDecoder};
+
+    let decoder = image::png::PNGDecoder::new(bytes)?;
+    let (w, h) = decoder.dimensions();
+    let color_type = decoder.colortype();
+    let mut vec = decoder.read_image()?;
+
+    // PNG stores 16 bit samples in big-endian byte order.
+    for sample in vec.chunks_exact_mut(2) {
+        let value = u16::from_be_bytes([sample[0], sample[1]]);
+        sample.copy_from_slice(&value.to_ne_bytes());
+    }
+
+    if config.repr == Repr::Srgb {
+        // Alpha is always linear.
+        let (channels, alpha) = match color_type {
+            ColorType::GrayA(16) => (2, Some(1)),
+            ColorType::RGB(16) => (3, None),
+            ColorType::RGBA(16) => (4, Some(3)),
+            _ => (1, None),
+        };
+        for (index, sample) in vec.chunks_exact_mut(2).enumerate() {
+            if Some(index % channels) != alpha {
+                let value = u16::from_ne_bytes([sample[0], sample[1]]);
+                let linear = srgb_to_linear(f64::from(value) / 65535.0);
+                sample.copy_from_slice(&((linear * 65535.0).round() as u16).to_ne_bytes());
+            }
+        }
+    }
+
+    let (format, swizzle) = match color_type {
+        ColorType::Gray(16) => (
+            dyn_format!(R, _16, config.repr),
+            Swizzle(Component::R, Component::R, Component::R, Component::One),
+        ),
+        ColorType::GrayA(16) => (
+            dyn_format!(Rg, _16, config.repr),
+            Swizzle(Component::R, Component::R, Component::R, Component::G),
+        ),
+        ColorType::RGB(16) => (dyn_format!(Rgb, _16, config.repr), Swizzle::NO),
+        ColorType::RGBA(16) => (dyn_format!(Rgba, _16, config.repr), Swizzle::NO),
+        color_type => failure::bail!("Unexpected PNG color type {:?}", color_type),
+    };
+
+    Ok((
+        w as u32,
+        h as u32,
+        TextureBuilder::new()
+            .with_raw_data(vec, format)
+            .with_swizzle(swizzle),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::ImageData,
+        gfx_hal::format::{Component, Format, Swizzle},
+    };
+
+    fn exr(width: u32, height: u32, format: Format, data: Vec<u8>, swizzle: Swizzle) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        ImageData {
+            width,
+            height,
+            depth: 1,
+            format,
+            swizzle,
+            data,
+        }
+        .write_exr(&mut bytes)
+        .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn exr_single_round_trip() {
+        let pixels: [[f32; 4]; 6] = [
+            [0.0, 0.5, 1.0, 1.0],
+            [2.0, -1.0, 1e-3, 0.25],
+            [65504.0, 3.5, 0.0, 0.0],
+            [1e6, 0.1, 0.2, 0.75],
+            [0.3, 0.4, 0.5, 0.5],
+            [7.0, 8.0, 9.0, 1.0],
+        ];
+        let data = pixels
+            .iter()
+            .flat_map(|pixel| {
+                pixel
+                    .iter()
+                    .flat_map(|value| value.to_bits().to_ne_bytes().to_vec())
+            })
+            .collect();
+        let bytes = exr(3, 2, Format::Rgba32Float, data, Swizzle::NO);
+
+        let (width, height, decoded) = decode_exr(&bytes).unwrap();
+        assert_eq!((width, height), (3, 2));
+        assert_eq!(decoded, pixels);
+    }
+
+    #[test]
+    fn exr_half_round_trip() {
+        // 8 bit data is written with half precision channels.
+        let data = vec![0, 51, 255, 255, 255, 0, 102, 0];
+        let swizzle = Swizzle(Component::R, Component::G, Component::B, Component::One);
+        let bytes = exr(2, 1, Format::Rgba8Unorm, data, swizzle);
+
+        let (width, height, decoded) = decode_exr(&bytes).unwrap();
+        assert_eq!((width, height), (2, 1));
+        let expected = [[0.0, 0.2, 1.0, 1.0], [1.0, 0.0, 0.4, 1.0]];
+        for (pixel, expected) in decoded.iter().zip(&expected) {
+            for (value, expected) in pixel.iter().zip(expected) {
+                assert!((value - expected).abs() < 1e-3, "{:?}", decoded);
+            }
+        }
+    }
+
+    #[test]
+    fn exr_rejects_compression() {
+        let mut bytes = exr(1, 1, Format::Rgba32Float, vec![0; 16], Swizzle::NO);
+        let name = b"compression\0compression\0";
+        let pos = bytes
+            .windows(name.len())
+            .position(|window| window == name)
+            .unwrap();
+        // Value follows the name, type and size of the attribute.
+        bytes[pos + name.len() + 4] = 3;
+        assert!(decode_exr(&bytes).is_err());
+    }
+
+    #[test]
+    fn exr_rejects_truncated() {
+        let bytes = exr(2, 2, Format::Rgba32Float, vec![0; 64], Swizzle::NO);
+        assert!(decode_exr(&bytes).is_ok());
+        assert!(decode_exr(&bytes[..bytes.len() - 1]).is_err());
+    }
 }
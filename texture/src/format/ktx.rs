@@ -0,0 +1,154 @@
+//! Module that turns a KTX2 container into a `Texture`
+
+use {
+    crate::{
+        texture::{align_up, level_size},
+        TextureBuilder,
+    },
+    gfx_hal::{
+        format::{Format, NUM_FORMATS},
+        image::{Kind, ViewKind},
+    },
+};
+
+const IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+/// Size of the header and index preceding level index.
+const HEADER_SIZE: usize = 80;
+
+/// Size of a single level index entry.
+const LEVEL_INDEX_ENTRY_SIZE: usize = 24;
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut value = [0; 4];
+    value.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(value)
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    let mut value = [0; 8];
+    value.copy_from_slice(&bytes[offset..offset + 8]);
+    u64::from_le_bytes(value)
+}
+
+/// Map `VkFormat` value to `Format`.
+fn vk_format(value: u32) -> Option<Format> {
+    if value == 0 || value as usize >= NUM_FORMATS {
+        None
+    } else {
+        // `Format` variants are declared in the same order as core `VkFormat` values,
+        // starting from 1, and `value` is within the range of declared variants.
+        Some(unsafe { std::mem::transmute::<u32, Format>(value) })
+    }
+}
+
+/// Attempts to load a Texture from a KTX2 container.
+///
+/// All mip levels, array layers and cube faces stored in the container are preserved.
+/// Supercompressed containers are not supported.
+pub fn load_from_ktx2(bytes: &[u8]) -> Result<TextureBuilder<'static>, failure::Error> {
+    if bytes.len() < HEADER_SIZE || bytes[..12] != IDENTIFIER {
+        failure::bail!("Not a KTX2 container");
+    }
+
+    let vk_format_value = read_u32(bytes, 12);
+    let width = read_u32(bytes, 20);
+    let height = read_u32(bytes, 24);
+    let depth = read_u32(bytes, 28);
+    let layers = read_u32(bytes, 32);
+    let faces = read_u32(bytes, 36);
+    // Zero means that mip levels should be generated at runtime.
+    let levels = read_u32(bytes, 40).max(1);
+    let supercompression = read_u32(bytes, 44);
+
+    if supercompression != 0 {
+        failure::bail!(
+            "KTX2 supercompression scheme {} is not supported",
+            supercompression
+        );
+    }
+
+    let format = vk_format(vk_format_value)
+        .ok_or_else(|| failure::format_err!("Unsupported VkFormat {}", vk_format_value))?;
+
+    if width == 0 {
+        failure::bail!("KTX2 image width must not be zero");
+    }
+    if faces != 1 && faces != 6 {
+        failure::bail!("Invalid KTX2 face count {}", faces);
+    }
+    if levels > 32 {
+        failure::bail!("Invalid KTX2 level count {}", levels);
+    }
+
+    let array_layers = layers.max(1) * faces;
+    if array_layers > u16::MAX as u32 {
+        failure::bail!("Too many KTX2 layers: {}", array_layers);
+    }
+    let array_layers = array_layers as u16;
+
+    let (kind, view_kind) = match (height, depth, layers, faces) {
+        (0, 0, 0, 1) => (Kind::D1(width, 1), ViewKind::D1),
+        (0, 0, _, 1) => (Kind::D1(width, array_layers), ViewKind::D1Array),
+        (_, 0, 0, 1) => (Kind::D2(width, height, 1, 1), ViewKind::D2),
+        (_, 0, _, 1) => (Kind::D2(width, height, array_layers, 1), ViewKind::D2Array),
+        (_, 0, 0, 6) => (Kind::D2(width, height, 6, 1), ViewKind::Cube),
+        (_, 0, _, 6) => (
+            Kind::D2(width, height, array_layers, 1),
+            ViewKind::CubeArray,
+        ),
+        (_, _, 0, 1) if height != 0 => (Kind::D3(width, height, depth), ViewKind::D3),
+        _ => failure::bail!(
+            "Unsupported KTX2 image: height {}, depth {}, layers {}, faces {}",
+            height,
+            depth,
+            layers,
+            faces
+        ),
+    };
+
+    if bytes.len() < HEADER_SIZE + levels as usize * LEVEL_INDEX_ENTRY_SIZE {
+        failure::bail!("KTX2 level index is truncated");
+    }
+
+    let mut data = Vec::new();
+    for level in 0..levels {
+        let entry = HEADER_SIZE + level as usize * LEVEL_INDEX_ENTRY_SIZE;
+        let offset = read_u64(bytes, entry);
+        let length = read_u64(bytes, entry + 8);
+        let expected = level_size(format, kind, level as u8)
+            .ok_or_else(|| failure::format_err!("KTX2 level {} is too large", level))?;
+
+        if length != expected as u64 {
+            failure::bail!(
+                "KTX2 level {} has size {} while {} expected",
+                level,
+                length,
+                expected
+            );
+        }
+
+        let level_data = offset
+            .checked_add(length)
+            .filter(|&end| end <= bytes.len() as u64)
+            .map(|end| &bytes[offset as usize..end as usize])
+            .ok_or_else(|| failure::format_err!("KTX2 level {} is out of bounds", level))?;
+
+        // Levels in KTX2 are stored layer by layer, then face by face, then slice by slice.
+        // That matches layout expected by `TextureBuilder`.
+        data.extend_from_slice(level_data);
+    }
+
+    let (block_width, block_height) = format.surface_desc().dim;
+    let extent = kind.extent();
+
+    Ok(TextureBuilder::new()
+        .with_raw_data(data, format)
+        .with_data_width(align_up(extent.width, block_width as u32))
+        .with_data_height(align_up(extent.height, block_height as u32))
+        .with_levels(levels as u8)
+        .with_kind(kind)
+        .with_view_kind(view_kind))
+}
@@ -0,0 +1,382 @@
+//! Module that turns an equirectangular panorama into a cube `Texture`
+
+use {
+    crate::{
+        format::image::{FloatRepr, ImageFormat},
+        pixel::{self, Half},
+        TextureBuilder,
+    },
+    derivative::Derivative,
+    std::f32::consts::PI,
+};
+
+/// Prefiltering of the cube mip chain for roughness-based sampling.
+/// Mip level `n` of `levels` is convolved with GGX distribution
+/// of roughness `n / (levels - 1)`.
+#[derive(Derivative, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derivative(Default)]
+pub struct PrefilterConfig {
+    /// Number of mip levels.
+    /// Clamped to number of levels that fits face size.
+    #[derivative(Default(value = "6"))]
+    pub levels: u8,
+
+    /// Number of samples per texel.
+    #[derivative(Default(value = "64"))]
+    pub samples: u32,
+}
+
+/// Config for loading cube texture from equirectangular panorama.
+#[derive(Derivative, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derivative(Default)]
+pub struct PanoramaTextureConfig {
+    /// Interpret the image as given format.
+    /// When `None`, format is determined automatically based on magic bytes.
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::format::image::serde_image_format")
+    )]
+    pub format: Option<ImageFormat>,
+
+    /// Width and height of each cube face.
+    #[derivative(Default(value = "256"))]
+    pub face_size: u32,
+
+    /// Channel size of the resulting texture.
+    pub float_repr: FloatRepr,
+
+    /// Generate prefiltered mip chain.
+    /// When `None` texture has single level.
+    pub prefilter: Option<PrefilterConfig>,
+
+    /// Sampler info for the texture.
+    #[derivative(Default(
+        value = "gfx_hal::image::SamplerInfo::new(gfx_hal::image::Filter::Linear, gfx_hal::image::WrapMode::Clamp)"
+    ))]
+    pub sampler_info: gfx_hal::image::SamplerInfo,
+}
+
+/// Linear RGBA image.
+struct Panorama {
+    width: u32,
+    height: u32,
+    pixels: Vec<[f32; 4]>,
+}
+
+impl Panorama {
+    fn load(bytes: &[u8], format: ImageFormat) -> Result<Self, failure::Error> {
+        match format {
+            ImageFormat::HDR => {
+                let decoder = image::hdr::HDRDecoder::new(bytes)?;
+                let metadata = decoder.metadata();
+                let pixels = decoder
+                    .read_image_hdr()?
+                    .into_iter()
+                    .map(|rgb| [rgb.data[0], rgb.data[1], rgb.data[2], 1.0])
+                    .collect();
+                Ok(Panorama {
+                    width: metadata.width,
+                    height: metadata.height,
+                    pixels,
+                })
+            }
+            format => {
+                let image = image::load_from_memory_with_format(bytes, format)?.to_rgba();
+                let (width, height) = image.dimensions();
+                let pixels = image
+                    .into_raw()
+                    .chunks_exact(4)
+                    .map(|rgba| {
+                        [
+                            srgb_to_linear(rgba[0]),
+                            srgb_to_linear(rgba[1]),
+                            srgb_to_linear(rgba[2]),
+                            rgba[3] as f32 / 255.0,
+                        ]
+                    })
+                    .collect();
+                Ok(Panorama {
+                    width,
+                    height,
+                    pixels,
+                })
+            }
+        }
+    }
+
+    fn texel(&self, x: u32, y: u32) -> [f32; 4] {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    /// Sample panorama in `direction` with bilinear filtering.
+    fn sample(&self, direction: [f32; 3]) -> [f32; 4] {
+        let [x, y, z] = direction;
+        let u = 0.5 + z.atan2(x) / (2.0 * PI);
+        let v = y.clamp(-1.0, 1.0).acos() / PI;
+
+        let fx = u * self.width as f32 - 0.5;
+        let fy = (v * self.height as f32 - 0.5).clamp(0.0, (self.height - 1) as f32);
+        let x0 = fx.floor();
+        let y0 = fy.floor();
+        let tx = fx - x0;
+        let ty = fy - y0;
+
+        // Wrap horizontally, clamp vertically.
+        let wrap = |x: f32| (x as i64).rem_euclid(self.width as i64) as u32;
+        let x0i = wrap(x0);
+        let x1i = wrap(x0 + 1.0);
+        let y0i = y0 as u32;
+        let y1i = (y0i + 1).min(self.height - 1);
+
+        let mut result = [0.0; 4];
+        for (c, value) in result.iter_mut().enumerate() {
+            let top = self.texel(x0i, y0i)[c] * (1.0 - tx) + self.texel(x1i, y0i)[c] * tx;
+            let bottom = self.texel(x0i, y1i)[c] * (1.0 - tx) + self.texel(x1i, y1i)[c] * tx;
+            *value = top * (1.0 - ty) + bottom * ty;
+        }
+        result
+    }
+
+    /// Downsample panorama by the factor of two with box filter.
+    fn downsample(&self) -> Self {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let x0 = (x * 2).min(self.width - 1);
+                let x1 = (x * 2 + 1).min(self.width - 1);
+                let y0 = (y * 2).min(self.height - 1);
+                let y1 = (y * 2 + 1).min(self.height - 1);
+                let mut texel = [0.0; 4];
+                for &(sx, sy) in &[(x0, y0), (x1, y0), (x0, y1), (x1, y1)] {
+                    let source = self.texel(sx, sy);
+                    for c in 0..4 {
+                        texel[c] += source[c] * 0.25;
+                    }
+                }
+                pixels.push(texel);
+            }
+        }
+        Panorama {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let value = value as f32 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn normalize([x, y, z]: [f32; 3]) -> [f32; 3] {
+    let len = (x * x + y * y + z * z).sqrt();
+    [x / len, y / len, z / len]
+}
+
+fn cross([ax, ay, az]: [f32; 3], [bx, by, bz]: [f32; 3]) -> [f32; 3] {
+    [ay * bz - az * by, az * bx - ax * bz, ax * by - ay * bx]
+}
+
+/// Direction through texel center `(x, y)` of cube `face` with `size`.
+/// Faces are in layer order `+X`, `-X`, `+Y`, `-Y`, `+Z`, `-Z`.
+fn face_direction(face: u32, x: u32, y: u32, size: u32) -> [f32; 3] {
+    let u = (x as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+    let v = (y as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+    normalize(match face {
+        0 => [1.0, -v, -u],
+        1 => [-1.0, -v, u],
+        2 => [u, 1.0, v],
+        3 => [u, -1.0, -v],
+        4 => [u, -v, 1.0],
+        _ => [-u, -v, -1.0],
+    })
+}
+
+/// Hammersley point `i` of `count`.
+fn hammersley(i: u32, count: u32) -> (f32, f32) {
+    (
+        i as f32 / count as f32,
+        i.reverse_bits() as f32 / 4_294_967_296.0,
+    )
+}
+
+/// Prefilter panorama with GGX distribution around `normal`.
+/// Assumes view direction equal to normal.
+fn prefilter(pyramid: &[Panorama], normal: [f32; 3], roughness: f32, samples: u32) -> [f32; 4] {
+    let alpha = roughness * roughness;
+    let alpha2 = alpha * alpha;
+
+    let up = if normal[1].abs() < 0.999 {
+        [0.0, 1.0, 0.0]
+    } else {
+        [1.0, 0.0, 0.0]
+    };
+    let tangent = normalize(cross(up, normal));
+    let bitangent = cross(normal, tangent);
+
+    let base = &pyramid[0];
+    let texel_solid_angle = 4.0 * PI / (base.width * base.height) as f32;
+
+    let mut color = [0.0; 4];
+    let mut total_weight = 0.0;
+
+    for i in 0..samples {
+        let (xi1, xi2) = hammersley(i, samples);
+        let phi = 2.0 * PI * xi1;
+        let cos_theta = ((1.0 - xi2) / (1.0 + (alpha2 - 1.0) * xi2)).sqrt();
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+
+        let h = [
+            tangent[0] * sin_theta * phi.cos()
+                + bitangent[0] * sin_theta * phi.sin()
+                + normal[0] * cos_theta,
+            tangent[1] * sin_theta * phi.cos()
+                + bitangent[1] * sin_theta * phi.sin()
+                + normal[1] * cos_theta,
+            tangent[2] * sin_theta * phi.cos()
+                + bitangent[2] * sin_theta * phi.sin()
+                + normal[2] * cos_theta,
+        ];
+
+        // Reflect view (normal) around half vector.
+        let n_dot_h = cos_theta;
+        let l = [
+            2.0 * n_dot_h * h[0] - normal[0],
+            2.0 * n_dot_h * h[1] - normal[1],
+            2.0 * n_dot_h * h[2] - normal[2],
+        ];
+        let n_dot_l = normal[0] * l[0] + normal[1] * l[1] + normal[2] * l[2];
+        if n_dot_l <= 0.0 {
+            continue;
+        }
+
+        // Pick source level by ratio of sample and texel solid angles
+        // to avoid aliasing on rough levels.
+        let d = alpha2 / (PI * (n_dot_h * n_dot_h * (alpha2 - 1.0) + 1.0).powi(2));
+        let pdf = d / 4.0;
+        let sample_solid_angle = 1.0 / (samples as f32 * pdf + 0.0001);
+        let level = if roughness == 0.0 {
+            0.0
+        } else {
+            (0.5 * (sample_solid_angle / texel_solid_angle).log2() + 1.0).max(0.0)
+        };
+        let level = (level.round() as usize).min(pyramid.len() - 1);
+
+        let sample = pyramid[level].sample(l);
+        for c in 0..4 {
+            color[c] += sample[c] * n_dot_l;
+        }
+        total_weight += n_dot_l;
+    }
+
+    for c in &mut color {
+        *c /= total_weight;
+    }
+    color
+}
+
+/// Attempts to load a cube Texture from an equirectangular panorama.
+///
+/// Panorama is sampled in linear space.
+/// HDR images are used as is, other images are decoded from sRGB.
+/// Resulting texture has floating point format with channel size from `config.float_repr`.
+pub fn load_cube_from_panorama(
+    bytes: &[u8],
+    config: PanoramaTextureConfig,
+) -> Result<TextureBuilder<'static>, failure::Error> {
+    if config.face_size == 0 {
+        failure::bail!("Cube face size must not be zero");
+    }
+
+    let image_format = config
+        .format
+        .map_or_else(|| image::guess_format(bytes), Ok)?;
+    let panorama = Panorama::load(bytes, image_format)?;
+
+    let max_levels = 32 - config.face_size.leading_zeros();
+    let (levels, samples) = match config.prefilter {
+        Some(prefilter_config) => (
+            (prefilter_config.levels as u32).clamp(1, max_levels),
+            prefilter_config.samples.max(1),
+        ),
+        None => (1, 0),
+    };
+
+    let mut pyramid = vec![panorama];
+    if levels > 1 {
+        loop {
+            let last = pyramid.last().expect("Pyramid is never empty");
+            if last.width <= 1 && last.height <= 1 {
+                break;
+            }
+            let next = last.downsample();
+            pyramid.push(next);
+        }
+    }
+
+    let mut texels = Vec::new();
+    for level in 0..levels {
+        let size = (config.face_size >> level).max(1);
+        let roughness = if levels > 1 {
+            level as f32 / (levels - 1) as f32
+        } else {
+            0.0
+        };
+        for face in 0..6 {
+            for y in 0..size {
+                for x in 0..size {
+                    let direction = face_direction(face, x, y, size);
+                    texels.push(if level == 0 {
+                        pyramid[0].sample(direction)
+                    } else {
+                        prefilter(&pyramid, direction, roughness, samples)
+                    });
+                }
+            }
+        }
+    }
+
+    let builder = match config.float_repr {
+        FloatRepr::Half => TextureBuilder::new().with_data(
+            texels
+                .iter()
+                .map(|&[r, g, b, a]| pixel::Rgba16Float {
+                    repr: [
+                        Half::from_f32(r),
+                        Half::from_f32(g),
+                        Half::from_f32(b),
+                        Half::from_f32(a),
+                    ],
+                })
+                .collect::<Vec<_>>(),
+        ),
+        FloatRepr::Single => TextureBuilder::new().with_data(
+            texels
+                .iter()
+                .map(|&repr| pixel::Rgba32Float { repr })
+                .collect::<Vec<_>>(),
+        ),
+    };
+
+    Ok(builder
+        .with_data_width(config.face_size)
+        .with_data_height(config.face_size)
+        .with_levels(levels as u8)
+        .with_kind(gfx_hal::image::Kind::D2(
+            config.face_size,
+            config.face_size,
+            6,
+            1,
+        ))
+        .with_view_kind(gfx_hal::image::ViewKind::Cube)
+        .with_sampler_info(config.sampler_info))
+}
@@ -12,13 +12,17 @@
     unused_qualifications
 )]
 
+use rendy_command as command;
 use rendy_factory as factory;
 use rendy_memory as memory;
 use rendy_resource as resource;
 use rendy_util as util;
 
+mod atlas;
+pub mod convert;
 mod format;
 pub mod pixel;
+mod readback;
 mod texture;
 
-pub use crate::{format::*, pixel::Rgba8Unorm, texture::*};
+pub use crate::{atlas::*, format::*, pixel::Rgba8Unorm, readback::*, texture::*};
@@ -34,6 +34,98 @@ pub struct Srgb;
 #[derive(Clone, Copy, Debug, Default)]
 pub struct Float;
 
+/// Half-precision floating point number.
+/// Stored as raw IEEE 754 binary16 bits.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct Half(pub u16);
+
+impl Half {
+    /// Convert single-precision value into half-precision,
+    /// rounding to nearest even.
+    /// Values out of range become infinities.
+    pub fn from_f32(value: f32) -> Self {
+        let bits = value.to_bits();
+        let sign = ((bits >> 16) & 0x8000) as u16;
+        let exp = ((bits >> 23) & 0xFF) as i32;
+        let man = bits & 0x7F_FFFF;
+
+        if exp == 0xFF {
+            // Infinity or NaN. Keep NaN quiet.
+            let nan = if man != 0 {
+                0x200 | (man >> 13) as u16
+            } else {
+                0
+            };
+            return Half(sign | 0x7C00 | nan);
+        }
+
+        let half_exp = exp - 127 + 15;
+        if half_exp >= 0x1F {
+            return Half(sign | 0x7C00);
+        }
+
+        if half_exp <= 0 {
+            if half_exp < -10 {
+                return Half(sign);
+            }
+            // Subnormal half. Add implicit bit and shift it into place.
+            let man = man | 0x80_0000;
+            let shift = (14 - half_exp) as u32;
+            let round_bit = 1 << (shift - 1);
+            let mut half_man = (man >> shift) as u16;
+            if man & round_bit != 0 && man & (3 * round_bit - 1) != 0 {
+                half_man += 1;
+            }
+            return Half(sign | half_man);
+        }
+
+        let half = sign | ((half_exp as u16) << 10) | (man >> 13) as u16;
+        let round_bit = 0x1000;
+        if man & round_bit != 0 && man & (3 * round_bit - 1) != 0 {
+            // Carry may overflow into exponent which correctly yields next power of two or infinity.
+            Half(half + 1)
+        } else {
+            Half(half)
+        }
+    }
+
+    /// Convert half-precision value into single-precision.
+    /// This conversion is lossless.
+    pub fn to_f32(self) -> f32 {
+        let sign = u32::from(self.0 & 0x8000) << 16;
+        let exp = u32::from(self.0 >> 10) & 0x1F;
+        let man = u32::from(self.0 & 0x3FF);
+
+        match (exp, man) {
+            (0, 0) => f32::from_bits(sign),
+            (0, man) => {
+                // Subnormal half is normal single.
+                let value = man as f32 * (1.0 / (1 << 24) as f32);
+                if sign != 0 {
+                    -value
+                } else {
+                    value
+                }
+            }
+            (0x1F, man) => f32::from_bits(sign | 0x7F80_0000 | (man << 13)),
+            (exp, man) => f32::from_bits(sign | ((exp + 112) << 23) | (man << 13)),
+        }
+    }
+}
+
+impl From<f32> for Half {
+    fn from(value: f32) -> Self {
+        Half::from_f32(value)
+    }
+}
+
+impl From<Half> for f32 {
+    fn from(value: Half) -> Self {
+        value.to_f32()
+    }
+}
+
 /// 8 bits marker type
 #[derive(Clone, Copy, Debug, Default)]
 pub struct _8;
@@ -102,6 +194,7 @@ impl_channel_repr! {
     Uscaled * _16 = u16;
     Iscaled * _16 = u16;
     Srgb * _16 = u16;
+    Float * _16 = Half;
 
     Unorm * _32 = u32;
     Inorm * _32 = u32;
@@ -258,7 +351,6 @@ macro_rules! impl_pixel {
 }
 
 // Actually implement AsPixel for all the formats
-// TODO: Implement AsPixel for the Float; they are commented out until then
 impl_pixel! {
     R8Unorm = R _8 Unorm;
     R8Inorm = R _8 Inorm;
@@ -315,28 +407,28 @@ impl_pixel! {
     R16Iscaled = R _16 Iscaled;
     R16Uint = R _16 Uint;
     R16Int = R _16 Int;
-    // R16Float = R _16 Float;
+    R16Float = R _16 Float;
     Rg16Unorm = Rg _16 Unorm;
     Rg16Inorm = Rg _16 Inorm;
     Rg16Uscaled = Rg _16 Uscaled;
     Rg16Iscaled = Rg _16 Iscaled;
     Rg16Uint = Rg _16 Uint;
     Rg16Int = Rg _16 Int;
-    // Rg16Float = Rg _16 Float;
+    Rg16Float = Rg _16 Float;
     Rgb16Unorm = Rgb _16 Unorm;
     Rgb16Inorm = Rgb _16 Inorm;
     Rgb16Uscaled = Rgb _16 Uscaled;
     Rgb16Iscaled = Rgb _16 Iscaled;
     Rgb16Uint = Rgb _16 Uint;
     Rgb16Int = Rgb _16 Int;
-    // Rgb16Float = Rgb _16 Float;
+    Rgb16Float = Rgb _16 Float;
     Rgba16Unorm = Rgba _16 Unorm;
     Rgba16Inorm = Rgba _16 Inorm;
     Rgba16Uscaled = Rgba _16 Uscaled;
     Rgba16Iscaled = Rgba _16 Iscaled;
     Rgba16Uint = Rgba _16 Uint;
     Rgba16Int = Rgba _16 Int;
-    // Rgba16Float = Rgba _16 Float;
+    Rgba16Float = Rgba _16 Float;
     R32Uint = R _32 Uint;
     R32Int = R _32 Int;
     R32Float = R _32 Float;
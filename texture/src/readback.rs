@@ -0,0 +1,306 @@
+//! Module for reading image content back from the device and saving it into image files
+use {
+    crate::{
+        command::Families,
+        convert::Converter,
+        factory::{Factory, ImageState},
+        resource::Image,
+        texture::Texture,
+    },
+    gfx_hal::{
+        format::{Format, Swizzle},
+        image, Backend,
+    },
+    std::io::Write,
+};
+
+/// Content of single layer and mip level read back from the device.
+#[derive(Clone, Debug)]
+pub struct ImageData {
+    /// Width of the image in texels.
+    pub width: u32,
+    /// Height of the image in texels.
+    pub height: u32,
+    /// Number of depth slices.
+    /// Slices are stacked vertically when saved into image files.
+    pub depth: u32,
+    /// Format of the data.
+    pub format: Format,
+    /// Swizzle applied when data is saved into image files,
+    /// so that files show the image the way it is sampled through the view.
+    pub swizzle: Swizzle,
+    /// Tightly packed texels.
+    pub data: Vec<u8>,
+}
+
+impl ImageData {
+    /// Read single layer and mip level of the image.
+    /// Data is returned in the format of the image.
+    ///
+    /// # Safety
+    ///
+    /// Image must be created by this `Factory` with `TRANSFER_SRC` usage.
+    /// `last` and `next` states must satisfy the requirements of `Factory::download_image`.
+    pub unsafe fn read<B>(
+        factory: &Factory<B>,
+        families: &mut Families<B>,
+        image: &Image<B>,
+        layer: image::Layer,
+        level: image::Level,
+        last: ImageState,
+        next: ImageState,
+    ) -> Result<Self, failure::Error>
+    where
+        B: Backend,
+    {
+        if !image.info().usage.contains(image::Usage::TRANSFER_SRC) {
+            failure::bail!("Image must have TRANSFER_SRC usage to be read back");
+        }
+        if layer >= image.kind().num_layers() {
+            failure::bail!(
+                "Layer {} is out of bounds. Image has {} layers",
+                layer,
+                image.kind().num_layers()
+            );
+        }
+        if level >= image.info().levels {
+            failure::bail!(
+                "Level {} is out of bounds. Image has {} levels",
+                level,
+                image.info().levels
+            );
+        }
+
+        let extent = image.kind().level_extent(level);
+        let data = factory.download_image(
+            families,
+            image,
+            image::SubresourceLayers {
+                aspects: image.format().surface_desc().aspects,
+                level,
+                layers: layer..layer + 1,
+            },
+            image::Offset::ZERO,
+            extent,
+            last,
+            next,
+        )?;
+
+        Ok(ImageData {
+            width: extent.width,
+            height: extent.height,
+            depth: extent.depth,
+            format: image.format(),
+            swizzle: Swizzle::NO,
+            data,
+        })
+    }
+
+    /// Convert data into another format.
+    pub fn convert(&self, format: Format) -> Result<Self, failure::Error> {
+        if format == self.format {
+            return Ok(self.clone());
+        }
+        let converter = Converter::new(self.format, format).ok_or_else(|| {
+            failure::format_err!("Can't convert image from {:?} to {:?}", self.format, format)
+        })?;
+        Ok(self.converted(&converter))
+    }
+
+    fn converted(&self, converter: &Converter) -> Self {
+        ImageData {
+            format: converter.dst(),
+            data: converter.convert(&self.data),
+            ..*self
+        }
+    }
+
+    /// Write image in PNG format.
+    ///
+    /// Normalized formats are written as RGBA with 8 bit channels,
+    /// or 16 bit channels if 8 bits can't hold all values.
+    /// Values are written as is, without color space conversion.
+    /// `swizzle` is applied to the values.
+    #[cfg(feature = "image")]
+    pub fn write_png(&self, writer: impl Write) -> Result<(), failure::Error> {
+        let eight_bit = match self.format.base_format().1 {
+            gfx_hal::format::ChannelType::Srgb => Format::Rgba8Srgb,
+            _ => Format::Rgba8Unorm,
+        };
+
+        let (bits, data) = match Converter::new(self.format, eight_bit) {
+            Some(converter) if converter.is_lossless() => {
+                (8, converter.convert_swizzled(&self.data, self.swizzle))
+            }
+            _ => {
+                let converter =
+                    Converter::new(self.format, Format::Rgba16Unorm).ok_or_else(|| {
+                        failure::format_err!(
+                            "Image with format {:?} can't be saved as PNG",
+                            self.format
+                        )
+                    })?;
+                // PNG stores samples in big-endian order.
+                let data = converter
+                    .convert_swizzled(&self.data, self.swizzle)
+                    .chunks_exact(2)
+                    .flat_map(|sample| u16::from_ne_bytes([sample[0], sample[1]]).to_be_bytes())
+                    .collect();
+                (16, data)
+            }
+        };
+
+        ::image::png::PNGEncoder::new(writer).encode(
+            &data,
+            self.width,
+            self.height * self.depth,
+            ::image::ColorType::RGBA(bits),
+        )?;
+        Ok(())
+    }
+
+    /// Write image in OpenEXR format.
+    ///
+    /// Image is written as uncompressed RGBA scanlines
+    /// with half or single precision floating point channels
+    /// depending on precision of the data.
+    /// `swizzle` is applied to the values.
+    pub fn write_exr(&self, mut writer: impl Write) -> Result<(), failure::Error> {
+        let (format, pixel_type, sample_size) =
+            match Converter::new(self.format, Format::Rgba16Float) {
+                Some(converter) if converter.is_lossless() => (Format::Rgba16Float, 1u32, 2),
+                _ => (Format::Rgba32Float, 2u32, 4),
+            };
+        let converter = Converter::new(self.format, format).ok_or_else(|| {
+            failure::format_err!("Image with format {:?} can't be saved as EXR", self.format)
+        })?;
+        let image = ImageData {
+            format,
+            swizzle: Swizzle::NO,
+            data: converter.convert_swizzled(&self.data, self.swizzle),
+            ..*self
+        };
+
+        let width = image.width as usize;
+        let height = (image.height * image.depth) as usize;
+
+        let mut header = Vec::new();
+        header.extend_from_slice(&0x0131_2f76u32.to_le_bytes());
+        header.extend_from_slice(&2u32.to_le_bytes());
+
+        let mut channels = Vec::new();
+        // Channels must be sorted by name.
+        for name in &[b"A", b"B", b"G", b"R"] {
+            channels.extend_from_slice(*name);
+            channels.push(0);
+            channels.extend_from_slice(&pixel_type.to_le_bytes());
+            // Perceptually linear flag and reserved bytes.
+            channels.extend_from_slice(&[0; 4]);
+            channels.extend_from_slice(&1i32.to_le_bytes());
+            channels.extend_from_slice(&1i32.to_le_bytes());
+        }
+        channels.push(0);
+        exr_attribute(&mut header, "channels", "chlist", &channels);
+
+        exr_attribute(&mut header, "compression", "compression", &[0]);
+        let mut window = Vec::new();
+        for value in &[0, 0, width as i32 - 1, height as i32 - 1] {
+            window.extend_from_slice(&value.to_le_bytes());
+        }
+        exr_attribute(&mut header, "dataWindow", "box2i", &window);
+        exr_attribute(&mut header, "displayWindow", "box2i", &window);
+        exr_attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+        exr_attribute(
+            &mut header,
+            "pixelAspectRatio",
+            "float",
+            &1.0f32.to_le_bytes(),
+        );
+        exr_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+        exr_attribute(
+            &mut header,
+            "screenWindowWidth",
+            "float",
+            &1.0f32.to_le_bytes(),
+        );
+        header.push(0);
+
+        let line_size = width * 4 * sample_size;
+        let chunk_size = 8 + line_size;
+        let table_size = height * 8;
+        let chunks_start = header.len() + table_size;
+        for y in 0..height {
+            let offset = (chunks_start + y * chunk_size) as u64;
+            header.extend_from_slice(&offset.to_le_bytes());
+        }
+        writer.write_all(&header)?;
+
+        let mut line = Vec::with_capacity(chunk_size);
+        for (y, texels) in image.data.chunks_exact(width * 4 * sample_size).enumerate() {
+            line.clear();
+            line.extend_from_slice(&(y as i32).to_le_bytes());
+            line.extend_from_slice(&(line_size as i32).to_le_bytes());
+            for &component in &[3, 2, 1, 0] {
+                for texel in texels.chunks_exact(4 * sample_size) {
+                    let sample = &texel[component * sample_size..(component + 1) * sample_size];
+                    // Samples are stored in little-endian order.
+                    match sample_size {
+                        2 => line.extend_from_slice(
+                            &u16::from_ne_bytes([sample[0], sample[1]]).to_le_bytes(),
+                        ),
+                        _ => line.extend_from_slice(
+                            &u32::from_ne_bytes([sample[0], sample[1], sample[2], sample[3]])
+                                .to_le_bytes(),
+                        ),
+                    }
+                }
+            }
+            writer.write_all(&line)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn exr_attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+impl<B> Texture<B>
+where
+    B: Backend,
+{
+    /// Read single layer and mip level of the texture.
+    ///
+    /// If texture data was converted to another format by `TextureBuilder::build`
+    /// the conversion is reversed and data is returned in `source_format`.
+    /// Data is returned as it was provided, with swizzle of the texture view
+    /// stored in `ImageData::swizzle` to be applied when saved into image files.
+    ///
+    /// # Safety
+    ///
+    /// Texture must be created by this `Factory`
+    /// with `TRANSFER_SRC` usage requested by `TextureBuilder::with_usage`.
+    /// `last` and `next` states must satisfy the requirements of `Factory::download_image`.
+    pub unsafe fn read(
+        &self,
+        factory: &Factory<B>,
+        families: &mut Families<B>,
+        layer: image::Layer,
+        level: image::Level,
+        last: ImageState,
+        next: ImageState,
+    ) -> Result<ImageData, failure::Error> {
+        let mut data = ImageData::read(factory, families, self.image(), layer, level, last, next)?;
+        data.swizzle = self.view().info().swizzle;
+        Ok(match self.converter() {
+            Some(converter) => data.converted(&converter.inverse()),
+            None => data,
+        })
+    }
+}
@@ -1,16 +1,18 @@
 //! Module for creating a `Texture` from an image
 use {
     crate::{
-        factory::{Factory, ImageState},
+        convert::{conversion_targets, Converter},
+        factory::{Factory, ImageState, ImageStateOrLayout},
         memory::Data,
         pixel::AsPixel,
         resource::{Escape, Handle, Image, ImageInfo, ImageView, ImageViewInfo, Sampler},
         util::cast_cow,
     },
     gfx_hal::{
-        format::{Component, Format, Swizzle},
+        format::{Aspects, Format, Swizzle},
         image, Backend,
     },
+    std::ops::Range,
 };
 
 /// Static image.
@@ -19,7 +21,10 @@ use {
 pub struct Texture<B: Backend> {
     image: Handle<Image<B>>,
     view: Escape<ImageView<B>>,
+    extra_views: Vec<Escape<ImageView<B>>>,
     sampler: Handle<Sampler<B>>,
+    source_format: Format,
+    converter: Option<Converter>,
 }
 
 impl<B> Texture<B>
@@ -45,6 +50,257 @@ where
     pub fn view_mut(&mut self) -> &mut ImageView<B> {
         &mut self.view
     }
+
+    /// Get reference to additional image view
+    /// in order views were added to `TextureBuilder`.
+    pub fn extra_view(&self, index: usize) -> Option<&ImageView<B>> {
+        self.extra_views.get(index).map(|view| &**view)
+    }
+
+    /// Get number of additional image views.
+    pub fn extra_views_count(&self) -> usize {
+        self.extra_views.len()
+    }
+
+    /// Get conversion applied to the pixel data texture was built from.
+    pub(crate) fn converter(&self) -> Option<&Converter> {
+        self.converter.as_ref()
+    }
+
+    /// Get format of the pixel data texture was built from.
+    /// Data for updates must be provided in this format.
+    pub fn source_format(&self) -> Format {
+        self.source_format
+    }
+
+    /// Update rectangular region of a single layer and mip level of the texture.
+    ///
+    /// `data` must contain tightly packed pixels of the `rect` in the `source_format`.
+    /// It is converted to the image format the same way `TextureBuilder::build` does.
+    /// For block-compressed formats `rect` must be aligned to whole blocks,
+    /// except for blocks on the edge of the level.
+    /// All depth slices are updated for 3D textures.
+    ///
+    /// # Safety
+    ///
+    /// Texture must be created by this `Factory`.
+    /// `last_state` and `next_state` must satisfy the requirements of `Factory::upload_image`.
+    // Arguments follow `Factory::upload_image`.
+    #[allow(clippy::too_many_arguments)]
+    pub unsafe fn update_region(
+        &self,
+        factory: &Factory<B>,
+        layer: image::Layer,
+        level: image::Level,
+        rect: gfx_hal::pso::Rect,
+        data: &[u8],
+        last_state: impl Into<ImageStateOrLayout>,
+        next_state: ImageState,
+    ) -> Result<(), failure::Error> {
+        if layer >= self.image.kind().num_layers() {
+            failure::bail!(
+                "Layer {} is out of bounds. Texture has {} layers",
+                layer,
+                self.image.kind().num_layers()
+            );
+        }
+        if rect.x < 0 || rect.y < 0 || rect.w <= 0 || rect.h <= 0 {
+            failure::bail!("Invalid update region {:?}", rect);
+        }
+
+        self.update(
+            factory,
+            layer..layer + 1,
+            level,
+            (rect.x as u32, rect.y as u32),
+            (rect.w as u32, rect.h as u32),
+            data,
+            last_state.into(),
+            next_state,
+        )
+    }
+
+    /// Update all layers of a single mip level of the texture.
+    /// Useful for progressive streaming of mip levels.
+    ///
+    /// `data` must contain the level in the `source_format`
+    /// using the same layout as a single level of `TextureBuilder` data.
+    ///
+    /// # Safety
+    ///
+    /// Texture must be created by this `Factory`.
+    /// `last_state` and `next_state` must satisfy the requirements of `Factory::upload_image`.
+    pub unsafe fn update_level(
+        &self,
+        factory: &Factory<B>,
+        level: image::Level,
+        data: &[u8],
+        last_state: impl Into<ImageStateOrLayout>,
+        next_state: ImageState,
+    ) -> Result<(), failure::Error> {
+        let kind = self.image.kind();
+        let extent = kind.level_extent(level);
+        self.update(
+            factory,
+            0..kind.num_layers(),
+            level,
+            (0, 0),
+            (extent.width, extent.height),
+            data,
+            last_state.into(),
+            next_state,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    unsafe fn update(
+        &self,
+        factory: &Factory<B>,
+        layers: Range<image::Layer>,
+        level: image::Level,
+        (x, y): (u32, u32),
+        (width, height): (u32, u32),
+        data: &[u8],
+        last_state: ImageStateOrLayout,
+        next_state: ImageState,
+    ) -> Result<(), failure::Error> {
+        let info = self.image.info();
+        if level >= info.levels {
+            failure::bail!(
+                "Level {} is out of bounds. Texture has {} levels",
+                level,
+                info.levels
+            );
+        }
+
+        let extent = info.kind.level_extent(level);
+        if x + width > extent.width || y + height > extent.height {
+            failure::bail!(
+                "Region {}x{} at ({}, {}) exceeds level {} extent {}x{}",
+                width,
+                height,
+                x,
+                y,
+                level,
+                extent.width,
+                extent.height
+            );
+        }
+
+        let (block_width, block_height) = info.format.surface_desc().dim;
+        let (block_width, block_height) = (block_width as u32, block_height as u32);
+        if x % block_width != 0
+            || y % block_height != 0
+            || (width % block_width != 0 && x + width != extent.width)
+            || (height % block_height != 0 && y + height != extent.height)
+        {
+            failure::bail!(
+                "Region {}x{} at ({}, {}) is not aligned to {}x{} blocks of {:?}",
+                width,
+                height,
+                x,
+                y,
+                block_width,
+                block_height,
+                info.format
+            );
+        }
+
+        let data_width = align_up(width, block_width);
+        let data_height = align_up(height, block_height);
+        let expected = (data_width / block_width) as usize
+            * (data_height / block_height) as usize
+            * extent.depth as usize
+            * (layers.end - layers.start) as usize
+            * (self.source_format.surface_desc().bits as usize / 8);
+        if data.len() != expected {
+            failure::bail!(
+                "Update data has size {} while {} expected",
+                data.len(),
+                expected
+            );
+        }
+
+        let converted;
+        let data = match &self.converter {
+            None => data,
+            Some(converter) => {
+                converted = converter.convert(data);
+                &converted
+            }
+        };
+
+        factory.upload_image(
+            &self.image,
+            data_width,
+            data_height,
+            image::SubresourceLayers {
+                aspects: info.format.surface_desc().aspects,
+                level,
+                layers,
+            },
+            image::Offset {
+                x: x as i32,
+                y: y as i32,
+                z: 0,
+            },
+            image::Extent {
+                width,
+                height,
+                depth: extent.depth,
+            },
+            data,
+            last_state,
+            next_state,
+        )
+    }
+}
+
+/// Description of additional view of the texture image.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TextureViewDesc {
+    /// Kind of the view.
+    pub view_kind: image::ViewKind,
+    /// Aspects of the view.
+    /// When `None` aspects of the main view are used.
+    pub aspects: Option<Aspects>,
+    /// Mip levels of the view.
+    /// When `None` all levels are included.
+    pub levels: Option<Range<image::Level>>,
+    /// Layers of the view.
+    /// When `None` all layers are included.
+    pub layers: Option<Range<image::Layer>>,
+}
+
+impl TextureViewDesc {
+    /// View of all levels and layers of the image.
+    pub fn new(view_kind: image::ViewKind) -> Self {
+        TextureViewDesc {
+            view_kind,
+            aspects: None,
+            levels: None,
+            layers: None,
+        }
+    }
+
+    /// With specific aspects.
+    pub fn with_aspects(mut self, aspects: Aspects) -> Self {
+        self.aspects = Some(aspects);
+        self
+    }
+
+    /// With range of mip levels.
+    pub fn with_levels(mut self, levels: Range<image::Level>) -> Self {
+        self.levels = Some(levels);
+        self
+    }
+
+    /// With range of layers.
+    pub fn with_layers(mut self, layers: Range<image::Layer>) -> Self {
+        self.layers = Some(layers);
+        self
+    }
 }
 
 /// Generics-free texture builder.
@@ -58,8 +314,13 @@ pub struct TextureBuilder<'a> {
     data: std::borrow::Cow<'a, [u8]>,
     data_width: u32,
     data_height: u32,
+    levels: image::Level,
     sampler_info: gfx_hal::image::SamplerInfo,
     swizzle: Swizzle,
+    usage: image::Usage,
+    view_caps: image::ViewCapabilities,
+    view_aspects: Option<Aspects>,
+    extra_views: Vec<TextureViewDesc>,
 }
 
 impl<'a> TextureBuilder<'a> {
@@ -72,11 +333,16 @@ impl<'a> TextureBuilder<'a> {
             data: std::borrow::Cow::Borrowed(&[]),
             data_width: 0,
             data_height: 0,
+            levels: 1,
             sampler_info: gfx_hal::image::SamplerInfo::new(
                 gfx_hal::image::Filter::Linear,
                 gfx_hal::image::WrapMode::Clamp,
             ),
             swizzle: Swizzle::NO,
+            usage: image::Usage::empty(),
+            view_caps: image::ViewCapabilities::empty(),
+            view_aspects: None,
+            extra_views: Vec::new(),
         }
     }
 
@@ -117,6 +383,22 @@ impl<'a> TextureBuilder<'a> {
         self
     }
 
+    /// Set format without providing pixel data.
+    pub fn with_format(mut self, format: Format) -> Self {
+        self.set_format(format);
+        self
+    }
+
+    /// Set format without providing pixel data.
+    ///
+    /// Image built without pixel data is not uploaded,
+    /// it is transitioned into `next_state` passed to `build` with undefined content.
+    pub fn set_format(&mut self, format: Format) -> &mut Self {
+        self.data = std::borrow::Cow::Borrowed(&[]);
+        self.format = format;
+        self
+    }
+
     /// Set pixel data width.
     pub fn with_data_width(mut self, data_width: u32) -> Self {
         self.set_data_width(data_width);
@@ -141,6 +423,24 @@ impl<'a> TextureBuilder<'a> {
         self
     }
 
+    /// Set number of mip levels in pixel data.
+    pub fn with_levels(mut self, levels: image::Level) -> Self {
+        self.set_levels(levels);
+        self
+    }
+
+    /// Set number of mip levels in pixel data.
+    ///
+    /// Levels are stored one after another starting from the largest one.
+    /// Each level contains all layers tightly packed with its extent rounded up to whole blocks.
+    /// `data_width` and `data_height` are only used for the first level
+    /// when there are more than one.
+    pub fn set_levels(&mut self, levels: image::Level) -> &mut Self {
+        assert!(levels > 0, "Texture must have at least one level");
+        self.levels = levels;
+        self
+    }
+
     /// Set image extent.
     pub fn with_kind(mut self, kind: image::Kind) -> Self {
         self.set_kind(kind);
@@ -189,11 +489,97 @@ impl<'a> TextureBuilder<'a> {
         self
     }
 
+    /// With additional image usage.
+    pub fn with_usage(mut self, usage: image::Usage) -> Self {
+        self.set_usage(usage);
+        self
+    }
+
+    /// Set additional image usage.
+    /// `SAMPLED` and `TRANSFER_DST` are always requested.
+    /// `TRANSFER_SRC` is required to read texture back with `Texture::read`.
+    pub fn set_usage(&mut self, usage: image::Usage) -> &mut Self {
+        self.usage = usage;
+        self
+    }
+
+    /// With additional image view capabilities.
+    pub fn with_view_caps(mut self, view_caps: image::ViewCapabilities) -> Self {
+        self.set_view_caps(view_caps);
+        self
+    }
+
+    /// Set additional image view capabilities.
+    /// Capabilities required by view kind are always requested.
+    pub fn set_view_caps(&mut self, view_caps: image::ViewCapabilities) -> &mut Self {
+        self.view_caps = view_caps;
+        self
+    }
+
+    /// With aspects of the main view.
+    pub fn with_view_aspects(mut self, aspects: Aspects) -> Self {
+        self.set_view_aspects(aspects);
+        self
+    }
+
+    /// Set aspects of the main view.
+    /// By default view has all aspects of the format,
+    /// except for combined depth-stencil formats where only depth is viewed.
+    pub fn set_view_aspects(&mut self, aspects: Aspects) -> &mut Self {
+        self.view_aspects = Some(aspects);
+        self
+    }
+
+    /// With additional view of the image.
+    pub fn with_view(mut self, view: TextureViewDesc) -> Self {
+        self.add_view(view);
+        self
+    }
+
+    /// Add additional view of the image.
+    /// For example view of a single layer or a single mip level.
+    pub fn add_view(&mut self, view: TextureViewDesc) -> &mut Self {
+        self.extra_views.push(view);
+        self
+    }
+
+    /// Get pixel data.
+    pub(crate) fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Get format of pixel data.
+    pub(crate) fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Get swizzle of the image view.
+    pub(crate) fn swizzle(&self) -> Swizzle {
+        self.swizzle
+    }
+
+    /// Get pixel data width.
+    pub(crate) fn data_width(&self) -> u32 {
+        self.data_width
+    }
+
+    /// Get image kind.
+    pub(crate) fn kind(&self) -> image::Kind {
+        self.kind
+    }
+
+    /// Get number of mip levels in pixel data.
+    pub(crate) fn levels(&self) -> image::Level {
+        self.levels
+    }
+
     /// Build texture.
     ///
     /// ## Parameters
     /// * `next_state`: The next state that this texture will be used in.
     ///     It will get transitioned to this state after uploading.
+    ///     Texture without pixel data is transitioned to this state as well,
+    ///     leaving its content undefined.
     /// * `factory`: Factory to use to build the texture
     pub fn build<B>(
         &self,
@@ -203,23 +589,44 @@ impl<'a> TextureBuilder<'a> {
     where
         B: Backend,
     {
-        let view_caps = match self.view_kind {
-            gfx_hal::image::ViewKind::D2Array => gfx_hal::image::ViewCapabilities::KIND_2D_ARRAY,
-            gfx_hal::image::ViewKind::Cube | gfx_hal::image::ViewKind::CubeArray => {
-                gfx_hal::image::ViewCapabilities::KIND_CUBE
+        let view_caps = Some(self.view_kind)
+            .into_iter()
+            .chain(self.extra_views.iter().map(|view| view.view_kind))
+            .fold(self.view_caps, |caps, view_kind| {
+                caps | match view_kind {
+                    image::ViewKind::D2Array => image::ViewCapabilities::KIND_2D_ARRAY,
+                    image::ViewKind::Cube | image::ViewKind::CubeArray => {
+                        image::ViewCapabilities::KIND_CUBE
+                    }
+                    _ => image::ViewCapabilities::empty(),
+                }
+            });
+
+        let format_aspects = self.format.surface_desc().aspects;
+        let view_aspects = self.view_aspects.unwrap_or_else(|| {
+            if format_aspects.contains(Aspects::DEPTH | Aspects::STENCIL) {
+                Aspects::DEPTH
+            } else {
+                format_aspects
             }
-            _ => gfx_hal::image::ViewCapabilities::empty(),
-        };
+        });
 
-        let (info, transform, transform_swizzle) = find_compatible_format(
+        if !self.data.is_empty() && format_aspects.contains(Aspects::DEPTH | Aspects::STENCIL) {
+            failure::bail!(
+                "Uploading pixel data to combined depth-stencil format {:?} is not supported",
+                self.format
+            );
+        }
+
+        let (info, converter) = find_compatible_format(
             factory,
             ImageInfo {
                 kind: self.kind,
-                levels: 1,
+                levels: self.levels,
                 format: self.format,
                 tiling: gfx_hal::image::Tiling::Optimal,
                 view_caps,
-                usage: gfx_hal::image::Usage::SAMPLED | gfx_hal::image::Usage::TRANSFER_DST,
+                usage: self.usage | image::Usage::SAMPLED | image::Usage::TRANSFER_DST,
             },
         )
         .ok_or_else(|| {
@@ -231,42 +638,73 @@ impl<'a> TextureBuilder<'a> {
 
         let image: Handle<Image<B>> = factory.create_image(info, Data)?.into();
 
-        let mut transformed_vec: Vec<u8> = Vec::new();
+        let converted;
+        let buffer: &[u8] = match &converter {
+            None => &self.data,
+            Some(converter) => {
+                converted = converter.convert(&self.data);
+                &converted
+            }
+        };
 
-        let buffer: &[u8] = match transform {
-            BufferTransform::Intact => &self.data,
-            BufferTransform::AddPadding { stride, padding } => {
-                transformed_vec.reserve_exact(self.data.len() / stride * (stride + padding));
-                transformed_vec.extend(self.data.chunks_exact(stride).flat_map(|chunk| {
-                    chunk
-                        .iter()
-                        .cloned()
-                        .chain(std::iter::repeat(0).take(padding))
-                }));
+        let mut offset = 0usize;
+        // Images without data are not uploaded.
+        let upload_levels = if buffer.is_empty() { 0 } else { self.levels };
+        for level in 0..upload_levels {
+            let extent = self.kind.level_extent(level);
+            let (data_width, data_height, data) = if self.levels == 1 {
+                (self.data_width, self.data_height, buffer)
+            } else {
+                let (block_width, block_height) = info.format.surface_desc().dim;
+                let data = level_size(info.format, self.kind, level)
+                    .and_then(|size| buffer.get(offset..offset.checked_add(size)?))
+                    .ok_or_else(|| {
+                        failure::format_err!("Not enough pixel data for level {}", level)
+                    })?;
+                let size = data.len();
+                offset += size;
+                (
+                    align_up(extent.width, block_width as u32),
+                    align_up(extent.height, block_height as u32),
+                    data,
+                )
+            };
 
-                &transformed_vec
+            // The reason that factory.upload_image is unsafe is that the image being uploaded
+            // must have been created by the same factory and that it is not in use; we guarantee
+            // that here because we just created the image on the same factory right before.
+            unsafe {
+                factory.upload_image(
+                    &image,
+                    data_width,
+                    data_height,
+                    image::SubresourceLayers {
+                        aspects: self.format.surface_desc().aspects,
+                        level,
+                        layers: 0..self.kind.num_layers(),
+                    },
+                    image::Offset::ZERO,
+                    extent,
+                    data,
+                    image::Layout::Undefined,
+                    next_state,
+                )?;
             }
-        };
+        }
 
-        // The reason that factory.upload_image is unsafe is that the image being uploaded
-        // must have been created by the same factory and that it is not in use; we guarantee
-        // that here because we just created the image on the same factory right before.
-        unsafe {
-            factory.upload_image(
-                &image,
-                self.data_width,
-                self.data_height,
-                image::SubresourceLayers {
-                    aspects: self.format.surface_desc().aspects,
-                    level: 0,
-                    layers: 0..self.kind.num_layers(),
-                },
-                image::Offset::ZERO,
-                self.kind.extent(),
-                buffer,
-                image::Layout::Undefined,
-                next_state,
-            )?;
+        if upload_levels == 0 {
+            // Same as above, the image was just created on this factory and is not in use yet.
+            unsafe {
+                factory.transition_image(
+                    &image,
+                    image::SubresourceRange {
+                        aspects: format_aspects,
+                        levels: 0..self.levels,
+                        layers: 0..self.kind.num_layers(),
+                    },
+                    next_state,
+                )?;
+            }
         }
 
         let view = factory.create_image_view(
@@ -274,193 +712,93 @@ impl<'a> TextureBuilder<'a> {
             ImageViewInfo {
                 view_kind: self.view_kind,
                 format: info.format,
-                swizzle: double_swizzle(self.swizzle, transform_swizzle),
+                swizzle: self.swizzle,
                 range: image::SubresourceRange {
-                    aspects: self.format.surface_desc().aspects,
-                    levels: 0..1,
+                    aspects: view_aspects,
+                    levels: 0..self.levels,
                     layers: 0..self.kind.num_layers(),
                 },
             },
         )?;
 
+        let extra_views = self
+            .extra_views
+            .iter()
+            .map(|view| {
+                factory.create_image_view(
+                    image.clone(),
+                    ImageViewInfo {
+                        view_kind: view.view_kind,
+                        format: info.format,
+                        swizzle: self.swizzle,
+                        range: image::SubresourceRange {
+                            aspects: view.aspects.unwrap_or(view_aspects),
+                            levels: view.levels.clone().unwrap_or(0..self.levels),
+                            layers: view.layers.clone().unwrap_or(0..self.kind.num_layers()),
+                        },
+                    },
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
         let sampler = factory.get_sampler(self.sampler_info.clone())?;
 
         Ok(Texture {
             image,
             view,
+            extra_views,
             sampler,
+            source_format: self.format,
+            converter,
         })
     }
 }
 
-enum BufferTransform {
-    Intact,
-    AddPadding { stride: usize, padding: usize },
+/// Size in bytes of the tightly packed mip `level` of image with `format` and `kind`,
+/// including all layers.
+/// Returns `None` if size doesn't fit into `usize`.
+pub(crate) fn level_size(format: Format, kind: image::Kind, level: image::Level) -> Option<usize> {
+    let desc = format.surface_desc();
+    let extent = kind.level_extent(level);
+    let blocks = |size: u32, block: u8| {
+        let block = u32::from(block);
+        u64::from(size / block) + u64::from(size % block != 0)
+    };
+    let size = blocks(extent.width, desc.dim.0)
+        .checked_mul(blocks(extent.height, desc.dim.1))?
+        .checked_mul(u64::from(extent.depth))?
+        .checked_mul(u64::from(kind.num_layers()))?
+        .checked_mul(u64::from(desc.bits / 8))?;
+    std::convert::TryFrom::try_from(size).ok()
 }
 
-fn double_swizzle(src: Swizzle, overlay: Swizzle) -> Swizzle {
-    fn pick_component(src: Swizzle, component: Component) -> Component {
-        let Swizzle(r, g, b, a) = src;
-        match component {
-            Component::R => r,
-            Component::G => g,
-            Component::B => b,
-            Component::A => a,
-            Component::Zero => Component::Zero,
-            Component::One => Component::One,
-        }
-    }
-    Swizzle(
-        pick_component(src, overlay.0),
-        pick_component(src, overlay.1),
-        pick_component(src, overlay.2),
-        pick_component(src, overlay.3),
-    )
+pub(crate) fn align_up(value: u32, align: u32) -> u32 {
+    (value + align - 1) / align * align
 }
 
 fn find_compatible_format<B: Backend>(
     factory: &Factory<B>,
     info: ImageInfo,
-) -> Option<(ImageInfo, BufferTransform, Swizzle)> {
-    if let Some(info) = image_format_supported(factory, info) {
-        return Some((info, BufferTransform::Intact, Swizzle::NO));
-    }
-    if let Some((format, transform, swizzle)) = expand_format_channels(info.format) {
-        let mut new_info = info.clone();
-        new_info.format = format;
-        if let Some(new_info) = image_format_supported(factory, new_info) {
+) -> Option<(ImageInfo, Option<Converter>)> {
+    if let Some(info) = factory.supported_image_info(info) {
+        return Some((info, None));
+    }
+
+    for converter in conversion_targets(info.format) {
+        let mut new_info = info;
+        new_info.format = converter.dst();
+        if let Some(new_info) = factory.supported_image_info(new_info) {
             log::trace!("Converting image from {:?} to {:?}", info, new_info);
-            return Some((new_info, transform, swizzle));
+            if !converter.is_lossless() {
+                log::warn!(
+                    "Format {:?} is not supported. Converting to {:?} loses precision",
+                    info.format,
+                    new_info.format
+                );
+            }
+            return Some((new_info, Some(converter)));
         }
     }
 
     None
 }
-
-fn expand_format_channels(format: Format) -> Option<(Format, BufferTransform, Swizzle)> {
-    let t2to4_8 = BufferTransform::AddPadding {
-        stride: 2,
-        padding: 2,
-    };
-
-    let t2to4_16 = BufferTransform::AddPadding {
-        stride: 4,
-        padding: 4,
-    };
-
-    let t2to4_32 = BufferTransform::AddPadding {
-        stride: 8,
-        padding: 8,
-    };
-
-    let t3to4_8 = BufferTransform::AddPadding {
-        stride: 3,
-        padding: 1,
-    };
-
-    let t3to4_16 = BufferTransform::AddPadding {
-        stride: 6,
-        padding: 2,
-    };
-
-    let t3to4_32 = BufferTransform::AddPadding {
-        stride: 12,
-        padding: 4,
-    };
-
-    let intact = BufferTransform::Intact;
-
-    let rgzo = Swizzle(Component::R, Component::G, Component::Zero, Component::One);
-    let rgbo = Swizzle(Component::R, Component::G, Component::B, Component::One);
-    let bgro = Swizzle(Component::B, Component::G, Component::R, Component::One);
-    let bgra = Swizzle(Component::B, Component::G, Component::R, Component::A);
-
-    Some(match format {
-        // Destination formats chosen according to this table
-        // https://vulkan.gpuinfo.org/listformats.php
-        Format::Rg8Unorm => (Format::Rgba8Unorm, t2to4_8, rgzo),
-        Format::Rg8Inorm => (Format::Rgba8Inorm, t2to4_8, rgzo),
-        Format::Rg8Uscaled => (Format::Rgba8Uscaled, t2to4_8, rgzo),
-        Format::Rg8Iscaled => (Format::Rgba8Iscaled, t2to4_8, rgzo),
-        Format::Rg8Uint => (Format::Rgba8Uint, t2to4_8, rgzo),
-        Format::Rg8Int => (Format::Rgba8Int, t2to4_8, rgzo),
-        Format::Rg8Srgb => (Format::Rgba8Srgb, t2to4_8, rgzo),
-
-        Format::Rgb8Unorm => (Format::Rgba8Unorm, t3to4_8, rgbo),
-        Format::Rgb8Inorm => (Format::Rgba8Inorm, t3to4_8, rgbo),
-        Format::Rgb8Uscaled => (Format::Rgba8Uscaled, t3to4_8, rgbo),
-        Format::Rgb8Iscaled => (Format::Rgba8Iscaled, t3to4_8, rgbo),
-        Format::Rgb8Uint => (Format::Rgba8Uint, t3to4_8, rgbo),
-        Format::Rgb8Int => (Format::Rgba8Int, t3to4_8, rgbo),
-        Format::Rgb8Srgb => (Format::Rgba8Srgb, t3to4_8, rgbo),
-
-        Format::Bgr8Unorm => (Format::Rgba8Unorm, t3to4_8, bgro),
-        Format::Bgr8Inorm => (Format::Rgba8Inorm, t3to4_8, bgro),
-        Format::Bgr8Uscaled => (Format::Rgba8Uscaled, t3to4_8, bgro),
-        Format::Bgr8Iscaled => (Format::Rgba8Iscaled, t3to4_8, bgro),
-        Format::Bgr8Uint => (Format::Rgba8Uint, t3to4_8, bgro),
-        Format::Bgr8Int => (Format::Rgba8Int, t3to4_8, bgro),
-        Format::Bgr8Srgb => (Format::Rgba8Srgb, t3to4_8, bgro),
-
-        Format::Bgra8Unorm => (Format::Rgba8Unorm, intact, bgra),
-        Format::Bgra8Inorm => (Format::Rgba8Inorm, intact, bgra),
-        Format::Bgra8Uscaled => (Format::Rgba8Uscaled, intact, bgra),
-        Format::Bgra8Iscaled => (Format::Rgba8Iscaled, intact, bgra),
-        Format::Bgra8Uint => (Format::Rgba8Uint, intact, bgra),
-        Format::Bgra8Int => (Format::Rgba8Int, intact, bgra),
-        Format::Bgra8Srgb => (Format::Rgba8Srgb, intact, bgra),
-
-        Format::Rg16Unorm => (Format::Rgba16Unorm, t2to4_16, rgzo),
-        Format::Rg16Inorm => (Format::Rgba16Inorm, t2to4_16, rgzo),
-        Format::Rg16Uscaled => (Format::Rgba16Uscaled, t2to4_16, rgzo),
-        Format::Rg16Iscaled => (Format::Rgba16Iscaled, t2to4_16, rgzo),
-        Format::Rg16Uint => (Format::Rgba16Uint, t2to4_16, rgzo),
-        Format::Rg16Int => (Format::Rgba16Int, t2to4_16, rgzo),
-        Format::Rg16Float => (Format::Rgba16Float, t2to4_16, rgzo),
-
-        Format::Rgb16Unorm => (Format::Rgba16Unorm, t3to4_16, rgbo),
-        Format::Rgb16Inorm => (Format::Rgba16Inorm, t3to4_16, rgbo),
-        Format::Rgb16Uscaled => (Format::Rgba16Uscaled, t3to4_16, rgbo),
-        Format::Rgb16Iscaled => (Format::Rgba16Iscaled, t3to4_16, rgbo),
-        Format::Rgb16Uint => (Format::Rgba16Uint, t3to4_16, rgbo),
-        Format::Rgb16Int => (Format::Rgba16Int, t3to4_16, rgbo),
-        Format::Rgb16Float => (Format::Rgba16Float, t3to4_16, rgbo),
-
-        Format::Rg32Uint => (Format::Rgba32Uint, t2to4_32, rgzo),
-        Format::Rg32Int => (Format::Rgba32Int, t2to4_32, rgzo),
-        Format::Rg32Float => (Format::Rgba32Float, t2to4_32, rgzo),
-
-        Format::Rgb32Uint => (Format::Rgba32Uint, t3to4_32, rgbo),
-        Format::Rgb32Int => (Format::Rgba32Int, t3to4_32, rgbo),
-        Format::Rgb32Float => (Format::Rgba32Float, t3to4_32, rgbo),
-        // TODO: add more conversions
-        _ => return None,
-    })
-}
-
-fn image_format_supported<B: Backend>(
-    factory: &Factory<B>,
-    mut info: ImageInfo,
-) -> Option<ImageInfo> {
-    factory
-        .image_format_properties(info)
-        .filter(|props| {
-            props.max_layers >= info.kind.num_layers()
-                && props.max_extent.width >= info.kind.extent().width
-                && props.max_extent.height >= info.kind.extent().height
-                && props.max_extent.depth >= info.kind.extent().depth
-        })
-        .map(|props| {
-            match &mut info.kind {
-                image::Kind::D2(_, _, _, s) if *s & props.sample_count_mask != *s => {
-                    let mut new_samples = *s >> 1;
-                    while new_samples > 1 && new_samples & props.sample_count_mask != new_samples {
-                        new_samples = new_samples >> 1;
-                    }
-                    *s = new_samples;
-                }
-                _ => {}
-            };
-            info
-        })
-}
@@ -185,10 +185,33 @@ where
     }
 
     /// Cast surface into render target.
+    /// Surface ideal format is used.
     pub unsafe fn into_target(
+        self,
+        physical_device: &B::PhysicalDevice,
+        device: &Device<B>,
+        image_count: u32,
+        present_mode: gfx_hal::PresentMode,
+        usage: gfx_hal::image::Usage,
+    ) -> Result<Target<B>, failure::Error> {
+        let format = self.format(physical_device);
+        self.into_target_with_format(
+            physical_device,
+            device,
+            format,
+            image_count,
+            present_mode,
+            usage,
+        )
+    }
+
+    /// Cast surface into render target with specified format.
+    /// `format` must be supported by the surface.
+    pub unsafe fn into_target_with_format(
         mut self,
         physical_device: &B::PhysicalDevice,
         device: &Device<B>,
+        format: gfx_hal::format::Format,
         image_count: u32,
         present_mode: gfx_hal::PresentMode,
         usage: gfx_hal::image::Usage,
@@ -203,6 +226,7 @@ where
             &mut self,
             physical_device,
             device,
+            format,
             image_count,
             present_mode,
             usage,
@@ -214,6 +238,7 @@ where
             surface: self,
             swapchain: Some(swapchain),
             backbuffer: Some(backbuffer),
+            format,
             present_mode,
             usage,
         })
@@ -229,6 +254,7 @@ unsafe fn create_swapchain<B: Backend>(
     surface: &mut Surface<B>,
     physical_device: &B::PhysicalDevice,
     device: &Device<B>,
+    format: gfx_hal::format::Format,
     image_count: u32,
     present_mode: gfx_hal::PresentMode,
     usage: gfx_hal::image::Usage,
@@ -250,20 +276,17 @@ unsafe fn create_swapchain<B: Backend>(
         present_mode
     );
 
-    let formats = formats.unwrap();
-
-    let format = *formats
-        .iter()
-        .max_by_key(|format| {
-            let base = format.base_format();
-            let desc = base.0.desc();
-            (
-                !desc.is_compressed(),
-                base.1 == gfx_hal::format::ChannelType::Srgb,
-                desc.bits,
-            )
-        })
-        .unwrap();
+    // `None` means that surface supports any format.
+    if let Some(formats) = &formats {
+        if !formats.contains(&format) {
+            log::warn!(
+                "Format is not supported. Supported: {:#?}, requested: {:#?}",
+                formats,
+                format,
+            );
+            failure::bail!("Format not supported.");
+        }
+    }
 
     log::info!("Surface formats: {:#?}. Pick {:#?}", formats, format);
 
@@ -390,6 +413,7 @@ pub struct Target<B: Backend> {
     surface: Surface<B>,
     swapchain: Option<B::Swapchain>,
     backbuffer: Option<Backbuffer<B>>,
+    format: gfx_hal::format::Format,
     present_mode: gfx_hal::PresentMode,
     usage: gfx_hal::image::Usage,
     relevant: relevant::Relevant,
@@ -474,6 +498,7 @@ where
             &mut self.surface,
             physical_device,
             device,
+            self.format,
             image_count as u32,
             self.present_mode,
             self.usage,